@@ -7,7 +7,12 @@ pub async fn get_settings(config_manager: State<'_, ConfigManager>) -> Result<Ap
     Ok(config_manager.get_config())
 }
 
+// Recent projects and trusted paths are kept as they are: they grant access
+// to files, so only the workspace trust commands may change them
 #[command]
-pub async fn update_settings(config_manager: State<'_, ConfigManager>, config: AppConfig) -> Result<(), String> {
+pub async fn update_settings(config_manager: State<'_, ConfigManager>, mut config: AppConfig) -> Result<(), String> {
+    let current = config_manager.get_config();
+    config.recent_projects = current.recent_projects;
+    config.workspace = current.workspace;
     config_manager.update_config(config)
 } 
\ No newline at end of file
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EditorConfig {
@@ -35,12 +35,77 @@ impl Default for EditorConfig {
     }
 }
 
+// Git settings that take precedence over the user's git configuration
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GitConfig {
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+}
+
+// File explorer and file watcher settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FilesConfig {
+    // Globs of files and folders to leave out of the explorer and the
+    // watcher, relative to the workspace root
+    pub exclude: Vec<String>,
+    // List ignored and excluded entries, flagged as such, instead of hiding them
+    pub show_ignored: bool,
+    // Poll for changes instead of relying on native notifications, for
+    // filesystems that do not deliver them
+    pub watcher_polling: bool,
+    pub watcher_poll_interval_ms: u64,
+    // Most directories a single native watch may cover. Each one uses an
+    // inotify watch, of which the system has a limited number.
+    pub watcher_max_directories: usize,
+    // Encoding assumed for files that are neither UTF-8 nor UTF-16, either
+    // "windows-1254" or "iso-8859-9"
+    pub fallback_encoding: String,
+    // Files larger than this many bytes open in the read-only large file
+    // viewer, which reads them a range of lines at a time
+    pub large_file_threshold: u64,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        FilesConfig {
+            exclude: vec![
+                "**/.git".into(),
+                "**/.svn".into(),
+                "**/.hg".into(),
+                "**/.DS_Store".into(),
+                "**/Thumbs.db".into(),
+            ],
+            show_ignored: false,
+            watcher_polling: false,
+            watcher_poll_interval_ms: 2000,
+            watcher_max_directories: 65536,
+            fallback_encoding: "windows-1254".into(),
+            large_file_threshold: 16 * 1024 * 1024,
+        }
+    }
+}
+
+// Paths outside opened workspaces that the user allowed the IDE to access.
+// Only changed through the workspace trust commands.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WorkspaceConfig {
+    pub trusted_paths: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AppConfig {
     pub language: String,
     pub show_welcome_page: bool,
     pub recent_projects: Vec<String>,
     pub editor: EditorConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    #[serde(default)]
+    pub files: FilesConfig,
+    #[serde(default)]
+    pub workspace: WorkspaceConfig,
 }
 
 impl Default for AppConfig {
@@ -50,6 +115,9 @@ impl Default for AppConfig {
             show_welcome_page: true,
             recent_projects: Vec::new(),
             editor: EditorConfig::default(),
+            git: GitConfig::default(),
+            files: FilesConfig::default(),
+            workspace: WorkspaceConfig::default(),
         }
     }
 }
@@ -136,4 +204,34 @@ impl ConfigManager {
 
         Ok(())
     }
-} 
\ No newline at end of file
+
+    pub fn remove_recent_project(&self, path: &Path) -> Result<(), String> {
+        let mut config = self.config.lock().unwrap();
+        let path_str = path.to_string_lossy().to_string();
+        config.recent_projects.retain(|p| p != &path_str);
+        self.write_config(&config)
+    }
+
+    pub fn add_trusted_path(&self, path: &Path) -> Result<(), String> {
+        let mut config = self.config.lock().unwrap();
+        let path_str = path.to_string_lossy().to_string();
+        if !config.workspace.trusted_paths.contains(&path_str) {
+            config.workspace.trusted_paths.push(path_str);
+        }
+        self.write_config(&config)
+    }
+
+    pub fn remove_trusted_path(&self, path: &Path) -> Result<(), String> {
+        let mut config = self.config.lock().unwrap();
+        let path_str = path.to_string_lossy().to_string();
+        config.workspace.trusted_paths.retain(|p| p != &path_str);
+        self.write_config(&config)
+    }
+
+    fn write_config(&self, config: &AppConfig) -> Result<(), String> {
+        let config_str = serde_json::to_string_pretty(config)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        fs::write(&self.config_path, config_str)
+            .map_err(|e| format!("Failed to write config file: {}", e))
+    }
+}
\ No newline at end of file
@@ -1,12 +1,14 @@
 pub mod commands;
 pub mod syntax;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use serde::Serialize;
+use crate::editor::syntax::get_language_id_from_path;
 
 // Represents an open document in the editor
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Document {
     pub path: Option<PathBuf>,
     pub language_id: String,
@@ -37,7 +39,16 @@ impl Document {
     }
 }
 
+// An open document that followed its file to a new path
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentMove {
+    pub old_uri: String,
+    pub new_uri: String,
+    pub path: String,
+}
+
 // Manages open documents in the editor
+#[derive(Clone)]
 pub struct DocumentManager {
     documents: Arc<Mutex<HashMap<String, Document>>>,
 }
@@ -71,6 +82,80 @@ impl DocumentManager {
         }
     }
     
+    // Files behind the open documents, leaving out unsaved buffers
+    pub fn open_paths(&self) -> Vec<PathBuf> {
+        let documents = self.documents.lock().unwrap();
+        documents.values().filter_map(|doc| doc.path.clone()).collect()
+    }
+    
+    // The open document backed by `path`, with its uri
+    pub fn find_by_path(&self, path: &Path) -> Option<(String, Document)> {
+        let documents = self.documents.lock().unwrap();
+        documents
+            .iter()
+            .find(|(_, doc)| doc.path.as_deref() == Some(path))
+            .map(|(uri, doc)| (uri.clone(), doc.clone()))
+    }
+    
+    pub fn mark_saved(&self, uri: &str) -> Result<(), String> {
+        let mut documents = self.documents.lock().unwrap();
+        if let Some(doc) = documents.get_mut(uri) {
+            doc.save();
+            Ok(())
+        } else {
+            Err(format!("Document not found: {}", uri))
+        }
+    }
+    
+    // Point documents at `from`, or below it when it is a directory, to the
+    // same files under `to`. Documents keyed by their path or `file://` uri
+    // are re-keyed.
+    pub fn rename_path(&self, from: &Path, to: &Path) -> Vec<DocumentMove> {
+        let mut documents = self.documents.lock().unwrap();
+        let moved: Vec<(String, PathBuf, PathBuf)> = documents
+            .iter()
+            .filter_map(|(uri, doc)| {
+                let old_path = doc.path.as_ref()?;
+                let relative = old_path.strip_prefix(from).ok()?;
+                let new_path = if relative.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(relative)
+                };
+                Some((uri.clone(), old_path.clone(), new_path))
+            })
+            .collect();
+        
+        let mut moves = Vec::new();
+        for (uri, old_path, new_path) in moved {
+            let mut doc = match documents.remove(&uri) {
+                Some(doc) => doc,
+                None => continue,
+            };
+            let old_path_str = old_path.to_string_lossy().to_string();
+            let new_path_str = new_path.to_string_lossy().to_string();
+            let new_uri = if uri == old_path_str {
+                new_path_str.clone()
+            } else if uri == format!("file://{}", old_path_str) {
+                format!("file://{}", new_path_str)
+            } else {
+                uri.clone()
+            };
+            
+            if let Some(language_id) = get_language_id_from_path(&new_path_str) {
+                doc.language_id = language_id;
+            }
+            doc.path = Some(new_path);
+            documents.insert(new_uri.clone(), doc);
+            moves.push(DocumentMove {
+                old_uri: uri,
+                new_uri,
+                path: new_path_str,
+            });
+        }
+        moves
+    }
+    
     pub fn close_document(&self, uri: &str) -> Result<(), String> {
         let mut documents = self.documents.lock().unwrap();
         if documents.remove(uri).is_some() {
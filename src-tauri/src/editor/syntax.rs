@@ -43,5 +43,5 @@ pub fn get_language_id_from_path(path: &str) -> Option<String> {
     language_map.insert("yml", "yaml");
     
     // Get language ID from map
-    language_map.get(extension.as_ref()).map(|&s| s.to_string())
+    language_map.get(extension.as_str()).map(|&s| s.to_string())
 } 
\ No newline at end of file
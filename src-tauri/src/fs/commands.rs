@@ -1,38 +1,275 @@
-use std::fs;
 use std::path::Path;
-use tauri::command;
+use tauri::{command, State};
+use crate::config::ConfigManager;
+use crate::editor::DocumentManager;
 use crate::fs::FileInfo;
+use crate::fs::encoding::{self, TextEncoding, TextFile, TextFormat, WriteOptions};
+use crate::fs::ignores::IgnoreEngine;
+use crate::fs::large_file::{self, FileContents, HexPreview, LineChunk, LineIndexStore, LineStart};
+use crate::fs::listing::{list_directory, FileListPage, ListOptions};
+use crate::fs::operations::{FileOperation, FileOperations, OperationResult};
+use crate::fs::save::{self, SaveError, SaveOptions, SaveResult};
+use crate::fs::watcher::{FileWatcher, WatchInfo};
+use crate::utils::find_project_root;
+use crate::workspace::WorkspaceTrust;
 
+// Text in a legacy encoding or UTF-16 is decoded as well
 #[command]
-pub async fn read_file(path: &str) -> Result<String, String> {
-    fs::read_to_string(path).map_err(|e| e.to_string())
+pub async fn read_file(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: &str,
+) -> Result<String, String> {
+    read_text_file(config_manager, trust, path).await.map(|file| file.content)
 }
 
+// Keeps the encoding, BOM and line endings the file had
 #[command]
-pub async fn write_file(path: &str, contents: &str) -> Result<(), String> {
-    fs::write(path, contents).map_err(|e| e.to_string())
+pub async fn write_file(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: &str,
+    contents: &str,
+) -> Result<(), String> {
+    write_text_file(config_manager, trust, path, contents, None).await.map(|_| ())
 }
 
+// The text of a file with the encoding, BOM and line endings it uses
 #[command]
-pub async fn list_files(path: &str) -> Result<Vec<FileInfo>, String> {
+pub async fn read_text_file(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: &str,
+) -> Result<TextFile, String> {
+    trust.check("read_text_file", Path::new(path))?;
+    encoding::read_text_file(Path::new(path), fallback_encoding(&config_manager))
+}
+
+#[command]
+pub async fn write_text_file(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: &str,
+    contents: &str,
+    options: Option<WriteOptions>,
+) -> Result<TextFormat, String> {
+    trust.check("write_text_file", Path::new(path))?;
+    let options = options.unwrap_or_default();
+    encoding::write_text_file(Path::new(path), contents, &options, fallback_encoding(&config_manager))
+}
+
+// Replaces the file atomically. With an expected version, a file changed
+// on disk since is reported as a `changed_on_disk` error instead of being
+// overwritten.
+#[command]
+pub async fn save_file(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: &str,
+    contents: &str,
+    options: Option<SaveOptions>,
+) -> Result<SaveResult, SaveError> {
+    trust.check("save_file", Path::new(path))?;
+    let options = options.unwrap_or_default();
+    save::save_file(Path::new(path), contents, &options, fallback_encoding(&config_manager))
+}
+
+// Small text files come with their text. Files above
+// `files.large_file_threshold` are described for the large file viewer and
+// binary files come with a hex preview of their start.
+#[command]
+pub async fn open_file(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    line_indexes: State<'_, LineIndexStore>,
+    path: String,
+) -> Result<FileContents, String> {
+    trust.check("open_file", Path::new(&path))?;
+    let threshold = config_manager.get_config().files.large_file_threshold;
+    let fallback = fallback_encoding(&config_manager);
+    let line_indexes = line_indexes.inner().clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        large_file::open_file(Path::new(&path), &line_indexes, threshold, fallback)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Lines of a large file, from a line number or the line holding a byte offset
+#[command]
+pub async fn read_file_lines(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    line_indexes: State<'_, LineIndexStore>,
+    path: String,
+    start: LineStart,
+    count: usize,
+) -> Result<LineChunk, String> {
+    trust.check("read_file_lines", Path::new(&path))?;
+    let fallback = fallback_encoding(&config_manager);
+    let line_indexes = line_indexes.inner().clone();
+
+    tauri::async_runtime::spawn_blocking(move || line_indexes.read_lines(Path::new(&path), start, count, fallback))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn read_file_hex(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    offset: u64,
+    length: usize,
+) -> Result<HexPreview, String> {
+    trust.check("read_file_hex", Path::new(&path))?;
+    large_file::read_hex(Path::new(&path), offset, length)
+}
+
+fn fallback_encoding(config_manager: &ConfigManager) -> TextEncoding {
+    encoding::fallback_encoding(&config_manager.get_config().files)
+}
+
+// Ignored entries are left out unless `files.show_ignored` is set, in
+// which case they are flagged so the explorer can dim them. With a depth
+// above 1, subdirectories come with their children.
+#[command]
+pub async fn list_files(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: &str,
+    depth: Option<usize>,
+) -> Result<Vec<FileInfo>, String> {
+    let options = ListOptions {
+        depth: depth.unwrap_or(1),
+        ..ListOptions::default()
+    };
+    list_files_page(config_manager, trust, path, Some(options))
+        .await
+        .map(|page| page.entries)
+}
+
+// A page of a directory listing, for directories too large to list at once
+#[command]
+pub async fn list_files_page(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: &str,
+    options: Option<ListOptions>,
+) -> Result<FileListPage, String> {
     let path = Path::new(path);
-    
-    if !path.exists() {
-        return Err(format!("Path does not exist: {}", path.display()));
-    }
-    
-    if !path.is_dir() {
-        return Err(format!("Path is not a directory: {}", path.display()));
-    }
-    
-    let entries = fs::read_dir(path)
+    trust.check("list_files", path)?;
+    let settings = config_manager.get_config().files;
+    let mut options = options.unwrap_or_default();
+    options.show_ignored |= settings.show_ignored;
+
+    let root = find_project_root(path).unwrap_or_else(|| path.to_path_buf());
+    let ignores = IgnoreEngine::new(&root, &settings.exclude);
+
+    list_directory(path, &ignores, &options)
+}
+
+// Changes below `path` are reported in batches as `fs-event` events
+#[command]
+pub async fn watch_path(
+    watcher: State<'_, FileWatcher>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+) -> Result<WatchInfo, String> {
+    trust.check("watch_path", Path::new(&path))?;
+    // Counting the directories to watch walks the whole tree
+    let watcher = watcher.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || watcher.watch(Path::new(&path)))
+        .await
         .map_err(|e| e.to_string())?
-        .filter_map(Result::ok)
-        .map(|entry| {
-            let path = entry.path();
-            FileInfo::from_path(&path).map_err(|e| e.to_string())
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    
-    Ok(entries)
-} 
\ No newline at end of file
+}
+
+#[command]
+pub async fn unwatch_path(watcher: State<'_, FileWatcher>, path: String) -> Result<(), String> {
+    watcher.unwatch(Path::new(&path))
+}
+
+#[command]
+pub async fn create_file(
+    operations: State<'_, FileOperations>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+) -> Result<OperationResult, String> {
+    trust.check("create_file", Path::new(&path))?;
+    operations.create_file(Path::new(&path))
+}
+
+#[command]
+pub async fn create_directory(
+    operations: State<'_, FileOperations>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+) -> Result<OperationResult, String> {
+    trust.check("create_directory", Path::new(&path))?;
+    operations.create_directory(Path::new(&path))
+}
+
+// Open documents follow the renamed file
+#[command]
+pub async fn rename_path(
+    operations: State<'_, FileOperations>,
+    documents: State<'_, DocumentManager>,
+    trust: State<'_, WorkspaceTrust>,
+    from: String,
+    to: String,
+) -> Result<OperationResult, String> {
+    trust.check("rename_path", Path::new(&from))?;
+    trust.check("rename_path", Path::new(&to))?;
+    operations.rename(Path::new(&from), Path::new(&to), &documents)
+}
+
+#[command]
+pub async fn move_path(
+    operations: State<'_, FileOperations>,
+    documents: State<'_, DocumentManager>,
+    trust: State<'_, WorkspaceTrust>,
+    from: String,
+    destination: String,
+) -> Result<OperationResult, String> {
+    trust.check("move_path", Path::new(&from))?;
+    trust.check("move_path", Path::new(&destination))?;
+    operations.move_into(Path::new(&from), Path::new(&destination), &documents)
+}
+
+#[command]
+pub async fn copy_path(
+    operations: State<'_, FileOperations>,
+    trust: State<'_, WorkspaceTrust>,
+    from: String,
+    to: String,
+) -> Result<OperationResult, String> {
+    trust.check("copy_path", Path::new(&from))?;
+    trust.check("copy_path", Path::new(&to))?;
+    operations.copy(Path::new(&from), Path::new(&to))
+}
+
+// Moves to the trash unless `permanent` is set, which cannot be undone
+#[command]
+pub async fn delete_path(
+    operations: State<'_, FileOperations>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    permanent: Option<bool>,
+) -> Result<Option<OperationResult>, String> {
+    trust.check("delete_path", Path::new(&path))?;
+    operations.delete(Path::new(&path), permanent.unwrap_or(false))
+}
+
+#[command]
+pub async fn undo_file_operation(
+    operations: State<'_, FileOperations>,
+    documents: State<'_, DocumentManager>,
+    trust: State<'_, WorkspaceTrust>,
+) -> Result<OperationResult, String> {
+    operations.undo(&documents, &trust)
+}
+
+#[command]
+pub async fn get_file_operation_history(operations: State<'_, FileOperations>) -> Result<Vec<FileOperation>, String> {
+    Ok(operations.history())
+}
\ No newline at end of file
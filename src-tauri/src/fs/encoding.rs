@@ -0,0 +1,429 @@
+use encoding_rs::WINDOWS_1254;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::config::FilesConfig;
+use crate::fs::save::{write_atomic, FileVersion};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+// Bytes looked at to recognize UTF-16 without a BOM
+const UTF16_SAMPLE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    #[serde(rename = "iso-8859-9")]
+    Iso8859_9,
+    #[serde(rename = "windows-1254")]
+    Windows1254,
+}
+
+impl TextEncoding {
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(TextEncoding::Utf8),
+            "utf-16le" | "utf-16" => Some(TextEncoding::Utf16Le),
+            "utf-16be" => Some(TextEncoding::Utf16Be),
+            "iso-8859-9" | "latin5" => Some(TextEncoding::Iso8859_9),
+            "windows-1254" | "cp1254" => Some(TextEncoding::Windows1254),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Utf16Be => "utf-16be",
+            TextEncoding::Iso8859_9 => "iso-8859-9",
+            TextEncoding::Windows1254 => "windows-1254",
+        }
+    }
+
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            TextEncoding::Utf8 => UTF8_BOM,
+            TextEncoding::Utf16Le => UTF16LE_BOM,
+            TextEncoding::Utf16Be => UTF16BE_BOM,
+            TextEncoding::Iso8859_9 | TextEncoding::Windows1254 => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    // Both kinds occur; such files are written back as they are
+    Mixed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextFormat {
+    pub encoding: TextEncoding,
+    pub has_bom: bool,
+    pub line_ending: LineEnding,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextFile {
+    pub content: String,
+    #[serde(flatten)]
+    pub format: TextFormat,
+    // Set when read from disk
+    pub version: Option<FileVersion>,
+}
+
+// How to write a text file. Unset fields keep what the file on disk uses,
+// or UTF-8 without a BOM for a new file; the line ending is then left as
+// the content has it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WriteOptions {
+    pub encoding: Option<TextEncoding>,
+    pub has_bom: Option<bool>,
+    pub line_ending: Option<LineEnding>,
+}
+
+pub fn detect_line_ending(content: &str) -> LineEnding {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+
+    match (lf, crlf) {
+        (0, 0) if cfg!(windows) => LineEnding::Crlf,
+        (_, 0) => LineEnding::Lf,
+        (0, _) => LineEnding::Crlf,
+        _ => LineEnding::Mixed,
+    }
+}
+
+pub fn convert_line_endings(content: &str, line_ending: LineEnding) -> String {
+    match line_ending {
+        LineEnding::Lf => content.replace("\r\n", "\n"),
+        LineEnding::Crlf => content.replace("\r\n", "\n").replace('\n', "\r\n"),
+        LineEnding::Mixed => content.to_string(),
+    }
+}
+
+// The encoding configured in `files.fallback_encoding`, or Windows-1254
+// when it is not recognized
+pub fn fallback_encoding(settings: &FilesConfig) -> TextEncoding {
+    TextEncoding::from_label(&settings.fallback_encoding).unwrap_or(TextEncoding::Windows1254)
+}
+
+// Decode file contents, recognizing a BOM, UTF-16 without one and UTF-8.
+// Anything else is taken to be in `fallback`, a legacy single-byte encoding.
+pub fn decode(bytes: &[u8], fallback: TextEncoding) -> Result<TextFile, String> {
+    let (encoding, has_bom) = detect_encoding(bytes, fallback);
+    let body = if has_bom { &bytes[encoding.bom().len()..] } else { bytes };
+
+    let content = match encoding {
+        TextEncoding::Utf8 => String::from_utf8(body.to_vec())
+            .map_err(|e| format!("File is not valid UTF-8: {}", e))?,
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => decode_utf16(body, encoding == TextEncoding::Utf16Le)?,
+        TextEncoding::Iso8859_9 | TextEncoding::Windows1254 => decode_lossy(body, encoding),
+    };
+
+    Ok(TextFile {
+        format: TextFormat {
+            encoding,
+            has_bom,
+            line_ending: detect_line_ending(&content),
+        },
+        content,
+        version: None,
+    })
+}
+
+// The encoding of `bytes` and whether they start with its BOM
+pub fn detect_encoding(bytes: &[u8], fallback: TextEncoding) -> (TextEncoding, bool) {
+    if bytes.starts_with(UTF8_BOM) {
+        return (TextEncoding::Utf8, true);
+    }
+    if bytes.starts_with(UTF16LE_BOM) {
+        return (TextEncoding::Utf16Le, true);
+    }
+    if bytes.starts_with(UTF16BE_BOM) {
+        return (TextEncoding::Utf16Be, true);
+    }
+    if let Some(encoding) = detect_utf16(bytes) {
+        return (encoding, false);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return (TextEncoding::Utf8, false);
+    }
+
+    // C1 control characters do not occur in text, so bytes in that range
+    // mean Windows-1254 rather than ISO-8859-9
+    if fallback == TextEncoding::Iso8859_9 && bytes.iter().any(|byte| (0x80..0xA0).contains(byte)) {
+        return (TextEncoding::Windows1254, false);
+    }
+    (fallback, false)
+}
+
+// Text in UTF-16 without a BOM has a zero byte in most ASCII characters,
+// always at the same position of each pair
+fn detect_utf16(bytes: &[u8]) -> Option<TextEncoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SAMPLE)];
+    if sample.len() < 4 || !sample.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let zeros_even = sample.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let zeros_odd = sample.iter().skip(1).step_by(2).filter(|byte| **byte == 0).count();
+
+    if zeros_odd * 10 > pairs * 4 && zeros_even * 20 < pairs {
+        Some(TextEncoding::Utf16Le)
+    } else if zeros_even * 10 > pairs * 4 && zeros_odd * 20 < pairs {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> Result<String, String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err("File is not valid UTF-16: odd number of bytes".to_string());
+    }
+    let units = bytes.chunks_exact(2).map(|pair| {
+        if little_endian {
+            u16::from_le_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_be_bytes([pair[0], pair[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|e| format!("File is not valid UTF-16: {}", e))
+}
+
+// Decode bytes known to be in `encoding`, replacing invalid sequences, for
+// parts of a file such as single lines
+pub fn decode_lossy(bytes: &[u8], encoding: TextEncoding) -> String {
+    match encoding {
+        TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+            let units = bytes.chunks_exact(2).map(|pair| {
+                if encoding == TextEncoding::Utf16Le {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                }
+            });
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        TextEncoding::Iso8859_9 | TextEncoding::Windows1254 => {
+            let table = single_byte_table(encoding);
+            bytes
+                .iter()
+                .map(|&byte| if byte < 0x80 { byte as char } else { table[(byte - 0x80) as usize] })
+                .collect()
+        }
+    }
+}
+
+// Characters for bytes 0x80 to 0xFF. ISO-8859-9 matches Windows-1254 from
+// 0xA0 up and has the C1 control characters below.
+fn single_byte_table(encoding: TextEncoding) -> Vec<char> {
+    let high: Vec<u8> = (0x80..=0xFF).collect();
+    let (decoded, _) = WINDOWS_1254.decode_without_bom_handling(&high);
+
+    decoded
+        .chars()
+        .zip(0x80..=0xFFu8)
+        .map(|(c, byte)| {
+            if encoding == TextEncoding::Iso8859_9 && byte < 0xA0 {
+                byte as char
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+pub fn encode(content: &str, encoding: TextEncoding, has_bom: bool) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(content.len() + 3);
+    if has_bom {
+        bytes.extend_from_slice(encoding.bom());
+    }
+
+    match encoding {
+        TextEncoding::Utf8 => bytes.extend_from_slice(content.as_bytes()),
+        TextEncoding::Utf16Le => content.encode_utf16().for_each(|unit| bytes.extend(unit.to_le_bytes())),
+        TextEncoding::Utf16Be => content.encode_utf16().for_each(|unit| bytes.extend(unit.to_be_bytes())),
+        TextEncoding::Iso8859_9 | TextEncoding::Windows1254 => {
+            let table: HashMap<char, u8> = single_byte_table(encoding)
+                .into_iter()
+                .zip(0x80..=0xFF)
+                .collect();
+            for c in content.chars() {
+                let byte = if c.is_ascii() { Some(c as u8) } else { table.get(&c).copied() };
+                match byte {
+                    Some(byte) => bytes.push(byte),
+                    None => {
+                        return Err(format!(
+                            "The text contains characters such as '{}' that cannot be saved as {}",
+                            c,
+                            encoding.label()
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+// The text of a file, with the version the editor passes back when saving
+pub fn read_text_file(path: &Path, fallback: TextEncoding) -> Result<TextFile, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let mut file = decode(&bytes, fallback)?;
+    file.version = Some(FileVersion::of(path, &bytes));
+    Ok(file)
+}
+
+// Encode `content` to replace the file contents `existing`, keeping their
+// encoding, BOM and line endings unless `options` say otherwise. Returns
+// the bytes and the format they are in.
+pub fn prepare_write(
+    existing: Option<&[u8]>,
+    content: &str,
+    options: &WriteOptions,
+    fallback: TextEncoding,
+) -> Result<(Vec<u8>, TextFormat), String> {
+    let existing = existing
+        .and_then(|bytes| decode(bytes, fallback).ok())
+        .map(|file| file.format);
+
+    let encoding = options
+        .encoding
+        .or(existing.map(|format| format.encoding))
+        .unwrap_or(TextEncoding::Utf8);
+    let has_bom = options
+        .has_bom
+        .or(existing.map(|format| format.has_bom))
+        .unwrap_or(false);
+    // A file that had consistent line endings keeps them, whatever the
+    // editor sends
+    let line_ending = options.line_ending.or(existing
+        .map(|format| format.line_ending)
+        .filter(|line_ending| *line_ending != LineEnding::Mixed));
+
+    let content = match line_ending {
+        Some(line_ending) => convert_line_endings(content, line_ending),
+        None => content.to_string(),
+    };
+    let bytes = encode(&content, encoding, has_bom)?;
+
+    let format = TextFormat {
+        encoding,
+        has_bom,
+        line_ending: detect_line_ending(&content),
+    };
+    Ok((bytes, format))
+}
+
+pub fn write_text_file(path: &Path, content: &str, options: &WriteOptions, fallback: TextEncoding) -> Result<TextFormat, String> {
+    let existing = fs::read(path).ok();
+    let (bytes, written) = prepare_write(existing.as_deref(), content, options, fallback)?;
+    write_atomic(path, &bytes, false)?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn utf8_bom_and_crlf_survive_a_round_trip() {
+        let original = b"\xEF\xBB\xBFfirst\r\nsecond\r\n".to_vec();
+        let file = decode(&original, TextEncoding::Windows1254).unwrap();
+        assert_eq!(file.content, "first\r\nsecond\r\n");
+        assert_eq!(
+            file.format,
+            TextFormat { encoding: TextEncoding::Utf8, has_bom: true, line_ending: LineEnding::Crlf }
+        );
+
+        // The editor may hand back LF line endings
+        let (bytes, format) =
+            prepare_write(Some(&original), "first\nsecond\nthird\n", &WriteOptions::default(), TextEncoding::Utf8).unwrap();
+        assert_eq!(bytes, b"\xEF\xBB\xBFfirst\r\nsecond\r\nthird\r\n");
+        assert_eq!(format, file.format);
+    }
+
+    #[test]
+    fn utf16_is_recognized_with_and_without_a_bom() {
+        let mut with_bom = UTF16LE_BOM.to_vec();
+        with_bom.extend(utf16le("merhaba\r\n"));
+        let file = decode(&with_bom, TextEncoding::Utf8).unwrap();
+        assert_eq!(file.content, "merhaba\r\n");
+        assert_eq!((file.format.encoding, file.format.has_bom), (TextEncoding::Utf16Le, true));
+        assert_eq!(encode(&file.content, TextEncoding::Utf16Le, true).unwrap(), with_bom);
+
+        let big_endian: Vec<u8> = "plain text\n".encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect();
+        let file = decode(&big_endian, TextEncoding::Utf8).unwrap();
+        assert_eq!(file.content, "plain text\n");
+        assert_eq!((file.format.encoding, file.format.has_bom), (TextEncoding::Utf16Be, false));
+    }
+
+    #[test]
+    fn legacy_turkish_encodings_round_trip() {
+        let text = "Şişli'de ığdır çöğü\n";
+        let bytes = encode(text, TextEncoding::Windows1254, false).unwrap();
+        assert_eq!(bytes.len(), text.chars().count());
+
+        let file = decode(&bytes, TextEncoding::Windows1254).unwrap();
+        assert_eq!(file.content, text);
+        assert_eq!(file.format.encoding, TextEncoding::Windows1254);
+
+        let error = encode("漢字", TextEncoding::Iso8859_9, false).unwrap_err();
+        assert!(error.contains("cannot be saved as iso-8859-9"));
+    }
+
+    #[test]
+    fn mixed_line_endings_are_written_as_given() {
+        let original = b"a\r\nb\nc\n".to_vec();
+        assert_eq!(decode(&original, TextEncoding::Utf8).unwrap().format.line_ending, LineEnding::Mixed);
+
+        let (bytes, _) = prepare_write(Some(&original), "a\r\nb\nc\nd\n", &WriteOptions::default(), TextEncoding::Utf8).unwrap();
+        assert_eq!(bytes, b"a\r\nb\nc\nd\n");
+
+        let options = WriteOptions { line_ending: Some(LineEnding::Lf), ..Default::default() };
+        let (bytes, format) = prepare_write(Some(&original), "a\r\nb\nc\n", &options, TextEncoding::Utf8).unwrap();
+        assert_eq!(bytes, b"a\nb\nc\n");
+        assert_eq!(format.line_ending, LineEnding::Lf);
+    }
+
+    #[test]
+    fn writing_keeps_the_format_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        let mut original = UTF16LE_BOM.to_vec();
+        original.extend(utf16le("one\r\n"));
+        fs::write(&path, &original).unwrap();
+
+        let written = write_text_file(&path, "one\ntwo\n", &WriteOptions::default(), TextEncoding::Utf8).unwrap();
+        assert_eq!(written.encoding, TextEncoding::Utf16Le);
+        let file = read_text_file(&path, TextEncoding::Utf8).unwrap();
+        assert_eq!(file.content, "one\r\ntwo\r\n");
+        assert_eq!(file.format, written);
+    }
+}
@@ -0,0 +1,299 @@
+use git2::Repository;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Per-directory ignore files, the first taking precedence
+const IGNORE_FILES: [&str; 2] = [".ignore", ".gitignore"];
+
+// Decides which paths below a root are ignored, honoring `.ignore` and
+// `.gitignore` files, the repository's `info/exclude`, the global git
+// excludes file and the `files.exclude` globs from the settings
+pub struct IgnoreEngine {
+    root: PathBuf,
+    // Topmost directory whose ignore files apply, i.e. the working tree
+    // when the root is inside a repository
+    top: PathBuf,
+    excludes: GlobSet,
+    repo_excludes: Gitignore,
+    global_excludes: Gitignore,
+    // Only the exclude globs apply, e.g. inside a `.git` directory
+    globs_only: bool,
+    directories: Mutex<HashMap<PathBuf, Arc<Vec<Gitignore>>>>,
+}
+
+impl IgnoreEngine {
+    pub fn new(root: &Path, exclude: &[String]) -> Self {
+        let mut top = root.to_path_buf();
+        let mut repo_excludes = Gitignore::empty();
+        let mut globs_only = false;
+
+        if let Ok(repo) = Repository::discover(root) {
+            match repo.workdir() {
+                Some(workdir) if !root.starts_with(repo.path()) && root.starts_with(workdir) => {
+                    top = workdir.to_path_buf();
+                    let mut builder = GitignoreBuilder::new(workdir);
+                    builder.add(repo.path().join("info").join("exclude"));
+                    repo_excludes = builder.build().unwrap_or_else(|_| Gitignore::empty());
+                }
+                // Git never looks at ignore files for its own directory
+                _ => globs_only = true,
+            }
+        }
+
+        IgnoreEngine {
+            root: root.to_path_buf(),
+            top,
+            excludes: build_globs(exclude),
+            repo_excludes,
+            global_excludes: if globs_only { Gitignore::empty() } else { Gitignore::global().0 },
+            globs_only,
+            directories: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Whether `path` is ignored, either itself or through an ignored parent
+    // directory below the root. Paths outside the root are never ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+
+        let components: Vec<_> = relative.components().collect();
+        let mut current = self.root.clone();
+        let mut current_relative = PathBuf::new();
+
+        for (index, component) in components.iter().enumerate() {
+            current.push(component);
+            current_relative.push(component);
+            // Everything above the path itself is a directory
+            let component_is_dir = index + 1 < components.len() || is_dir;
+
+            if self.excludes.is_match(&current_relative) {
+                return true;
+            }
+            if !self.globs_only && self.ignored_by_files(&current, component_is_dir) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Like `is_ignored`, for a path whose parent directories are already
+    // known not to be ignored, e.g. while walking down the tree
+    pub fn is_ignored_entry(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative,
+            _ => return false,
+        };
+
+        self.excludes.is_match(relative) || (!self.globs_only && self.ignored_by_files(path, is_dir))
+    }
+
+    // Visit every entry below `start` that is not ignored, parents before
+    // their children. Returning false from `visit` for a directory skips
+    // its contents. Symlinked directories are not followed.
+    pub fn walk(&self, start: &Path, visit: &mut dyn FnMut(&Path, bool) -> bool) {
+        let mut pending = vec![start.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+                if self.is_ignored_entry(&path, is_dir) {
+                    continue;
+                }
+                if visit(&path, is_dir) && is_dir {
+                    pending.push(path);
+                }
+            }
+        }
+    }
+
+    // Forget cached rules of an ignore file that changed on disk
+    pub fn invalidate(&self, path: &Path) {
+        let is_ignore_file = path
+            .file_name()
+            .is_some_and(|name| IGNORE_FILES.iter().any(|file| name == *file));
+        if let (true, Some(dir)) = (is_ignore_file, path.parent()) {
+            self.directories.lock().unwrap().remove(dir);
+        }
+    }
+
+    fn ignored_by_files(&self, path: &Path, is_dir: bool) -> bool {
+        // Rules in deeper directories override those further up
+        for dir in path.ancestors().skip(1) {
+            for matcher in self.directory_rules(dir).iter() {
+                match matcher.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            if dir == self.top {
+                break;
+            }
+        }
+
+        let relative = path.strip_prefix(&self.top).unwrap_or(path);
+        for matcher in [&self.repo_excludes, &self.global_excludes] {
+            match matcher.matched(relative, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        false
+    }
+
+    fn directory_rules(&self, dir: &Path) -> Arc<Vec<Gitignore>> {
+        if let Some(rules) = self.directories.lock().unwrap().get(dir) {
+            return rules.clone();
+        }
+
+        let rules: Vec<Gitignore> = IGNORE_FILES
+            .iter()
+            .map(|file| dir.join(file))
+            .filter(|file| file.is_file())
+            .map(|file| Gitignore::new(&file).0)
+            .collect();
+        let rules = Arc::new(rules);
+        self.directories
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+}
+
+// Globs like `files.exclude` are matched against paths relative to the
+// root. A pattern without a slash matches at any depth, like in a `.gitignore`.
+pub fn build_globs(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns.iter().map(|pattern| pattern.trim()).filter(|pattern| !pattern.is_empty()) {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        let pattern = if pattern.contains('/') {
+            pattern.to_string()
+        } else {
+            format!("**/{}", pattern)
+        };
+
+        match GlobBuilder::new(&pattern).literal_separator(true).build() {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => log::warn!("Invalid glob pattern {}: {}", pattern, e),
+        }
+    }
+
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::init_repo;
+
+    fn write(root: &Path, relative: &str, content: &str) {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn deeper_ignore_files_override_parent_rules() {
+        let dir = init_repo();
+        let root = dir.path();
+        write(root, ".gitignore", "*.log\nbuild/\n");
+        write(root, "logs/.gitignore", "!keep.log\n");
+        let engine = IgnoreEngine::new(root, &[]);
+
+        assert!(engine.is_ignored(&root.join("debug.log"), false));
+        assert!(engine.is_ignored(&root.join("logs/other.log"), false));
+        assert!(!engine.is_ignored(&root.join("logs/keep.log"), false));
+        assert!(engine.is_ignored(&root.join("build"), true));
+        // Anything below an ignored directory is ignored too
+        assert!(engine.is_ignored(&root.join("build/out/main.rs"), false));
+        assert!(!engine.is_ignored(&root.join("src/main.rs"), false));
+    }
+
+    #[test]
+    fn exclude_globs_and_info_exclude_apply() {
+        let dir = init_repo();
+        let root = dir.path();
+        write(root, ".git/info/exclude", "secret.txt\n");
+        let exclude = ["node_modules".to_string(), "dist/*.map".to_string()];
+        let engine = IgnoreEngine::new(root, &exclude);
+
+        assert!(engine.is_ignored(&root.join("secret.txt"), false));
+        assert!(engine.is_ignored(&root.join("app/node_modules"), true));
+        assert!(engine.is_ignored(&root.join("dist/app.js.map"), false));
+        assert!(!engine.is_ignored(&root.join("dist/nested/app.js.map"), false));
+        assert!(!engine.is_ignored(&root.join("dist/app.js"), false));
+    }
+
+    #[test]
+    fn ignore_files_are_read_from_the_working_tree_root() {
+        let dir = init_repo();
+        let root = dir.path();
+        write(root, ".gitignore", "generated/\n");
+        write(root, "src/lib.rs", "");
+        let engine = IgnoreEngine::new(&root.join("src"), &[]);
+
+        assert!(engine.is_ignored(&root.join("src/generated"), true));
+        assert!(!engine.is_ignored(&root.join("src/lib.rs"), false));
+        // Paths outside the root are never ignored
+        assert!(!engine.is_ignored(&root.join("generated"), true));
+    }
+
+    #[test]
+    fn invalidate_picks_up_changed_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, ".ignore", "a.txt\n");
+        let engine = IgnoreEngine::new(root, &[]);
+        assert!(engine.is_ignored(&root.join("a.txt"), false));
+
+        write(root, ".ignore", "b.txt\n");
+        assert!(engine.is_ignored(&root.join("a.txt"), false));
+        engine.invalidate(&root.join(".ignore"));
+        assert!(!engine.is_ignored(&root.join("a.txt"), false));
+        assert!(engine.is_ignored(&root.join("b.txt"), false));
+    }
+
+    #[test]
+    fn walk_skips_ignored_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, ".gitignore", "target/\n*.tmp\n");
+        write(root, "src/main.rs", "");
+        write(root, "src/scratch.tmp", "");
+        write(root, "target/debug/app", "");
+        let engine = IgnoreEngine::new(root, &[]);
+
+        let mut visited = Vec::new();
+        engine.walk(root, &mut |path, _| {
+            visited.push(path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"));
+            true
+        });
+        visited.sort();
+        assert_eq!(visited, [".gitignore", "src", "src/main.rs"]);
+    }
+}
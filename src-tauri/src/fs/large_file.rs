@@ -0,0 +1,548 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::fs::encoding::{self, TextEncoding, TextFile};
+use crate::fs::format_time;
+use crate::fs::save::content_hash;
+use crate::utils::is_binary_file;
+
+// The line index keeps where every this many lines start
+const LINE_INDEX_STRIDE: u64 = 1024;
+// Bytes looked at to tell the encoding of a file
+const ENCODING_SAMPLE: usize = 64 * 1024;
+// Bytes at the end of an indexed file that tell a file that grew from one
+// that was rewritten
+const TAIL_SAMPLE: u64 = 4096;
+// Longer lines are cut when read
+const MAX_LINE_BYTES: usize = 64 * 1024;
+const MAX_LINES_PER_READ: usize = 10_000;
+const HEX_ROW_BYTES: usize = 16;
+const HEX_PREVIEW_BYTES: usize = 4096;
+const MAX_HEX_BYTES: usize = 64 * 1024;
+const SCAN_BUFFER: usize = 1024 * 1024;
+
+// What opening a file gives the editor
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileContents {
+    Text(TextFile),
+    // Too large to load at once; its lines are read with `read_lines`
+    Large(LargeFileInfo),
+    Binary(HexPreview),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LargeFileInfo {
+    pub path: String,
+    pub size: u64,
+    pub encoding: TextEncoding,
+    pub has_bom: bool,
+    pub line_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HexPreview {
+    pub path: String,
+    pub size: u64,
+    pub offset: u64,
+    pub rows: Vec<HexRow>,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HexRow {
+    pub offset: u64,
+    // Two hex digits per byte, separated by spaces
+    pub hex: String,
+    // Printable ASCII, with a dot for any other byte
+    pub ascii: String,
+}
+
+// Where to start reading lines
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineStart {
+    // 0-based line number
+    Line(u64),
+    // Byte offset; reading starts with the line it falls in
+    Offset(u64),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileLine {
+    // 0-based
+    pub number: u64,
+    pub offset: u64,
+    // Without the line break
+    pub text: String,
+    // The line is longer than the viewer shows, so `text` is its start
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LineChunk {
+    pub lines: Vec<FileLine>,
+    pub line_count: u64,
+    pub size: u64,
+    // Where the line after the chunk starts
+    pub end_offset: u64,
+    pub eof: bool,
+}
+
+// Offsets of lines 0, STRIDE, 2 * STRIDE and so on. A file counts one line
+// more than it has line breaks, like in the editor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineIndex {
+    pub size: u64,
+    pub modified: Option<String>,
+    // Hash of the last bytes indexed
+    pub tail_hash: String,
+    pub line_count: u64,
+    pub checkpoints: Vec<u64>,
+}
+
+impl LineIndex {
+    fn empty() -> Self {
+        LineIndex {
+            size: 0,
+            modified: None,
+            tail_hash: content_hash(&[]),
+            line_count: 1,
+            checkpoints: vec![0],
+        }
+    }
+
+    // Index what the file has past `self.size`
+    fn extend(&mut self, file: &mut File, metadata: &fs::Metadata) -> io::Result<()> {
+        file.seek(SeekFrom::Start(self.size))?;
+        let mut buffer = vec![0; SCAN_BUFFER];
+        let mut offset = self.size;
+
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            for (i, _) in buffer[..read].iter().enumerate().filter(|(_, byte)| **byte == b'\n') {
+                // `line_count` is the number of the line this break starts
+                if self.line_count.is_multiple_of(LINE_INDEX_STRIDE) {
+                    self.checkpoints.push(offset + i as u64 + 1);
+                }
+                self.line_count += 1;
+            }
+            offset += read as u64;
+        }
+
+        self.size = offset;
+        self.modified = metadata.modified().ok().map(format_time);
+        self.tail_hash = tail_hash(file, offset)?;
+        Ok(())
+    }
+}
+
+fn tail_hash(file: &mut File, end: u64) -> io::Result<String> {
+    let start = end.saturating_sub(TAIL_SAMPLE);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::with_capacity((end - start) as usize);
+    file.take(end - start).read_to_end(&mut tail)?;
+    Ok(content_hash(&tail))
+}
+
+// Line indexes of large files, kept in memory and in `dir` so a file is
+// not scanned again when reopened. A file that only grew, like a log, has
+// just its new part indexed.
+#[derive(Clone)]
+pub struct LineIndexStore {
+    dir: PathBuf,
+    indexes: Arc<Mutex<HashMap<PathBuf, Arc<LineIndex>>>>,
+}
+
+impl LineIndexStore {
+    pub fn new(dir: PathBuf) -> Self {
+        LineIndexStore {
+            dir,
+            indexes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // The index of the file as it is now
+    pub fn index(&self, path: &Path) -> Result<Arc<LineIndex>, String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let metadata = file.metadata().map_err(|e| e.to_string())?;
+        let size = metadata.len();
+        let modified = metadata.modified().ok().map(format_time);
+
+        let known = self.indexes.lock().unwrap().get(path).cloned();
+        let known = known.or_else(|| self.load(path).map(Arc::new));
+
+        let mut index = match known {
+            Some(index) if index.size == size && index.modified == modified => {
+                self.indexes.lock().unwrap().insert(path.to_path_buf(), index.clone());
+                return Ok(index);
+            }
+            Some(index) if size > index.size && tail_hash(&mut file, index.size).ok().as_ref() == Some(&index.tail_hash) => {
+                (*index).clone()
+            }
+            _ => LineIndex::empty(),
+        };
+
+        index.extend(&mut file, &metadata).map_err(|e| e.to_string())?;
+        self.save(path, &index);
+
+        let index = Arc::new(index);
+        self.indexes.lock().unwrap().insert(path.to_path_buf(), index.clone());
+        Ok(index)
+    }
+
+    // Up to `count` lines from `start`, decoded in the file's encoding
+    pub fn read_lines(&self, path: &Path, start: LineStart, count: usize, fallback: TextEncoding) -> Result<LineChunk, String> {
+        let (encoding, has_bom) = sniff_encoding(path, fallback).map_err(|e| e.to_string())?;
+        if is_utf16(encoding) {
+            return Err(utf16_unsupported(path));
+        }
+
+        let index = self.index(path)?;
+        let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+        let (mut number, mut offset) = seek_line(&mut reader, &index, start).map_err(|e| e.to_string())?;
+
+        let mut lines = Vec::new();
+        while lines.len() < count.min(MAX_LINES_PER_READ) && number < index.line_count {
+            let mut bytes = Vec::new();
+            let length = read_line(&mut reader, &mut bytes, MAX_LINE_BYTES).map_err(|e| e.to_string())?;
+            let truncated = length > bytes.len() as u64;
+
+            if bytes.ends_with(b"\n") {
+                bytes.pop();
+                if bytes.ends_with(b"\r") {
+                    bytes.pop();
+                }
+            }
+            if number == 0 && has_bom && bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+                bytes.drain(..3);
+            }
+
+            lines.push(FileLine {
+                number,
+                offset,
+                text: encoding::decode_lossy(&bytes, encoding),
+                truncated,
+            });
+            offset += length;
+            number += 1;
+        }
+
+        Ok(LineChunk {
+            lines,
+            line_count: index.line_count,
+            size: index.size,
+            end_offset: offset,
+            eof: number >= index.line_count,
+        })
+    }
+
+    fn index_path(&self, path: &Path) -> PathBuf {
+        let key = content_hash(path.to_string_lossy().as_bytes());
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn load(&self, path: &Path) -> Option<LineIndex> {
+        let content = fs::read_to_string(self.index_path(path)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    // Indexes are only a cache, so failing to store one is not an error
+    fn save(&self, path: &Path, index: &LineIndex) {
+        let result = fs::create_dir_all(&self.dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_json::to_string(index).map_err(|e| e.to_string()))
+            .and_then(|content| fs::write(self.index_path(path), content).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::warn!("Failed to store the line index of {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Position `reader` at the start of the line to read from, returning its
+// number and offset
+fn seek_line(reader: &mut BufReader<File>, index: &LineIndex, start: LineStart) -> io::Result<(u64, u64)> {
+    let last_line = index.line_count - 1;
+    let checkpoint = match start {
+        LineStart::Line(line) => (line.min(last_line) / LINE_INDEX_STRIDE) as usize,
+        LineStart::Offset(offset) => index.checkpoints.partition_point(|start| *start <= offset) - 1,
+    };
+
+    let mut number = checkpoint as u64 * LINE_INDEX_STRIDE;
+    let mut offset = index.checkpoints[checkpoint];
+    reader.seek(SeekFrom::Start(offset))?;
+
+    let mut skipped = Vec::new();
+    while number < last_line {
+        let length = match start {
+            LineStart::Line(line) if number >= line => break,
+            LineStart::Line(_) => read_line(reader, &mut skipped, 0)?,
+            LineStart::Offset(target) => {
+                let length = read_line(reader, &mut skipped, 0)?;
+                // Go back to the start of the line holding the offset
+                if offset + length > target {
+                    reader.seek(SeekFrom::Start(offset))?;
+                    break;
+                }
+                length
+            }
+        };
+        offset += length;
+        number += 1;
+    }
+    Ok((number, offset))
+}
+
+// Read a line into `line`, keeping at most `max` bytes of it. Returns the
+// length of the whole line, with its line break.
+fn read_line(reader: &mut impl BufRead, line: &mut Vec<u8>, max: usize) -> io::Result<u64> {
+    let mut length = 0;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        let (used, done) = match buffer.iter().position(|byte| *byte == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (buffer.len(), false),
+        };
+        let keep = used.min(max.saturating_sub(line.len()));
+        line.extend_from_slice(&buffer[..keep]);
+        reader.consume(used);
+        length += used as u64;
+        if done {
+            break;
+        }
+    }
+    Ok(length)
+}
+
+// Open a file the way its size and contents call for: small text files
+// with their text, large ones for the large file viewer and binary files
+// with a hex preview of their start
+pub fn open_file(path: &Path, line_indexes: &LineIndexStore, threshold: u64, fallback: TextEncoding) -> Result<FileContents, String> {
+    let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+    if metadata.is_dir() {
+        return Err(format!("Path is a directory: {}", path.display()));
+    }
+
+    let (encoding, has_bom) = sniff_encoding(path, fallback).map_err(|e| e.to_string())?;
+    // UTF-16 text is full of zero bytes, which would make it look binary
+    if !is_utf16(encoding) && is_binary_file(&path.to_path_buf()).map_err(|e| e.to_string())? {
+        return read_hex(path, 0, HEX_PREVIEW_BYTES).map(FileContents::Binary);
+    }
+
+    if metadata.len() <= threshold {
+        return encoding::read_text_file(path, fallback).map(FileContents::Text);
+    }
+    if is_utf16(encoding) {
+        return Err(utf16_unsupported(path));
+    }
+
+    let index = line_indexes.index(path)?;
+    Ok(FileContents::Large(LargeFileInfo {
+        path: path.to_string_lossy().to_string(),
+        size: index.size,
+        encoding,
+        has_bom,
+        line_count: index.line_count,
+    }))
+}
+
+// `length` bytes from `offset` as hex rows, for binary files
+pub fn read_hex(path: &Path, offset: u64, length: usize) -> Result<HexPreview, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+    let offset = offset.min(size);
+
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+    file.take(length.min(MAX_HEX_BYTES) as u64)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+
+    let rows = bytes
+        .chunks(HEX_ROW_BYTES)
+        .enumerate()
+        .map(|(i, row)| HexRow {
+            offset: offset + (i * HEX_ROW_BYTES) as u64,
+            hex: row.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" "),
+            ascii: row
+                .iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect(),
+        })
+        .collect();
+
+    Ok(HexPreview {
+        path: path.to_string_lossy().to_string(),
+        size,
+        offset,
+        has_more: offset + (bytes.len() as u64) < size,
+        rows,
+    })
+}
+
+// The encoding of a file, told from its start
+fn sniff_encoding(path: &Path, fallback: TextEncoding) -> io::Result<(TextEncoding, bool)> {
+    let mut sample = Vec::with_capacity(ENCODING_SAMPLE);
+    File::open(path)?.take(ENCODING_SAMPLE as u64).read_to_end(&mut sample)?;
+
+    // The sample may end in the middle of a UTF-8 character
+    let sample = match std::str::from_utf8(&sample) {
+        Err(e) if e.error_len().is_none() => &sample[..e.valid_up_to()],
+        _ => &sample[..],
+    };
+    Ok(encoding::detect_encoding(sample, fallback))
+}
+
+fn is_utf16(encoding: TextEncoding) -> bool {
+    matches!(encoding, TextEncoding::Utf16Le | TextEncoding::Utf16Be)
+}
+
+fn utf16_unsupported(path: &Path) -> String {
+    format!("{} is a large UTF-16 file, which the large file viewer cannot show", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // "line 0\n" up to "line {count - 1}\n", with the offset of each line
+    fn numbered_lines(count: u64) -> (String, Vec<u64>) {
+        let mut content = String::new();
+        let mut offsets = Vec::new();
+        for number in 0..count {
+            offsets.push(content.len() as u64);
+            content.push_str(&format!("line {}\n", number));
+        }
+        (content, offsets)
+    }
+
+    fn store(dir: &Path) -> LineIndexStore {
+        LineIndexStore::new(dir.join("indexes"))
+    }
+
+    fn read(store: &LineIndexStore, path: &Path, start: LineStart, count: usize) -> LineChunk {
+        store.read_lines(path, start, count, TextEncoding::Utf8).unwrap()
+    }
+
+    #[test]
+    fn index_keeps_a_checkpoint_every_stride() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.log");
+        let (content, offsets) = numbered_lines(3000);
+        fs::write(&path, &content).unwrap();
+
+        let index = store(dir.path()).index(&path).unwrap();
+        assert_eq!(index.line_count, 3001);
+        assert_eq!(index.size, content.len() as u64);
+        assert_eq!(index.checkpoints, vec![0, offsets[1024], offsets[2048]]);
+
+        // Stored on disk for the next session
+        let reloaded = store(dir.path()).load(&path).unwrap();
+        assert_eq!(reloaded.checkpoints, index.checkpoints);
+    }
+
+    #[test]
+    fn grown_files_only_index_their_new_part() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.log");
+        let store = store(dir.path());
+        fs::write(&path, "a\nb\n").unwrap();
+        let index = store.index(&path).unwrap();
+        assert_eq!(index.line_count, 3);
+
+        // A wrong count carried over shows the old part was not scanned again
+        let mut marked = (*index).clone();
+        marked.line_count += 100;
+        store.indexes.lock().unwrap().insert(path.clone(), Arc::new(marked));
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"c\n").unwrap();
+        assert_eq!(store.index(&path).unwrap().line_count, 104);
+
+        // A rewritten file no longer ends like the indexed one
+        fs::write(&path, "x\ny\nz\nw\n").unwrap();
+        let index = store.index(&path).unwrap();
+        assert_eq!(index.line_count, 5);
+        assert_eq!(index.size, 8);
+    }
+
+    #[test]
+    fn lines_are_read_from_a_line_or_an_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.log");
+        let (content, offsets) = numbered_lines(3000);
+        fs::write(&path, format!("{}last", content)).unwrap();
+        let store = store(dir.path());
+
+        let chunk = read(&store, &path, LineStart::Line(1500), 2);
+        assert_eq!(chunk.lines[0].number, 1500);
+        assert_eq!(chunk.lines[0].offset, offsets[1500]);
+        assert_eq!(chunk.lines[1].text, "line 1501");
+        assert_eq!(chunk.end_offset, offsets[1502]);
+        assert!(!chunk.eof);
+
+        // An offset in the middle of a line reads from that line's start
+        let chunk = read(&store, &path, LineStart::Offset(offsets[2050] + 3), 1);
+        assert_eq!(chunk.lines[0].number, 2050);
+        assert_eq!(chunk.lines[0].offset, offsets[2050]);
+        assert_eq!(chunk.lines[0].text, "line 2050");
+
+        // The last line has no line break; lines past the end clamp to it
+        for start in [LineStart::Line(3000), LineStart::Line(9999), LineStart::Offset(u64::MAX)] {
+            let chunk = read(&store, &path, start, 10);
+            assert_eq!(chunk.lines.len(), 1);
+            assert_eq!(chunk.lines[0].number, 3000);
+            assert_eq!(chunk.lines[0].text, "last");
+            assert!(chunk.eof);
+        }
+    }
+
+    #[test]
+    fn long_lines_are_truncated_and_the_bom_is_stripped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.txt");
+        let long = "x".repeat(MAX_LINE_BYTES + 10);
+        fs::write(&path, format!("\u{FEFF}first\r\n{}\nnext\n", long)).unwrap();
+
+        let chunk = read(&store(dir.path()), &path, LineStart::Line(0), 3);
+        assert_eq!(chunk.lines[0].text, "first");
+        assert!(!chunk.lines[0].truncated);
+        assert_eq!(chunk.lines[1].text.len(), MAX_LINE_BYTES);
+        assert!(chunk.lines[1].truncated);
+        assert_eq!(chunk.lines[2].text, "next");
+        assert_eq!(chunk.lines[2].offset, (3 + 7 + long.len() + 1) as u64);
+    }
+
+    #[test]
+    fn hex_rows_stay_within_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let bytes: Vec<u8> = (0..40).collect();
+        fs::write(&path, &bytes).unwrap();
+
+        let preview = read_hex(&path, 0, 16).unwrap();
+        assert_eq!(preview.rows.len(), 1);
+        assert!(preview.rows[0].hex.starts_with("00 01 02"));
+        assert_eq!(preview.rows[0].ascii, ".".repeat(16));
+        assert!(preview.has_more);
+
+        let preview = read_hex(&path, 30, 100).unwrap();
+        let offsets: Vec<u64> = preview.rows.iter().map(|row| row.offset).collect();
+        assert_eq!(offsets, vec![30]);
+        assert_eq!(preview.rows[0].hex, "1e 1f 20 21 22 23 24 25 26 27");
+        assert_eq!(preview.rows[0].ascii, ".. !\"#$%&'");
+        assert!(!preview.has_more);
+
+        let preview = read_hex(&path, 100, 10).unwrap();
+        assert_eq!(preview.offset, 40);
+        assert!(preview.rows.is_empty());
+    }
+}
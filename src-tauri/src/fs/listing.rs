@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+use crate::fs::FileInfo;
+use crate::fs::ignores::IgnoreEngine;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListOptions {
+    // Levels to list; 1 lists the directory itself, deeper directories are
+    // left unexpanded for the explorer to load when opened
+    pub depth: usize,
+    // Pagination of the top level, for huge directories
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub show_ignored: bool,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        ListOptions {
+            depth: 1,
+            offset: 0,
+            limit: None,
+            show_ignored: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileListPage {
+    pub path: String,
+    pub entries: Vec<FileInfo>,
+    // Entries in the directory, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub has_more: bool,
+}
+
+// List a directory with directories first and names in natural order. An
+// entry whose metadata cannot be read is listed with its error.
+pub fn list_directory(path: &Path, ignores: &IgnoreEngine, options: &ListOptions) -> Result<FileListPage, String> {
+    if !path.exists() {
+        return Err(format!("Path does not exist: {}", path.display()));
+    }
+    if !path.is_dir() {
+        return Err(format!("Path is not a directory: {}", path.display()));
+    }
+
+    let mut entries: Vec<FileInfo> = fs::read_dir(path)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| match entry {
+            Ok(entry) => {
+                let path = entry.path();
+                Some(FileInfo::from_path(&path).unwrap_or_else(|e| FileInfo::from_error(&path, &e)))
+            }
+            Err(e) => {
+                log::warn!("Skipping unreadable entry in {}: {}", path.display(), e);
+                None
+            }
+        })
+        .filter_map(|mut info| {
+            info.ignored = ignores.is_ignored(Path::new(&info.path), info.is_dir);
+            if info.ignored && !options.show_ignored {
+                None
+            } else {
+                Some(info)
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| natural_cmp(&a.name, &b.name)));
+
+    let total = entries.len();
+    let offset = options.offset.min(total);
+    let end = options.limit.map_or(total, |limit| offset.saturating_add(limit).min(total));
+    let mut entries: Vec<FileInfo> = entries.drain(offset..end).collect();
+
+    if options.depth > 1 {
+        let child_options = ListOptions {
+            depth: options.depth - 1,
+            offset: 0,
+            limit: None,
+            show_ignored: options.show_ignored,
+        };
+        // Symlinked directories are not expanded, to avoid cycles
+        for info in entries.iter_mut().filter(|info| info.is_dir && !info.is_symlink && info.error.is_none()) {
+            match list_directory(Path::new(&info.path), ignores, &child_options) {
+                Ok(page) => info.children = Some(page.entries),
+                Err(e) => info.error = Some(e),
+            }
+        }
+    }
+
+    Ok(FileListPage {
+        path: path.to_string_lossy().to_string(),
+        entries,
+        total,
+        offset,
+        has_more: end < total,
+    })
+}
+
+// Compare names ignoring case, with runs of digits compared by value, so
+// `file2` sorts before `file10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut left = a.chars().peekable();
+    let mut right = b.chars().peekable();
+
+    loop {
+        let ordering = match (left.peek().copied(), right.peek().copied()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) if l.is_ascii_digit() && r.is_ascii_digit() => {
+                compare_numbers(&take_digits(&mut left), &take_digits(&mut right))
+            }
+            (Some(l), Some(r)) => {
+                left.next();
+                right.next();
+                l.to_lowercase().cmp(r.to_lowercase())
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // Names differing only in case or leading zeros keep a stable order
+    a.cmp(b)
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(entries: &[FileInfo]) -> Vec<&str> {
+        entries.iter().map(|info| info.name.as_str()).collect()
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        let mut names = vec!["file10", "File2", "file1", "file02", "a", "file2", "b1"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["a", "b1", "file1", "File2", "file02", "file2", "file10"]);
+
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("x", "x1"), Ordering::Less);
+        assert_eq!(natural_cmp("99999999999999999999999", "100000000000000000000000"), Ordering::Less);
+        assert_eq!(natural_cmp("same", "same"), Ordering::Equal);
+    }
+
+    #[test]
+    fn listing_pages_through_sorted_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for name in ["item10.txt", "item2.txt", "item1.txt", "Zeta.txt"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        fs::create_dir(root.join("src")).unwrap();
+        let ignores = IgnoreEngine::new(root, &[]);
+
+        let options = ListOptions { limit: Some(2), ..Default::default() };
+        let page = list_directory(root, &ignores, &options).unwrap();
+        assert_eq!(names(&page.entries), ["src", "item1.txt"]);
+        assert_eq!((page.total, page.offset, page.has_more), (5, 0, true));
+
+        let options = ListOptions { offset: 2, limit: Some(2), ..Default::default() };
+        let page = list_directory(root, &ignores, &options).unwrap();
+        assert_eq!(names(&page.entries), ["item2.txt", "item10.txt"]);
+        assert!(page.has_more);
+
+        let options = ListOptions { offset: 4, limit: Some(2), ..Default::default() };
+        let page = list_directory(root, &ignores, &options).unwrap();
+        assert_eq!(names(&page.entries), ["Zeta.txt"]);
+        assert!(!page.has_more);
+
+        let options = ListOptions { offset: 10, ..Default::default() };
+        let page = list_directory(root, &ignores, &options).unwrap();
+        assert!(page.entries.is_empty() && !page.has_more);
+    }
+
+    #[test]
+    fn listing_hides_ignored_entries_and_expands_to_a_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(".ignore"), "*.log\n").unwrap();
+        fs::write(root.join("debug.log"), "").unwrap();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        let ignores = IgnoreEngine::new(root, &[]);
+
+        let page = list_directory(root, &ignores, &ListOptions::default()).unwrap();
+        assert_eq!(names(&page.entries), ["src", ".ignore"]);
+        assert!(page.entries[0].children.is_none());
+
+        let options = ListOptions { depth: 2, show_ignored: true, ..Default::default() };
+        let page = list_directory(root, &ignores, &options).unwrap();
+        assert_eq!(names(&page.entries), ["src", ".ignore", "debug.log"]);
+        assert!(page.entries[2].ignored);
+        let children = page.entries[0].children.as_ref().unwrap();
+        assert_eq!(names(children), ["nested", "main.rs"]);
+        assert!(children[0].children.is_none());
+    }
+}
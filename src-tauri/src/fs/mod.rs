@@ -1,37 +1,158 @@
 pub mod commands;
+pub mod encoding;
+pub mod ignores;
+pub mod large_file;
+pub mod listing;
+pub mod operations;
+pub mod save;
 pub mod watcher;
 
+use std::fs::Metadata;
 use std::path::Path;
+use std::time::SystemTime;
+use crate::editor::syntax::get_language_id_from_path;
 
 #[derive(Debug, serde::Serialize)]
 pub struct FileInfo {
     pub name: String,
     pub path: String,
+    // A symlink counts as a directory when it points to one
     pub is_dir: bool,
     pub size: u64,
     pub modified: Option<String>,
+    pub created: Option<String>,
+    // Matched by an ignore file or `files.exclude`
+    pub ignored: bool,
+    // Starts with a dot, or has the hidden attribute on Windows
+    pub hidden: bool,
+    pub is_symlink: bool,
+    pub symlink_target: Option<String>,
+    // A symlink whose target does not exist
+    pub is_broken: bool,
+    pub readonly: bool,
+    // Unix permission bits, e.g. 0o755
+    pub permissions: Option<u32>,
+    pub executable: bool,
+    pub language_id: Option<String>,
+    // Contents of a directory listed with a depth, None when not expanded
+    pub children: Option<Vec<FileInfo>>,
+    // Why the entry's metadata could not be read
+    pub error: Option<String>,
 }
 
 impl FileInfo {
     pub fn from_path(path: &Path) -> Result<Self, std::io::Error> {
-        let metadata = path.metadata()?;
+        let link_metadata = path.symlink_metadata()?;
+        let is_symlink = link_metadata.file_type().is_symlink();
+        let symlink_target = if is_symlink {
+            std::fs::read_link(path)
+                .ok()
+                .map(|target| target.to_string_lossy().to_string())
+        } else {
+            None
+        };
         
-        let modified = metadata
-            .modified()
-            .ok()
-            .map(|time| {
-                let datetime: chrono::DateTime<chrono::Utc> = time.into();
-                datetime.to_rfc3339()
-            });
+        // Symlinks describe their target, unless it is missing
+        let (metadata, is_broken) = if is_symlink {
+            match path.metadata() {
+                Ok(metadata) => (metadata, false),
+                Err(_) => (link_metadata, true),
+            }
+        } else {
+            (link_metadata, false)
+        };
         
-        Ok(FileInfo {
-            name: path.file_name()
-                .map(|name| name.to_string_lossy().to_string())
-                .unwrap_or_else(|| String::from(".")),
+        let mut info = FileInfo::empty(path);
+        info.is_dir = metadata.is_dir();
+        info.size = metadata.len();
+        info.modified = metadata.modified().ok().map(format_time);
+        info.created = metadata.created().ok().map(format_time);
+        info.hidden = info.hidden || has_hidden_attribute(&metadata);
+        info.is_symlink = is_symlink;
+        info.symlink_target = symlink_target;
+        info.is_broken = is_broken;
+        info.readonly = metadata.permissions().readonly();
+        info.permissions = permission_bits(&metadata);
+        info.executable = !info.is_dir && is_executable(path, &metadata);
+        if !info.is_dir {
+            info.language_id = get_language_id_from_path(&info.path);
+        }
+        Ok(info)
+    }
+    
+    // An entry whose metadata cannot be read, listed with the error
+    // instead of failing the whole listing
+    pub fn from_error(path: &Path, error: &std::io::Error) -> Self {
+        let mut info = FileInfo::empty(path);
+        info.error = Some(error.to_string());
+        info
+    }
+    
+    fn empty(path: &Path) -> Self {
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("."));
+        
+        FileInfo {
+            hidden: name.starts_with('.') && name != ".",
+            name,
             path: path.to_string_lossy().to_string(),
-            is_dir: metadata.is_dir(),
-            size: metadata.len(),
-            modified,
-        })
+            is_dir: false,
+            size: 0,
+            modified: None,
+            created: None,
+            ignored: false,
+            is_symlink: false,
+            symlink_target: None,
+            is_broken: false,
+            readonly: false,
+            permissions: None,
+            executable: false,
+            language_id: None,
+            children: None,
+            error: None,
+        }
     }
-} 
\ No newline at end of file
+}
+
+pub fn format_time(time: SystemTime) -> String {
+    let datetime: chrono::DateTime<chrono::Utc> = time.into();
+    datetime.to_rfc3339()
+}
+
+#[cfg(unix)]
+fn permission_bits(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn permission_bits(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(_path: &Path, metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path, _metadata: &Metadata) -> bool {
+    let extension = path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    ["exe", "bat", "cmd", "com", "ps1"].contains(&extension.as_str())
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_metadata: &Metadata) -> bool {
+    false
+}
\ No newline at end of file
@@ -0,0 +1,481 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use crate::editor::{DocumentManager, DocumentMove};
+use crate::workspace::WorkspaceTrust;
+
+// Explorer operations kept for undo
+const UNDO_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileOperation {
+    Create { path: String, is_dir: bool },
+    // Renames and moves
+    Rename { from: String, to: String },
+    Copy { from: String, to: String },
+    Trash { path: String },
+}
+
+impl FileOperation {
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            FileOperation::Create { path, .. } | FileOperation::Trash { path } => vec![path],
+            FileOperation::Rename { from, to } | FileOperation::Copy { from, to } => vec![from, to],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationResult {
+    pub operation: FileOperation,
+    // Open documents that followed a rename or move
+    pub documents: Vec<DocumentMove>,
+}
+
+// Creates, renames, copies and deletes files for the explorer, keeping the
+// last operations so they can be undone. Existing targets are never
+// overwritten.
+pub struct FileOperations {
+    history: Arc<Mutex<VecDeque<FileOperation>>>,
+}
+
+impl FileOperations {
+    pub fn new() -> Self {
+        FileOperations {
+            history: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    // Most recent first
+    pub fn history(&self) -> Vec<FileOperation> {
+        self.history.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    pub fn create_file(&self, path: &Path) -> Result<OperationResult, String> {
+        ensure_parent(path)?;
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| conflict_or(path, e))?;
+        Ok(self.record(FileOperation::Create { path: display(path), is_dir: false }, Vec::new()))
+    }
+
+    pub fn create_directory(&self, path: &Path) -> Result<OperationResult, String> {
+        ensure_parent(path)?;
+        fs::create_dir(path).map_err(|e| conflict_or(path, e))?;
+        Ok(self.record(FileOperation::Create { path: display(path), is_dir: true }, Vec::new()))
+    }
+
+    // Rename or move `from` to the path `to`
+    pub fn rename(&self, from: &Path, to: &Path, documents: &DocumentManager) -> Result<OperationResult, String> {
+        move_path(from, to)?;
+        let moved = documents.rename_path(from, to);
+        Ok(self.record(FileOperation::Rename { from: display(from), to: display(to) }, moved))
+    }
+
+    // Move `from` into the directory `destination`, keeping its name
+    pub fn move_into(&self, from: &Path, destination: &Path, documents: &DocumentManager) -> Result<OperationResult, String> {
+        if !destination.is_dir() {
+            return Err(format!("Path is not a directory: {}", destination.display()));
+        }
+        let name = from
+            .file_name()
+            .ok_or_else(|| format!("Invalid path: {}", from.display()))?;
+        self.rename(from, &destination.join(name), documents)
+    }
+
+    // Copy a file, or a directory recursively
+    pub fn copy(&self, from: &Path, to: &Path) -> Result<OperationResult, String> {
+        if to.symlink_metadata().is_ok() {
+            return Err(conflict(to));
+        }
+        if to.starts_with(from) {
+            return Err(format!("Cannot copy {} into itself", from.display()));
+        }
+        ensure_parent(to)?;
+
+        if let Err(e) = copy_recursive(from, to) {
+            // Do not leave a partial copy behind
+            let _ = remove_path(to);
+            return Err(e.to_string());
+        }
+        Ok(self.record(FileOperation::Copy { from: display(from), to: display(to) }, Vec::new()))
+    }
+
+    // Move to the trash, or delete for good with `permanent`. Permanent
+    // deletes cannot be undone and are not recorded.
+    pub fn delete(&self, path: &Path, permanent: bool) -> Result<Option<OperationResult>, String> {
+        if path.symlink_metadata().is_err() {
+            return Err(format!("Path does not exist: {}", path.display()));
+        }
+
+        if permanent {
+            remove_path(path).map_err(|e| e.to_string())?;
+            return Ok(None);
+        }
+
+        trash::delete(path).map_err(|e| format!("Failed to move {} to the trash: {}", path.display(), e))?;
+        Ok(Some(self.record(FileOperation::Trash { path: display(path) }, Vec::new())))
+    }
+
+    // Revert the most recent operation, returning the one that was undone.
+    // It stays in the history when it cannot be reverted. The workspace may
+    // no longer be trusted since the operation was recorded, so its paths
+    // are checked again.
+    pub fn undo(&self, documents: &DocumentManager, trust: &WorkspaceTrust) -> Result<OperationResult, String> {
+        let operation = self
+            .history
+            .lock()
+            .unwrap()
+            .pop_back()
+            .ok_or("Nothing to undo")?;
+
+        let reverted = operation
+            .paths()
+            .iter()
+            .try_for_each(|path| trust.check("undo_file_operation", Path::new(path)))
+            .and_then(|_| revert(&operation, documents));
+        match reverted {
+            Ok(moved) => Ok(OperationResult { operation, documents: moved }),
+            Err(e) => {
+                self.history.lock().unwrap().push_back(operation);
+                Err(e)
+            }
+        }
+    }
+
+    fn record(&self, operation: FileOperation, documents: Vec<DocumentMove>) -> OperationResult {
+        let mut history = self.history.lock().unwrap();
+        history.push_back(operation.clone());
+        while history.len() > UNDO_LIMIT {
+            history.pop_front();
+        }
+        OperationResult { operation, documents }
+    }
+}
+
+fn revert(operation: &FileOperation, documents: &DocumentManager) -> Result<Vec<DocumentMove>, String> {
+    match operation {
+        // Created files may have been edited since, so they go to the trash
+        FileOperation::Create { path, .. } | FileOperation::Copy { to: path, .. } => {
+            let path = Path::new(path);
+            if path.symlink_metadata().is_ok() {
+                trash::delete(path).map_err(|e| format!("Failed to move {} to the trash: {}", path.display(), e))?;
+            }
+            Ok(Vec::new())
+        }
+        FileOperation::Rename { from, to } => {
+            let (from, to) = (Path::new(from), Path::new(to));
+            move_path(to, from)?;
+            Ok(documents.rename_path(to, from))
+        }
+        FileOperation::Trash { path } => {
+            restore_from_trash(Path::new(path))?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))))]
+fn restore_from_trash(path: &Path) -> Result<(), String> {
+    if path.symlink_metadata().is_ok() {
+        return Err(conflict(path));
+    }
+
+    // The most recently trashed item that came from `path`
+    let item = trash::os_limited::list()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or_else(|| format!("{} is no longer in the trash", path.display()))?;
+    trash::os_limited::restore_all([item]).map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))))]
+fn restore_from_trash(path: &Path) -> Result<(), String> {
+    Err(format!(
+        "Restoring from the trash is not supported on this platform. Restore {} from the trash manually.",
+        path.display()
+    ))
+}
+
+fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    if from.symlink_metadata().is_err() {
+        return Err(format!("Path does not exist: {}", from.display()));
+    }
+    let other_case = same_file_other_case(from, to);
+    if to.symlink_metadata().is_ok() && !other_case {
+        return Err(conflict(to));
+    }
+    if to.starts_with(from) {
+        return Err(format!("Cannot move {} into itself", from.display()));
+    }
+    ensure_parent(to)?;
+
+    let renamed = if other_case {
+        fs::rename(from, to)
+    } else {
+        rename_no_replace(from, to)
+    };
+    match renamed {
+        Ok(()) => Ok(()),
+        // Created by something else since the check above
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(conflict(to)),
+        // Another file system: copy, then remove the original
+        Err(e) if is_cross_device(&e) => {
+            if let Err(e) = copy_recursive(from, to) {
+                let _ = remove_path(to);
+                return Err(e.to_string());
+            }
+            remove_path(from).map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// A plain rename replaces a file created at `to` after `move_path` checked
+// for it. Files are linked under the new name and then unlinked instead,
+// which fails on an existing target. Directories cannot be linked, so they
+// are renamed and that window remains; a rename still fails over anything
+// but an empty directory.
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    if from.symlink_metadata()?.is_dir() {
+        return fs::rename(from, to);
+    }
+
+    match fs::hard_link(from, to) {
+        Ok(()) => fs::remove_file(from).inspect_err(|_| {
+            let _ = fs::remove_file(to);
+        }),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists || is_cross_device(&e) => Err(e),
+        // File systems without hard links
+        Err(_) => fs::rename(from, to),
+    }
+}
+
+// Renaming `a.txt` to `A.txt` on a case-insensitive file system, where the
+// target "exists" because it is the source
+fn same_file_other_case(from: &Path, to: &Path) -> bool {
+    from != to
+        && from.to_string_lossy().to_lowercase() == to.to_string_lossy().to_lowercase()
+        && match (fs::canonicalize(from), fs::canonicalize(to)) {
+            (Ok(from), Ok(to)) => from == to,
+            _ => false,
+        }
+}
+
+// EXDEV on Unix, ERROR_NOT_SAME_DEVICE on Windows
+#[cfg(unix)]
+const CROSS_DEVICE_ERROR: i32 = 18;
+#[cfg(windows)]
+const CROSS_DEVICE_ERROR: i32 = 17;
+
+fn is_cross_device(error: &io::Error) -> bool {
+    error.raw_os_error() == Some(CROSS_DEVICE_ERROR)
+}
+
+// Symlinks are copied as links rather than followed
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = from.symlink_metadata()?;
+
+    if metadata.file_type().is_symlink() {
+        let target = fs::read_link(from)?;
+        return create_symlink(&target, to, from);
+    }
+    if !metadata.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+
+    fs::create_dir(to)?;
+    fs::set_permissions(to, metadata.permissions())?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path, _original: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path, original: &Path) -> io::Result<()> {
+    if original.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    let metadata = path.symlink_metadata()?;
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn ensure_parent(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() && !parent.is_dir() => {
+            Err(format!("Directory does not exist: {}", parent.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn conflict(path: &Path) -> String {
+    format!("Conflict: {} already exists", path.display())
+}
+
+fn conflict_or(path: &Path, error: io::Error) -> String {
+    if error.kind() == io::ErrorKind::AlreadyExists {
+        conflict(path)
+    } else {
+        error.to_string()
+    }
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::tests::trusting;
+
+    fn error_of(result: Result<OperationResult, String>) -> String {
+        match result {
+            Ok(result) => panic!("unexpected success: {:?}", result.operation),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn existing_targets_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let operations = FileOperations::new();
+        let documents = DocumentManager::new();
+        let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        assert!(error_of(operations.create_file(&a)).starts_with("Conflict:"));
+        assert!(error_of(operations.create_directory(&a)).starts_with("Conflict:"));
+        assert!(error_of(operations.rename(&a, &b, &documents)).starts_with("Conflict:"));
+        assert!(error_of(operations.copy(&a, &b)).starts_with("Conflict:"));
+
+        // Nothing was touched or recorded
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+        assert!(operations.history().is_empty());
+    }
+
+    #[test]
+    fn a_file_created_at_the_target_is_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        // As if `b.txt` appeared after `move_path` checked for it
+        assert_eq!(rename_no_replace(&a, &b).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+
+        fs::remove_file(&b).unwrap();
+        rename_no_replace(&a, &b).unwrap();
+        assert!(a.symlink_metadata().is_err());
+        assert_eq!(fs::read_to_string(&b).unwrap(), "a");
+    }
+
+    #[test]
+    fn directories_cannot_be_copied_or_moved_into_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let operations = FileOperations::new();
+        let documents = DocumentManager::new();
+        let folder = dir.path().join("folder");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("a.txt"), "a").unwrap();
+
+        let inside = folder.join("copy");
+        assert!(error_of(operations.copy(&folder, &inside)).contains("into itself"));
+        assert!(error_of(operations.rename(&folder, &inside, &documents)).contains("into itself"));
+        assert!(error_of(operations.move_into(&folder, &folder, &documents)).contains("into itself"));
+        assert!(inside.symlink_metadata().is_err());
+        assert!(folder.join("a.txt").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_keep_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let operations = FileOperations::new();
+        let folder = dir.path().join("folder");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("a.txt", folder.join("link")).unwrap();
+        std::os::unix::fs::symlink("missing", folder.join("dangling")).unwrap();
+
+        let copy = dir.path().join("copy");
+        operations.copy(&folder, &copy).unwrap();
+
+        for name in ["link", "dangling"] {
+            let link = copy.join(name);
+            assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+            assert_eq!(fs::read_link(&link).unwrap(), fs::read_link(folder.join(name)).unwrap());
+        }
+        assert_eq!(fs::read_to_string(copy.join("link")).unwrap(), "a");
+    }
+
+    #[test]
+    fn undoing_a_rename_moves_the_file_and_its_document_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let operations = FileOperations::new();
+        let documents = DocumentManager::new();
+        let (from, to) = (root.join("a.txt"), root.join("b.txt"));
+        fs::write(&from, "a").unwrap();
+        let uri = format!("file://{}", from.display());
+        documents.open_document(&uri, Some(from.clone()), "plaintext", "a").unwrap();
+
+        let result = operations.rename(&from, &to, &documents).unwrap();
+        assert_eq!(result.documents.len(), 1);
+        assert_eq!(result.documents[0].new_uri, format!("file://{}", to.display()));
+        assert!(documents.get_document(&uri).is_none());
+        assert!(!from.exists());
+
+        let result = operations.undo(&documents, &trusting(&root)).unwrap();
+        assert!(matches!(result.operation, FileOperation::Rename { .. }));
+        assert_eq!(result.documents[0].new_uri, uri);
+        assert_eq!(fs::read_to_string(&from).unwrap(), "a");
+        assert!(!to.exists());
+        assert_eq!(documents.get_document(&uri).unwrap().path, Some(from));
+        assert!(operations.history().is_empty());
+    }
+
+    #[test]
+    fn undo_outside_the_trusted_workspace_keeps_the_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let operations = FileOperations::new();
+        let documents = DocumentManager::new();
+        let (from, to) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        fs::write(&from, "a").unwrap();
+        operations.rename(&from, &to, &documents).unwrap();
+
+        let elsewhere = tempfile::tempdir().unwrap();
+        assert!(operations.undo(&documents, &trusting(elsewhere.path())).is_err());
+        assert!(to.is_file());
+        assert_eq!(operations.history().len(), 1);
+    }
+}
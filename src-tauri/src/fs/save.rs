@@ -0,0 +1,309 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::fs::encoding::{decode, prepare_write, TextEncoding, TextFormat, WriteOptions};
+
+// Distinguishes temp files of saves running at the same time
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// What the editor last saw of a file, to detect changes made by others
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileVersion {
+    pub modified: Option<String>,
+    // SHA-256 of the raw bytes, hex encoded
+    pub hash: String,
+}
+
+impl FileVersion {
+    pub fn of(path: &Path, bytes: &[u8]) -> Self {
+        FileVersion {
+            modified: modified_time(path),
+            hash: content_hash(bytes),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveOptions {
+    #[serde(flatten)]
+    pub write: WriteOptions,
+    // The version the content was based on. The save is rejected when the
+    // file on disk no longer matches it; the hash wins over the time.
+    pub expected_modified: Option<String>,
+    pub expected_hash: Option<String>,
+    // Keep the previous contents as `<file>.bak`
+    pub backup: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveResult {
+    #[serde(flatten)]
+    pub format: TextFormat,
+    pub version: FileVersion,
+    pub backup_path: Option<String>,
+}
+
+// The file was changed by something else since the editor read it. Its
+// current content lets the UI show a diff.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskChange {
+    pub path: String,
+    pub expected_modified: Option<String>,
+    pub expected_hash: Option<String>,
+    pub disk_version: FileVersion,
+    pub disk_content: Option<String>,
+}
+
+// Errors of `save_file`, tagged so the UI can tell a conflict it can
+// resolve from a failure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SaveError {
+    ChangedOnDisk(Box<DiskChange>),
+    Failed { message: String },
+}
+
+impl From<String> for SaveError {
+    fn from(message: String) -> Self {
+        SaveError::Failed { message }
+    }
+}
+
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn modified_time(path: &Path) -> Option<String> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let datetime: DateTime<Utc> = modified.into();
+    Some(datetime.to_rfc3339())
+}
+
+fn same_time(a: &str, b: &str) -> bool {
+    match (DateTime::parse_from_rfc3339(a), DateTime::parse_from_rfc3339(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// Save `content` to `path` without ever leaving a half-written file,
+// keeping the file's encoding, BOM and line endings unless told otherwise
+pub fn save_file(path: &Path, content: &str, options: &SaveOptions, fallback: TextEncoding) -> Result<SaveResult, SaveError> {
+    let existing = match fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.to_string().into()),
+    };
+
+    // A file deleted on disk is simply written again
+    if let Some(bytes) = &existing {
+        let disk_version = FileVersion::of(path, bytes);
+        let changed = match (&options.expected_hash, &options.expected_modified) {
+            (Some(hash), _) => *hash != disk_version.hash,
+            (None, Some(modified)) => !disk_version
+                .modified
+                .as_deref()
+                .is_some_and(|disk_modified| same_time(modified, disk_modified)),
+            (None, None) => false,
+        };
+
+        if changed {
+            return Err(SaveError::ChangedOnDisk(Box::new(DiskChange {
+                path: path.to_string_lossy().to_string(),
+                expected_modified: options.expected_modified.clone(),
+                expected_hash: options.expected_hash.clone(),
+                disk_content: decode(bytes, fallback).ok().map(|file| file.content),
+                disk_version,
+            })));
+        }
+    }
+
+    let (bytes, format) = prepare_write(existing.as_deref(), content, &options.write, fallback)?;
+    let backup_path = write_atomic(path, &bytes, options.backup && existing.is_some())?;
+
+    Ok(SaveResult {
+        format,
+        version: FileVersion::of(path, &bytes),
+        backup_path: backup_path.map(|backup| backup.to_string_lossy().to_string()),
+    })
+}
+
+// Write to a temp file next to the target and rename it over the target,
+// so the file holds either the old or the new contents. Permissions and,
+// where allowed, ownership of the old file are kept. Returns the path of
+// the backup when `backup` is set.
+pub fn write_atomic(path: &Path, bytes: &[u8], backup: bool) -> Result<Option<PathBuf>, String> {
+    // Replace the file a symlink points to, not the link
+    let target = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path).map_err(|e| e.to_string())?,
+        _ => path.to_path_buf(),
+    };
+    let dir = target
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = target
+        .file_name()
+        .ok_or_else(|| format!("Invalid path: {}", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    let original = fs::metadata(&target).ok();
+
+    let temp_path = dir.join(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let result = write_temp(&temp_path, bytes, original.as_ref()).and_then(|_| {
+        let backup_path = if backup && original.is_some() {
+            let backup_path = dir.join(format!("{}.bak", name));
+            fs::copy(&target, &backup_path)?;
+            Some(backup_path)
+        } else {
+            None
+        };
+        fs::rename(&temp_path, &target)?;
+        sync_dir(dir);
+        Ok(backup_path)
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result.map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+}
+
+fn write_temp(temp_path: &Path, bytes: &[u8], original: Option<&fs::Metadata>) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(temp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+
+    if let Some(original) = original {
+        fs::set_permissions(temp_path, original.permissions())?;
+        keep_owner(temp_path, original);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn keep_owner(temp_path: &Path, original: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    // Only root may give files away; others keep their own files anyway
+    if let Err(e) = std::os::unix::fs::chown(temp_path, Some(original.uid()), Some(original.gid())) {
+        log::debug!("Could not keep the owner of {}: {}", temp_path.display(), e);
+    }
+}
+
+#[cfg(not(unix))]
+fn keep_owner(_temp_path: &Path, _original: &fs::Metadata) {}
+
+// Make the rename durable
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::encoding::read_text_file;
+
+    fn expecting(version: &FileVersion) -> SaveOptions {
+        SaveOptions {
+            expected_modified: version.modified.clone(),
+            expected_hash: Some(version.hash.clone()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn save_over_an_unchanged_file_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "one\n").unwrap();
+        let version = read_text_file(&path, TextEncoding::Utf8).unwrap().version.unwrap();
+
+        let result = save_file(&path, "two\n", &expecting(&version), TextEncoding::Utf8).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two\n");
+        assert_eq!(result.version.hash, content_hash(b"two\n"));
+
+        // The returned version is what the next save is checked against
+        save_file(&path, "three\n", &expecting(&result.version), TextEncoding::Utf8).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three\n");
+    }
+
+    #[test]
+    fn save_over_a_file_changed_on_disk_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "one\n").unwrap();
+        let version = read_text_file(&path, TextEncoding::Utf8).unwrap().version.unwrap();
+        fs::write(&path, "changed elsewhere\n").unwrap();
+
+        match save_file(&path, "mine\n", &expecting(&version), TextEncoding::Utf8) {
+            Err(SaveError::ChangedOnDisk(change)) => {
+                assert_eq!(change.disk_content.as_deref(), Some("changed elsewhere\n"));
+                assert_eq!(change.disk_version.hash, content_hash(b"changed elsewhere\n"));
+                assert_eq!(change.expected_hash, Some(version.hash));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "changed elsewhere\n");
+
+        // Saving without an expected version overwrites
+        save_file(&path, "mine\n", &SaveOptions::default(), TextEncoding::Utf8).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "mine\n");
+    }
+
+    #[test]
+    fn hash_wins_over_the_modification_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "one\n").unwrap();
+        let options = SaveOptions {
+            expected_modified: Some("2000-01-01T00:00:00+00:00".to_string()),
+            expected_hash: Some(content_hash(b"one\n")),
+            ..Default::default()
+        };
+        assert!(save_file(&path, "two\n", &options, TextEncoding::Utf8).is_ok());
+
+        let options = SaveOptions {
+            expected_modified: Some("2000-01-01T00:00:00+00:00".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            save_file(&path, "three\n", &options, TextEncoding::Utf8),
+            Err(SaveError::ChangedOnDisk(_))
+        ));
+    }
+
+    #[test]
+    fn deleted_file_is_written_again_with_a_backup_only_when_it_existed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "one\n").unwrap();
+        let version = read_text_file(&path, TextEncoding::Utf8).unwrap().version.unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let options = SaveOptions { backup: true, ..expecting(&version) };
+        let result = save_file(&path, "two\n", &options, TextEncoding::Utf8).unwrap();
+        assert_eq!(result.backup_path, None);
+
+        let options = SaveOptions { backup: true, ..expecting(&result.version) };
+        let result = save_file(&path, "three\n", &options, TextEncoding::Utf8).unwrap();
+        assert_eq!(fs::read_to_string(result.backup_path.unwrap()).unwrap(), "two\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three\n");
+    }
+}
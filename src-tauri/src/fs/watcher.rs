@@ -1,185 +1,841 @@
-use std::collections::HashMap;
+use notify::event::{CreateKind, EventKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{PollWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::mpsc;
-use tokio::time::{sleep, Duration};
+use tokio::time::{timeout, Duration, Instant};
+use crate::config::{ConfigManager, FilesConfig};
+use crate::fs::ignores::IgnoreEngine;
 
+// Quiet period after the last raw event before a batch is delivered
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+// Longest a batch is held back while events keep arriving
+const MAX_DELAY: Duration = Duration::from_millis(500);
+
+// Event emitted to the frontend with a batch of file changes
+pub const FS_EVENT: &str = "fs-event";
+
+// Most changes sent to the frontend in a single event
+const MAX_EVENTS_PER_MESSAGE: usize = 1000;
+
+// Filesystems that do not report changes made by other machines
+const NETWORK_FILESYSTEMS: [&str; 10] = [
+    "nfs", "nfs4", "cifs", "smbfs", "smb3", "9p", "afs", "fuse.sshfs", "fuse.rclone", "davfs",
+];
+
+#[derive(Debug, Clone)]
 pub enum WatcherEvent {
     Created(PathBuf),
     Modified(PathBuf),
     Deleted(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+impl WatcherEvent {
+    // The path the event leaves behind, i.e. the new path of a rename
+    pub fn path(&self) -> &Path {
+        match self {
+            WatcherEvent::Created(path) | WatcherEvent::Modified(path) | WatcherEvent::Deleted(path) => path,
+            WatcherEvent::Renamed { to, .. } => to,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WatchMode {
+    // inotify and friends
+    Native,
+    // Periodic rescans, for network filesystems or when native watching fails
+    Polling,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchInfo {
+    pub id: u64,
+    pub path: String,
+    pub mode: WatchMode,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FsEventKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+// A change as sent to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsEvent {
+    pub kind: FsEventKind,
+    pub path: String,
+    // Previous path of a renamed file
+    pub old_path: Option<String>,
+    pub is_dir: bool,
+    // Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    // Watch that reported the change, as returned by `watch`
+    pub watch_id: u64,
+}
+
+type RawEvent = notify::Result<notify::Event>;
+
+struct Watch {
+    path: PathBuf,
+    // Only delivered to backend subscribers, not the frontend
+    internal: bool,
+    mode: WatchMode,
+    ignores: Arc<IgnoreEngine>,
+    // Dropping the watcher stops it
+    watcher: Box<dyn Watcher + Send>,
+}
+
+// Shared between the app state and the backend services using it
+#[derive(Clone)]
 pub struct FileWatcher {
-    watchers: Arc<Mutex<HashMap<String, ()>>>,
+    watchers: Arc<Mutex<HashMap<u64, Watch>>>,
+    subscribers: Arc<Mutex<Vec<mpsc::UnboundedSender<WatcherEvent>>>>,
+    raw_events: mpsc::UnboundedSender<(u64, RawEvent)>,
+    next_id: Arc<AtomicU64>,
     app: AppHandle,
 }
 
 impl FileWatcher {
     pub fn new(app: AppHandle) -> Self {
+        let (raw_events, raw_receiver) = mpsc::unbounded_channel();
+        let watchers = Arc::new(Mutex::new(HashMap::new()));
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+
+        // All watches feed one debouncer
+        tauri::async_runtime::spawn(process_events(
+            app.clone(),
+            raw_receiver,
+            watchers.clone(),
+            subscribers.clone(),
+        ));
+
         FileWatcher {
-            watchers: Arc::new(Mutex::new(HashMap::new())),
+            watchers,
+            subscribers,
+            raw_events,
+            next_id: Arc::new(AtomicU64::new(1)),
             app,
         }
     }
-    
-    pub fn watch(&self, path: &Path) -> Result<(), String> {
-        let path_str = path.to_string_lossy().to_string();
-        
-        // Check if we're already watching this path
-        {
-            let mut watchers = self.watchers.lock().unwrap();
-            if watchers.contains_key(&path_str) {
-                return Ok(());
-            }
-            
-            // Add placeholder to mark as being watched
-            watchers.insert(path_str.clone(), ());
-        }
-        
-        // Create a channel to communicate watcher events
-        let (tx, mut rx) = mpsc::channel(100);
-        
-        // Clone what we need for the watcher task
-        let path_to_watch = PathBuf::from(&path_str);
-        let app = self.app.clone();
-        let watchers = self.watchers.clone();
-        
-        // Spawn the watcher task
-        tauri::async_runtime::spawn(async move {
-            // In a real implementation, we would use something like notify-rs
-            // For now, just simulate by checking the path periodically
-            
-            // Store last modified time for all files in the watched path
-            let mut last_modified = HashMap::new();
-            
-            // Initial scan to collect file metadata
-            if path_to_watch.is_dir() {
-                match std::fs::read_dir(&path_to_watch) {
-                    Ok(entries) => {
-                        for entry in entries.filter_map(Result::ok) {
-                            let path = entry.path();
-                            if let Ok(metadata) = path.metadata() {
-                                if let Ok(modified) = metadata.modified() {
-                                    last_modified.insert(path, modified);
-                                }
-                            }
-                        }
-                    },
-                    Err(_) => {}
+
+    // Receive every event of every watched path, e.g. for backend services
+    // that react to file changes. Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<WatcherEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    // Watch a file, or a directory and everything below it, reporting
+    // changes to the frontend. Watching a path twice returns the first watch.
+    pub fn watch(&self, path: &Path) -> Result<WatchInfo, String> {
+        let existing = self
+            .watchers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, watch)| !watch.internal && watch.path == path)
+            .map(|(id, watch)| watch_info(*id, watch));
+        match existing {
+            Some(info) => Ok(info),
+            None => self.add_watch(path, false),
+        }
+    }
+
+    // Watch for backend subscribers only. Every call creates a new watch,
+    // to be removed again with `unwatch_id`.
+    pub fn watch_internal(&self, path: &Path) -> Result<WatchInfo, String> {
+        self.add_watch(path, true)
+    }
+
+    fn add_watch(&self, path: &Path, internal: bool) -> Result<WatchInfo, String> {
+        if !path.exists() {
+            return Err(format!("Path does not exist: {}", path.display()));
+        }
+
+        let settings = self
+            .app
+            .try_state::<ConfigManager>()
+            .map(|config_manager| config_manager.get_config().files)
+            .unwrap_or_default();
+
+        // Changes to ignored paths are dropped before they reach the debouncer
+        let root = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+        let ignores = Arc::new(IgnoreEngine::new(root, &settings.exclude));
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let native = !settings.watcher_polling && !is_network_filesystem(path);
+        let (watcher, mode) = if native {
+            match self.watch_native(id, path, &settings, ignores.clone()) {
+                Ok(watcher) => (watcher, WatchMode::Native),
+                Err(NativeError::Limit(message)) => return Err(message),
+                Err(NativeError::Other(e)) => {
+                    log::warn!("Native watching of {} failed, polling instead: {}", path.display(), e);
+                    (self.watch_polling(id, path, &settings, ignores.clone())?, WatchMode::Polling)
                 }
-            } else if path_to_watch.is_file() {
-                if let Ok(metadata) = path_to_watch.metadata() {
-                    if let Ok(modified) = metadata.modified() {
-                        last_modified.insert(path_to_watch.clone(), modified);
+            }
+        } else {
+            (self.watch_polling(id, path, &settings, ignores.clone())?, WatchMode::Polling)
+        };
+
+        let watch = Watch {
+            path: path.to_path_buf(),
+            internal,
+            mode,
+            ignores,
+            watcher,
+        };
+        let info = watch_info(id, &watch);
+        self.watchers.lock().unwrap().insert(id, watch);
+        Ok(info)
+    }
+
+    fn watch_native(
+        &self,
+        id: u64,
+        path: &Path,
+        settings: &FilesConfig,
+        ignores: Arc<IgnoreEngine>,
+    ) -> Result<Box<dyn Watcher + Send>, NativeError> {
+        // Each directory is watched on its own, so ignored ones such as
+        // `node_modules` or `target` cost no watches
+        let directories = directories_to_watch(path, &ignores, settings.watcher_max_directories);
+        if directories.len() > settings.watcher_max_directories {
+            return Err(NativeError::Limit(format!(
+                "{} has more than {} directories to watch (files.watcher_max_directories). \
+                 Exclude large folders from the workspace or enable files.watcher_polling.",
+                path.display(),
+                settings.watcher_max_directories
+            )));
+        }
+
+        let raw_events = self.raw_events.clone();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            forward(id, &ignores, &raw_events, event);
+        })
+        .map_err(native_error)?;
+
+        for directory in &directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive).map_err(native_error)?;
+        }
+        Ok(Box::new(watcher))
+    }
+
+    fn watch_polling(
+        &self,
+        id: u64,
+        path: &Path,
+        settings: &FilesConfig,
+        ignores: Arc<IgnoreEngine>,
+    ) -> Result<Box<dyn Watcher + Send>, String> {
+        let raw_events = self.raw_events.clone();
+        let config = notify::Config::default()
+            .with_poll_interval(Duration::from_millis(settings.watcher_poll_interval_ms.max(100)));
+        let mut watcher = PollWatcher::new(
+            move |event| {
+                forward(id, &ignores, &raw_events, event);
+            },
+            config,
+        )
+        .map_err(|e| e.to_string())?;
+
+        watcher.watch(path, recursive_mode(path)).map_err(|e| e.to_string())?;
+        Ok(Box::new(watcher))
+    }
+
+    // Stop the frontend watch of `path`
+    pub fn unwatch(&self, path: &Path) -> Result<(), String> {
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.retain(|_, watch| watch.internal || watch.path != path);
+        Ok(())
+    }
+
+    pub fn unwatch_id(&self, id: u64) -> Result<(), String> {
+        self.watchers
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .map(|_| ())
+            .ok_or_else(|| format!("No watch with id {}", id))
+    }
+}
+
+fn watch_info(id: u64, watch: &Watch) -> WatchInfo {
+    WatchInfo {
+        id,
+        path: watch.path.to_string_lossy().to_string(),
+        mode: watch.mode,
+    }
+}
+
+// Pass a raw event on with its ignored paths removed
+fn forward(id: u64, ignores: &IgnoreEngine, raw_events: &mpsc::UnboundedSender<(u64, RawEvent)>, event: RawEvent) {
+    let mut event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            let _ = raw_events.send((id, Err(e)));
+            return;
+        }
+    };
+
+    let folder = matches!(
+        event.kind,
+        EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder)
+    );
+    event.paths.retain(|path| {
+        ignores.invalidate(path);
+        !ignores.is_ignored(path, folder || path.is_dir())
+    });
+
+    if !event.paths.is_empty() {
+        let _ = raw_events.send((id, Ok(event)));
+    }
+}
+
+enum NativeError {
+    // Too many directories; falling back to polling them all would not help
+    Limit(String),
+    Other(String),
+}
+
+fn native_error(error: notify::Error) -> NativeError {
+    match error.kind {
+        notify::ErrorKind::MaxFilesWatch => {
+            let limit = fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+                .map(|limit| format!(" ({})", limit.trim()))
+                .unwrap_or_default();
+            NativeError::Limit(format!(
+                "The system limit on file watches{} has been reached. Raise fs.inotify.max_user_watches, \
+                 e.g. `sudo sysctl fs.inotify.max_user_watches=524288`, or enable files.watcher_polling.",
+                limit
+            ))
+        }
+        _ => NativeError::Other(error.to_string()),
+    }
+}
+
+fn recursive_mode(path: &Path) -> RecursiveMode {
+    if path.is_dir() {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    }
+}
+
+// `path` and the directories below it that are not ignored, or just `path`
+// for a file. Stops once there are more than `limit`.
+fn directories_to_watch(path: &Path, ignores: &IgnoreEngine, limit: usize) -> Vec<PathBuf> {
+    let mut directories = vec![path.to_path_buf()];
+    if path.is_dir() {
+        ignores.walk(path, &mut |entry, is_dir| {
+            if !is_dir || directories.len() > limit {
+                return false;
+            }
+            directories.push(entry.to_path_buf());
+            true
+        });
+    }
+    directories
+}
+
+// Native watches cover a single directory, so directories created in or
+// moved into a watched tree need watches of their own. Returns what was
+// already created inside new directories before they were watched.
+async fn watch_new_directories(watchers: &Arc<Mutex<HashMap<u64, Watch>>>, id: u64, event: &RawEvent) -> Vec<PathBuf> {
+    // Ignored paths never get here, see `forward`. Symlinked directories
+    // are not followed.
+    let (added, created) = match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))) => {
+            let added: Vec<PathBuf> = event
+                .paths
+                .iter()
+                .filter(|path| path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir()))
+                .cloned()
+                .collect();
+            (added, matches!(event.kind, EventKind::Create(_)))
+        }
+        _ => return Vec::new(),
+    };
+    if added.is_empty() {
+        return Vec::new();
+    }
+
+    let ignores = match watchers.lock().unwrap().get(&id) {
+        Some(watch) if watch.mode == WatchMode::Native => watch.ignores.clone(),
+        _ => return Vec::new(),
+    };
+
+    // A tree moved into the workspace can be large, so it is walked without
+    // holding the lock that every watch and the event loop need
+    let watchers = watchers.clone();
+    let found = tauri::async_runtime::spawn_blocking(move || {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let mut unwalked = added;
+
+        while !unwalked.is_empty() {
+            let mut directories = Vec::new();
+            for path in unwalked.drain(..) {
+                directories.push(path.clone());
+                ignores.walk(&path, &mut |entry, is_dir| {
+                    if is_dir {
+                        directories.push(entry.to_path_buf());
                     }
-                }
+                    seen.insert(entry.to_path_buf());
+                    found.push(entry.to_path_buf());
+                    is_dir
+                });
             }
-            
-            // Poll for changes
-            loop {
-                sleep(Duration::from_secs(1)).await;
-                
-                // Check if we still need to watch this path
-                {
-                    let watchers = watchers.lock().unwrap();
-                    if !watchers.contains_key(&path_str) {
-                        break;
+
+            {
+                let mut watchers = watchers.lock().unwrap();
+                // Unwatched in the meantime
+                let watch = match watchers.get_mut(&id) {
+                    Some(watch) => watch,
+                    None => return Vec::new(),
+                };
+                for directory in &directories {
+                    if let Err(e) = watch.watcher.watch(directory, RecursiveMode::NonRecursive) {
+                        log::warn!("Failed to watch {}: {}", directory.display(), e);
                     }
                 }
-                
-                // Check for new, modified, or deleted files
-                if path_to_watch.is_dir() {
-                    let mut current_files = HashMap::new();
-                    
-                    match std::fs::read_dir(&path_to_watch) {
-                        Ok(entries) => {
-                            for entry in entries.filter_map(Result::ok) {
-                                let path = entry.path();
-                                if let Ok(metadata) = path.metadata() {
-                                    if let Ok(modified) = metadata.modified() {
-                                        current_files.insert(path.clone(), modified);
-                                        
-                                        // Check for new or modified files
-                                        match last_modified.get(&path) {
-                                            Some(last_time) => {
-                                                if modified != *last_time {
-                                                    let _ = tx.send(WatcherEvent::Modified(path)).await;
-                                                }
-                                            },
-                                            None => {
-                                                let _ = tx.send(WatcherEvent::Created(path)).await;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        },
-                        Err(_) => {}
+            }
+
+            // Entries created between the walk and the watch; new
+            // directories among them are walked and watched in turn
+            for directory in &directories {
+                for entry in fs::read_dir(directory).into_iter().flatten().flatten() {
+                    let path = entry.path();
+                    let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+                    if seen.contains(&path) || ignores.is_ignored(&path, is_dir) {
+                        continue;
                     }
-                    
-                    // Check for deleted files
-                    for path in last_modified.keys() {
-                        if !current_files.contains_key(path) {
-                            let _ = tx.send(WatcherEvent::Deleted(path.clone())).await;
-                        }
+                    seen.insert(path.clone());
+                    found.push(path.clone());
+                    if is_dir {
+                        unwalked.push(path);
                     }
-                    
-                    // Update last_modified
-                    last_modified = current_files;
-                } else if path_to_watch.is_file() {
-                    match path_to_watch.metadata() {
-                        Ok(metadata) => {
-                            if let Ok(modified) = metadata.modified() {
-                                if let Some(last_time) = last_modified.get(&path_to_watch) {
-                                    if &modified != last_time {
-                                        let _ = tx.send(WatcherEvent::Modified(path_to_watch.clone())).await;
-                                        last_modified.insert(path_to_watch.clone(), modified);
-                                    }
-                                }
-                            }
-                        },
-                        Err(_) => {
-                            // File might have been deleted
-                            if last_modified.contains_key(&path_to_watch) {
-                                let _ = tx.send(WatcherEvent::Deleted(path_to_watch.clone())).await;
-                                last_modified.remove(&path_to_watch);
-                            }
-                        }
+                }
+            }
+        }
+        found
+    })
+    .await
+    .unwrap_or_default();
+
+    // The contents of a directory renamed within the tree were reported
+    // under its old name already
+    if created {
+        found
+    } else {
+        Vec::new()
+    }
+}
+
+// Whether `path` lives on a network filesystem according to /proc/mounts.
+// Always false where that file does not exist.
+fn is_network_filesystem(path: &Path) -> bool {
+    let mounts = match fs::read_to_string("/proc/mounts") {
+        Ok(mounts) => mounts,
+        Err(_) => return false,
+    };
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    // The mount point closest to the path decides
+    let mut best: Option<(PathBuf, &str)> = None;
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (mount_point, fs_type) = match (fields.nth(1), fields.next()) {
+            (Some(mount_point), Some(fs_type)) => (PathBuf::from(mount_point.replace("\\040", " ")), fs_type),
+            _ => continue,
+        };
+        let closer = match &best {
+            Some((best_point, _)) => mount_point.as_os_str().len() > best_point.as_os_str().len(),
+            None => true,
+        };
+        if path.starts_with(&mount_point) && closer {
+            best = Some((mount_point, fs_type));
+        }
+    }
+
+    best.is_some_and(|(_, fs_type)| NETWORK_FILESYSTEMS.contains(&fs_type))
+}
+
+// Collect raw events into batches per watch and hand them out once things
+// are quiet
+async fn process_events(
+    app: AppHandle,
+    mut raw_events: mpsc::UnboundedReceiver<(u64, RawEvent)>,
+    watchers: Arc<Mutex<HashMap<u64, Watch>>>,
+    subscribers: Arc<Mutex<Vec<mpsc::UnboundedSender<WatcherEvent>>>>,
+) {
+    while let Some((id, event)) = raw_events.recv().await {
+        let mut batches: HashMap<u64, EventBatch> = HashMap::new();
+        let found = watch_new_directories(&watchers, id, &event).await;
+        batches.entry(id).or_default().add_found(event, found);
+
+        let deadline = Instant::now() + MAX_DELAY;
+        let mut closed = false;
+        loop {
+            let wait = DEBOUNCE.min(deadline.saturating_duration_since(Instant::now()));
+            match timeout(wait, raw_events.recv()).await {
+                Ok(Some((id, event))) => {
+                    let found = watch_new_directories(&watchers, id, &event).await;
+                    batches.entry(id).or_default().add_found(event, found);
+                }
+                Ok(None) => {
+                    closed = true;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        let mut fs_events = Vec::new();
+
+        for (id, batch) in batches {
+            // Unwatched while the batch was collected
+            let internal = match watchers.lock().unwrap().get(&id) {
+                Some(watch) => watch.internal,
+                None => continue,
+            };
+
+            for (event, is_dir) in batch.finish() {
+                // Forward to backend subscribers, forgetting the ones that are gone
+                subscribers
+                    .lock()
+                    .unwrap()
+                    .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+
+                if !internal {
+                    fs_events.push(fs_event(id, event, is_dir, timestamp));
+                }
+            }
+        }
+
+        for chunk in fs_events.chunks(MAX_EVENTS_PER_MESSAGE) {
+            let _ = app.emit(FS_EVENT, chunk.to_vec());
+        }
+
+        if closed {
+            break;
+        }
+    }
+}
+
+fn fs_event(watch_id: u64, event: WatcherEvent, is_dir: bool, timestamp: i64) -> FsEvent {
+    let (kind, path, old_path) = match event {
+        WatcherEvent::Created(path) => (FsEventKind::Created, path, None),
+        WatcherEvent::Modified(path) => (FsEventKind::Modified, path, None),
+        WatcherEvent::Deleted(path) => (FsEventKind::Deleted, path, None),
+        WatcherEvent::Renamed { from, to } => (FsEventKind::Renamed, to, Some(from)),
+    };
+
+    FsEvent {
+        kind,
+        path: path.to_string_lossy().to_string(),
+        old_path: old_path.map(|path| path.to_string_lossy().to_string()),
+        is_dir,
+        timestamp,
+        watch_id,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pending {
+    Created,
+    Modified,
+    Deleted,
+    // Renamed from the given path
+    Renamed(PathBuf),
+}
+
+// Net effect of a series of raw events per path, e.g. a file that is
+// created and deleted again within a batch produces no event at all
+#[derive(Default)]
+struct EventBatch {
+    order: Vec<PathBuf>,
+    pending: HashMap<PathBuf, Pending>,
+    // Sources of renames whose destination has not been seen yet, by cookie
+    moves: HashMap<usize, PathBuf>,
+    // Directories reported as removed, which can no longer be checked on disk
+    removed_directories: HashSet<PathBuf>,
+}
+
+impl EventBatch {
+    fn add(&mut self, event: RawEvent) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                log::warn!("File watcher error: {}", e);
+                return;
+            }
+        };
+
+        match event.kind {
+            EventKind::Create(_) => event.paths.into_iter().for_each(|path| self.created(path)),
+            EventKind::Remove(kind) => {
+                for path in event.paths {
+                    if kind == RemoveKind::Folder {
+                        self.removed_directories.insert(path.clone());
                     }
+                    self.deleted(path);
                 }
             }
-        });
-        
-        // Clone what we need for the event handler
-        let app = self.app.clone();
-        
-        // Spawn task to handle events from the watcher
-        tauri::async_runtime::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                // Process the file system event
-                match event {
-                    WatcherEvent::Created(path) => {
-                        let _ = app.emit_all("fs-created", path.to_string_lossy().to_string());
-                    },
-                    WatcherEvent::Modified(path) => {
-                        let _ = app.emit_all("fs-modified", path.to_string_lossy().to_string());
-                    },
-                    WatcherEvent::Deleted(path) => {
-                        let _ = app.emit_all("fs-deleted", path.to_string_lossy().to_string());
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                // Held back until the matching `To` arrives. Without a cookie
+                // this is the watched directory itself moving, which its
+                // parent would report.
+                if let (Some(tracker), Some(path)) = (event.attrs.tracker(), event.paths.into_iter().next()) {
+                    self.moves.insert(tracker, path);
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                let from = event.attrs.tracker().and_then(|tracker| self.moves.remove(&tracker));
+                if let Some(to) = event.paths.into_iter().next() {
+                    match from {
+                        Some(from) => self.renamed(from, to),
+                        None => self.created(to),
                     }
                 }
             }
-        });
-        
-        Ok(())
+            // Reported after `From` and `To`, which are already paired
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {}
+            // Backends that cannot tell which side of a rename a path is on
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in event.paths {
+                    if path.exists() {
+                        self.created(path);
+                    } else {
+                        self.deleted(path);
+                    }
+                }
+            }
+            EventKind::Modify(_) | EventKind::Any => {
+                event.paths.into_iter().for_each(|path| self.modified(path))
+            }
+            EventKind::Access(_) | EventKind::Other => {}
+        }
     }
-    
-    pub fn unwatch(&self, path: &Path) -> Result<(), String> {
-        let path_str = path.to_string_lossy().to_string();
-        let mut watchers = self.watchers.lock().unwrap();
-        watchers.remove(&path_str);
-        Ok(())
+
+    // An event along with the entries found in the directories it created
+    fn add_found(&mut self, event: RawEvent, found: Vec<PathBuf>) {
+        self.add(event);
+        found.into_iter().for_each(|path| self.created(path));
+    }
+
+    fn set(&mut self, path: PathBuf, pending: Pending) {
+        if self.pending.insert(path.clone(), pending).is_none() {
+            self.order.push(path);
+        }
+    }
+
+    fn created(&mut self, path: PathBuf) {
+        match self.pending.get(&path) {
+            // Replaced within the batch
+            Some(Pending::Deleted) => self.set(path, Pending::Modified),
+            Some(_) => {}
+            None => self.set(path, Pending::Created),
+        }
+    }
+
+    fn modified(&mut self, path: PathBuf) {
+        if !self.pending.contains_key(&path) {
+            self.set(path, Pending::Modified);
+        }
+    }
+
+    fn deleted(&mut self, path: PathBuf) {
+        match self.pending.get(&path).cloned() {
+            // Never existed as far as the listeners know
+            Some(Pending::Created) => {
+                self.pending.remove(&path);
+            }
+            Some(Pending::Renamed(from)) => {
+                self.pending.remove(&path);
+                self.deleted(from);
+            }
+            _ => self.set(path, Pending::Deleted),
+        }
+    }
+
+    fn renamed(&mut self, from: PathBuf, to: PathBuf) {
+        let pending = match self.pending.remove(&from) {
+            // Created and then renamed: listeners only need the final name
+            Some(Pending::Created) | Some(Pending::Deleted) => Pending::Created,
+            // Renamed twice
+            Some(Pending::Renamed(original)) => Pending::Renamed(original),
+            Some(Pending::Modified) | None => Pending::Renamed(from),
+        };
+
+        if let Pending::Renamed(original) = &pending {
+            // Renamed back to where it started
+            if *original == to {
+                self.set(to, Pending::Modified);
+                return;
+            }
+        }
+        self.pending.remove(&to);
+        self.set(to, pending);
+    }
+
+    // The resulting events, each with whether it concerns a directory
+    fn finish(mut self) -> Vec<(WatcherEvent, bool)> {
+        // Moved out of the watched tree
+        let moved_out: Vec<PathBuf> = self.moves.drain().map(|(_, path)| path).collect();
+        for path in moved_out {
+            self.deleted(path);
+        }
+
+        // Deleting a directory implies deleting everything in it
+        let deleted_directories: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(path, pending)| **pending == Pending::Deleted && self.removed_directories.contains(*path))
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut events = Vec::new();
+        for path in self.order {
+            let pending = match self.pending.remove(&path) {
+                Some(pending) => pending,
+                // Cancelled out, or already handed out for an earlier position
+                None => continue,
+            };
+
+            if pending == Pending::Deleted {
+                let inside_deleted = deleted_directories
+                    .iter()
+                    .any(|directory| path != *directory && path.starts_with(directory));
+                if inside_deleted {
+                    continue;
+                }
+                let is_dir = self.removed_directories.contains(&path);
+                events.push((WatcherEvent::Deleted(path), is_dir));
+                continue;
+            }
+
+            let is_dir = path.is_dir();
+            let event = match pending {
+                Pending::Created => WatcherEvent::Created(path),
+                Pending::Renamed(from) => WatcherEvent::Renamed { from, to: path },
+                _ => WatcherEvent::Modified(path),
+            };
+            events.push((event, is_dir));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::Event;
+
+    fn path(name: &str) -> PathBuf {
+        PathBuf::from("/watched").join(name)
+    }
+
+    fn event(kind: EventKind, name: &str) -> RawEvent {
+        Ok(Event::new(kind).add_path(path(name)))
+    }
+
+    fn rename(from: &str, to: &str, tracker: usize) -> [RawEvent; 2] {
+        [
+            Ok(Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From)))
+                .add_path(path(from))
+                .set_tracker(tracker)),
+            Ok(Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::To)))
+                .add_path(path(to))
+                .set_tracker(tracker)),
+        ]
+    }
+
+    fn finish(events: impl IntoIterator<Item = RawEvent>) -> Vec<String> {
+        let mut batch = EventBatch::default();
+        events.into_iter().for_each(|event| batch.add(event));
+        batch
+            .finish()
+            .into_iter()
+            .map(|(event, is_dir)| {
+                let name = |path: &Path| path.strip_prefix("/watched").unwrap().display().to_string();
+                let text = match &event {
+                    WatcherEvent::Created(path) => format!("created {}", name(path)),
+                    WatcherEvent::Modified(path) => format!("modified {}", name(path)),
+                    WatcherEvent::Deleted(path) => format!("deleted {}", name(path)),
+                    WatcherEvent::Renamed { from, to } => format!("renamed {} to {}", name(from), name(to)),
+                };
+                if is_dir {
+                    format!("{}/", text)
+                } else {
+                    text
+                }
+            })
+            .collect()
+    }
+
+    const CREATE: EventKind = EventKind::Create(CreateKind::File);
+    const REMOVE: EventKind = EventKind::Remove(RemoveKind::File);
+
+    #[test]
+    fn created_and_deleted_within_a_batch_cancel_out() {
+        assert!(finish([event(CREATE, "a"), event(REMOVE, "a")]).is_empty());
+        assert_eq!(finish([event(REMOVE, "a"), event(CREATE, "a")]), ["modified a"]);
+    }
+
+    #[test]
+    fn created_then_renamed_is_created_under_the_new_name() {
+        let [from, to] = rename("a", "b", 1);
+        assert_eq!(finish([event(CREATE, "a"), from, to]), ["created b"]);
+    }
+
+    #[test]
+    fn rename_chains_collapse() {
+        let [from1, to1] = rename("a", "b", 1);
+        let [from2, to2] = rename("b", "c", 2);
+        assert_eq!(finish([from1, to1, from2, to2]), ["renamed a to c"]);
+
+        let [from1, to1] = rename("a", "b", 1);
+        let [from2, to2] = rename("b", "c", 2);
+        assert_eq!(finish([from1, to1, from2, to2, event(REMOVE, "c")]), ["deleted a"]);
+    }
+
+    #[test]
+    fn renamed_back_is_a_modification() {
+        let [from1, to1] = rename("a", "b", 1);
+        let [from2, to2] = rename("b", "a", 2);
+        assert_eq!(finish([from1, to1, from2, to2]), ["modified a"]);
+    }
+
+    #[test]
+    fn moved_in_and_out_of_the_tree() {
+        let [from, _] = rename("a", "elsewhere", 1);
+        assert_eq!(finish([from]), ["deleted a"]);
+
+        let [_, to] = rename("elsewhere", "b", 2);
+        assert_eq!(finish([to]), ["created b"]);
+    }
+
+    #[test]
+    fn removed_directories_hide_their_contents() {
+        let events = [
+            event(REMOVE, "dir/a"),
+            event(REMOVE, "dir/sub/b"),
+            event(EventKind::Remove(RemoveKind::Folder), "dir"),
+            event(REMOVE, "directory.txt"),
+        ];
+        assert_eq!(finish(events), ["deleted dir/", "deleted directory.txt"]);
     }
-} 
\ No newline at end of file
+}
\ No newline at end of file
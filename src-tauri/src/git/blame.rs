@@ -0,0 +1,315 @@
+use git2::{BlameOptions, ObjectType, Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use crate::git::stage::repository_form;
+use crate::git::{format_time, open_with_path};
+
+// Oldest entries are dropped once the cache grows past this
+const MAX_CACHED_BLAMES: usize = 64;
+
+// Author and commit of one line. Lines that are not committed yet (edited
+// in the buffer or on disk) have `committed` unset and a zero hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlameLine {
+    // 1-based line in the blamed content
+    pub line: usize,
+    // 1-based line in the commit that introduced it
+    pub original_line: usize,
+    pub hash: String,
+    pub short_hash: String,
+    pub author: String,
+    pub email: String,
+    pub date: String,
+    pub summary: String,
+    pub committed: bool,
+}
+
+// Blame results keyed by the commit they were computed at and the blob id
+// of the blamed content, so unchanged files are not blamed again
+#[derive(Clone)]
+pub struct BlameCache {
+    entries: Arc<Mutex<HashMap<String, Arc<Vec<BlameLine>>>>>,
+    order: Arc<Mutex<Vec<String>>>,
+}
+
+impl BlameCache {
+    pub fn new() -> Self {
+        BlameCache {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            order: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Arc<Vec<BlameLine>>> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn insert(&self, key: String, lines: Arc<Vec<BlameLine>>) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut order = self.order.lock().unwrap();
+
+        if entries.insert(key.clone(), lines).is_none() {
+            order.push(key);
+        }
+        while order.len() > MAX_CACHED_BLAMES {
+            let oldest = order.remove(0);
+            entries.remove(&oldest);
+        }
+    }
+}
+
+// Blame a file line by line. With a `revision` the committed file is
+// blamed; otherwise `content` (an editor buffer) or the file on disk is
+// blamed on top of HEAD. `start_line` and `end_line` are 1-based and
+// inclusive.
+pub fn blame_file(
+    cache: &BlameCache,
+    repo_path: &Path,
+    file_path: &str,
+    revision: Option<&str>,
+    content: Option<&str>,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+) -> Result<Vec<BlameLine>, String> {
+    let (repo, relative) = open_with_path(repo_path, file_path)?;
+
+    let commit = match revision {
+        Some(revision) => Some(
+            repo.revparse_single(revision)
+                .and_then(|object| object.peel_to_commit())
+                .map_err(|e| format!("Invalid revision {}: {}", revision, e.message()))?,
+        ),
+        // Nothing is committed in a new repository
+        None => repo.head().and_then(|head| head.peel_to_commit()).ok(),
+    };
+
+    let committed_entry = commit
+        .as_ref()
+        .and_then(|commit| commit.tree().ok())
+        .and_then(|tree| tree.get_path(&relative).ok());
+    let committed_blob = committed_entry.as_ref().map(|entry| entry.id());
+
+    let buffer = match revision {
+        Some(revision) => {
+            if committed_blob.is_none() {
+                return Err(format!("{} does not exist at {}", relative.display(), revision));
+            }
+            None
+        }
+        None => Some(match content {
+            Some(content) => content.as_bytes().to_vec(),
+            None => {
+                let workdir = repo
+                    .workdir()
+                    .ok_or_else(|| "Repository has no working directory".to_string())?;
+                fs::read(workdir.join(&relative)).map_err(|e| e.to_string())?
+            }
+        }),
+    };
+
+    // The buffer is in working tree form; blamed and cached as the
+    // repository would store it, so e.g. `core.autocrlf` does not make
+    // every line uncommitted
+    let buffer = match (buffer, &committed_entry) {
+        (Some(buffer), Some(entry)) => match repo.find_blob(entry.id()) {
+            Ok(blob) => Some(repository_form(&repo, &relative, &buffer, &blob, entry.filemode() as u32)?),
+            Err(_) => Some(buffer),
+        },
+        (buffer, _) => buffer,
+    };
+
+    let blob_id = match &buffer {
+        Some(buffer) => Oid::hash_object(ObjectType::Blob, buffer).map_err(|e| e.to_string())?,
+        None => committed_blob.expect("checked above"),
+    };
+    let commit_id = commit.as_ref().map(|commit| commit.id()).unwrap_or_else(Oid::zero);
+    let key = format!("{}:{}:{}", relative.display(), commit_id, blob_id);
+
+    let lines = match cache.get(&key) {
+        Some(lines) => lines,
+        None => {
+            let lines = Arc::new(compute_blame(&repo, &relative, commit_id, committed_blob.is_some(), buffer.as_deref())?);
+            cache.insert(key, lines.clone());
+            lines
+        }
+    };
+
+    let start = start_line.unwrap_or(1).max(1);
+    let end = end_line.unwrap_or(usize::MAX);
+    Ok(lines
+        .iter()
+        .filter(|line| line.line >= start && line.line <= end)
+        .cloned()
+        .collect())
+}
+
+fn compute_blame(
+    repo: &Repository,
+    relative: &Path,
+    commit_id: Oid,
+    in_commit: bool,
+    buffer: Option<&[u8]>,
+) -> Result<Vec<BlameLine>, String> {
+    if !in_commit {
+        // A new file has no history; every line is uncommitted
+        let count = buffer.map_or(0, line_count);
+        return Ok((1..=count).map(uncommitted_line).collect());
+    }
+
+    let mut options = BlameOptions::new();
+    options.newest_commit(commit_id);
+    let committed = repo
+        .blame_file(relative, Some(&mut options))
+        .map_err(|e| e.to_string())?;
+
+    let blame = match buffer {
+        Some(buffer) => committed.blame_buffer(buffer).map_err(|e| e.to_string())?,
+        None => committed,
+    };
+
+    let mut summaries: HashMap<Oid, (String, String, String, String)> = HashMap::new();
+    let mut lines = Vec::new();
+
+    for hunk in blame.iter() {
+        let oid = hunk.final_commit_id();
+        let start = hunk.final_start_line();
+        let original_start = hunk.orig_start_line();
+
+        for offset in 0..hunk.lines_in_hunk() {
+            let line = start + offset;
+            let original_line = original_start + offset;
+
+            if oid.is_zero() {
+                lines.push(uncommitted_line(line));
+                continue;
+            }
+
+            let (author, email, date, summary) = summaries.entry(oid).or_insert_with(|| match repo.find_commit(oid) {
+                Ok(commit) => {
+                    let author = commit.author();
+                    (
+                        author.name().unwrap_or_default().to_string(),
+                        author.email().unwrap_or_default().to_string(),
+                        format_time(author.when()),
+                        commit.summary().unwrap_or_default().to_string(),
+                    )
+                }
+                Err(_) => Default::default(),
+            });
+
+            let hash = oid.to_string();
+            lines.push(BlameLine {
+                line,
+                original_line,
+                short_hash: hash[..7].to_string(),
+                hash,
+                author: author.clone(),
+                email: email.clone(),
+                date: date.clone(),
+                summary: summary.clone(),
+                committed: true,
+            });
+        }
+    }
+
+    Ok(lines)
+}
+
+fn uncommitted_line(line: usize) -> BlameLine {
+    let hash = Oid::zero().to_string();
+    BlameLine {
+        line,
+        original_line: line,
+        short_hash: hash[..7].to_string(),
+        hash,
+        author: "Not Committed Yet".to_string(),
+        email: String::new(),
+        date: String::new(),
+        summary: String::new(),
+        committed: false,
+    }
+}
+
+fn line_count(content: &[u8]) -> usize {
+    if content.is_empty() {
+        return 0;
+    }
+    let newlines = content.iter().filter(|byte| **byte == b'\n').count();
+    if content.ends_with(b"\n") {
+        newlines
+    } else {
+        newlines + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    fn summaries(lines: &[BlameLine]) -> Vec<(usize, &str, bool)> {
+        lines.iter().map(|line| (line.line, line.summary.as_str(), line.committed)).collect()
+    }
+
+    #[test]
+    fn blame_attributes_lines_to_commits() {
+        let dir = init_repo();
+        commit_file(dir.path(), "f.txt", "one\ntwo\n", "first");
+        commit_file(dir.path(), "f.txt", "one\nTWO\nthree\n", "second");
+        let cache = BlameCache::new();
+
+        let lines = blame_file(&cache, dir.path(), "f.txt", None, None, None, None).unwrap();
+        assert_eq!(summaries(&lines), [(1, "first", true), (2, "second", true), (3, "second", true)]);
+        assert_eq!(lines[0].author, "Test");
+
+        let lines = blame_file(&cache, dir.path(), "f.txt", Some("HEAD~1"), None, Some(2), Some(2)).unwrap();
+        assert_eq!(summaries(&lines), [(2, "first", true)]);
+    }
+
+    #[test]
+    fn buffer_edits_are_uncommitted() {
+        let dir = init_repo();
+        commit_file(dir.path(), "f.txt", "one\ntwo\n", "first");
+        let cache = BlameCache::new();
+
+        let lines = blame_file(&cache, dir.path(), "f.txt", None, Some("zero\none\ntwo\n"), None, None).unwrap();
+        assert_eq!(summaries(&lines), [(1, "", false), (2, "first", true), (3, "first", true)]);
+        assert_eq!(lines[1].original_line, 1);
+        assert_eq!(git(dir.path(), &["status", "--porcelain"]), "");
+    }
+
+    #[test]
+    fn checked_out_line_endings_are_not_edits() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "f.txt", "one\ntwo\n", "first");
+        git(path, &["config", "core.autocrlf", "true"]);
+        fs::remove_file(path.join("f.txt")).unwrap();
+        git(path, &["checkout", "--", "f.txt"]);
+        let cache = BlameCache::new();
+
+        let lines = blame_file(&cache, path, "f.txt", None, None, None, None).unwrap();
+        assert_eq!(summaries(&lines), [(1, "first", true), (2, "first", true)]);
+
+        let lines = blame_file(&cache, path, "f.txt", None, Some("one\r\nTWO\r\n"), None, None).unwrap();
+        assert_eq!(summaries(&lines), [(1, "first", true), (2, "", false)]);
+
+        // The buffer is cached in its normalized form
+        blame_file(&cache, path, "f.txt", None, Some("one\nTWO\n"), None, None).unwrap();
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn missing_file_at_revision_is_an_error() {
+        let dir = init_repo();
+        commit_file(dir.path(), "a.txt", "a\n", "first");
+        commit_file(dir.path(), "f.txt", "f\n", "second");
+        let cache = BlameCache::new();
+
+        let error = blame_file(&cache, dir.path(), "f.txt", Some("HEAD~1"), None, None, None).unwrap_err();
+        assert_eq!(error, "f.txt does not exist at HEAD~1");
+    }
+}
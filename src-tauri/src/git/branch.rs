@@ -0,0 +1,396 @@
+use git2::build::CheckoutBuilder;
+use git2::{Branch, BranchType, Repository, StatusOptions};
+use std::path::Path;
+use crate::config::GitConfig;
+use crate::git::commit::resolve_signature;
+use crate::git::{commit_info, open_repo, BranchInfo};
+
+// List local and remote-tracking branches with upstream and tip details
+pub fn get_branches(path: &Path) -> Result<Vec<BranchInfo>, String> {
+    let repo = open_repo(path)?;
+    let mut branches = Vec::new();
+
+    for item in repo.branches(None).map_err(|e| e.to_string())? {
+        let (branch, branch_type) = item.map_err(|e| e.to_string())?;
+        // Skip symbolic refs such as origin/HEAD
+        if branch.get().symbolic_target().is_some() {
+            continue;
+        }
+        branches.push(branch_info(&repo, &branch, branch_type)?);
+    }
+
+    // Local branches first, then remote-tracking branches, each by name
+    branches.sort_by(|a, b| a.is_remote.cmp(&b.is_remote).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(branches)
+}
+
+fn branch_info(repo: &Repository, branch: &Branch<'_>, branch_type: BranchType) -> Result<BranchInfo, String> {
+    let name = branch
+        .name()
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default()
+        .to_string();
+    let refname = branch.get().name().unwrap_or_default().to_string();
+    let is_remote = branch_type == BranchType::Remote;
+
+    let remote = if is_remote {
+        repo.branch_remote_name(&refname).ok()
+    } else {
+        repo.branch_upstream_remote(&refname).ok()
+    }
+    .and_then(|buf| buf.as_str().map(str::to_string));
+
+    let tip = branch.get().peel_to_commit().ok();
+
+    let mut upstream = None;
+    let mut ahead = 0;
+    let mut behind = 0;
+    if !is_remote {
+        if let Ok(upstream_branch) = branch.upstream() {
+            upstream = upstream_branch.name().ok().flatten().map(str::to_string);
+            if let (Some(local), Some(remote)) = (branch.get().target(), upstream_branch.get().target()) {
+                let (a, b) = repo.graph_ahead_behind(local, remote).map_err(|e| e.to_string())?;
+                ahead = a;
+                behind = b;
+            }
+        }
+    }
+
+    Ok(BranchInfo {
+        name,
+        is_current: branch.is_head(),
+        is_remote,
+        remote,
+        upstream,
+        ahead,
+        behind,
+        last_commit: tip.as_ref().map(commit_info),
+    })
+}
+
+// Whether tracked files have staged or unstaged changes
+pub fn has_local_changes(repo: &Repository) -> Result<bool, String> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut options)).map_err(|e| e.to_string())?;
+    Ok(!statuses.is_empty())
+}
+
+// Switch to a branch. With uncommitted changes the checkout is refused
+// unless `stash` is given, in which case the changes are stashed as that
+// identity right before the checkout, and restored if it fails. Checking
+// out a remote-tracking branch creates a local branch tracking it.
+pub fn checkout_branch(path: &Path, branch_name: &str, stash: Option<&GitConfig>) -> Result<(), String> {
+    let mut repo = open_repo(path)?;
+
+    let dirty = has_local_changes(&repo)?;
+    if dirty && stash.is_none() {
+        return Err("Working tree has uncommitted changes. Commit or stash them before switching branches.".to_string());
+    }
+
+    // Everything that can fail without touching the working tree comes
+    // before the stash
+    let (refname, created) = match repo.find_branch(branch_name, BranchType::Local) {
+        Ok(branch) => (branch.get().name().unwrap_or_default().to_string(), None),
+        Err(_) => {
+            let local_name = create_tracking_branch(&repo, branch_name)?;
+            (format!("refs/heads/{}", local_name), Some(local_name))
+        }
+    };
+    let target = repo
+        .find_reference(&refname)
+        .and_then(|reference| reference.peel_to_commit())
+        .map(|commit| commit.id());
+    let target = match target {
+        Ok(target) => target,
+        Err(e) => {
+            delete_created(&repo, created.as_deref());
+            return Err(e.to_string());
+        }
+    };
+
+    let mut stashed = false;
+    if let (true, Some(identity)) = (dirty, stash) {
+        let stashed_changes = resolve_signature(&repo, identity).and_then(|signature| {
+            let message = format!("Auto-stash before checkout of {}", branch_name);
+            repo.stash_save(&signature, &message, None).map_err(|e| e.to_string())
+        });
+        if let Err(e) = stashed_changes {
+            delete_created(&repo, created.as_deref());
+            return Err(e);
+        }
+        stashed = true;
+    }
+
+    let result = repo
+        .find_commit(target)
+        .and_then(|commit| {
+            let mut checkout = CheckoutBuilder::new();
+            checkout.safe();
+            repo.checkout_tree(commit.as_object(), Some(&mut checkout))
+        })
+        .and_then(|_| repo.set_head(&refname))
+        .map_err(|e| e.to_string());
+
+    if let Err(e) = result {
+        delete_created(&repo, created.as_deref());
+        if stashed {
+            if let Err(pop_error) = repo.stash_pop(0, None) {
+                return Err(format!("{}. The changes were left in the stash: {}", e, pop_error));
+            }
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Create a local branch for a remote-tracking one, "origin/feature"
+// becoming "feature" tracking it. Returns the local name.
+fn create_tracking_branch(repo: &Repository, branch_name: &str) -> Result<String, String> {
+    let remote_branch = repo
+        .find_branch(branch_name, BranchType::Remote)
+        .map_err(|_| format!("Branch not found: {}", branch_name))?;
+    let commit = remote_branch.get().peel_to_commit().map_err(|e| e.to_string())?;
+
+    let remote_refname = remote_branch.get().name().unwrap_or_default().to_string();
+    let remote_name = repo
+        .branch_remote_name(&remote_refname)
+        .ok()
+        .and_then(|buf| buf.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let local_name = branch_name
+        .strip_prefix(&format!("{}/", remote_name))
+        .unwrap_or(branch_name)
+        .to_string();
+    if repo.find_branch(&local_name, BranchType::Local).is_ok() {
+        return Err(format!("A local branch named {} already exists", local_name));
+    }
+
+    let mut local = repo.branch(&local_name, &commit, false).map_err(|e| e.to_string())?;
+    if let Err(e) = local.set_upstream(Some(branch_name)) {
+        let _ = local.delete();
+        return Err(e.to_string());
+    }
+    Ok(local_name)
+}
+
+// Remove a branch created for a checkout that did not happen
+fn delete_created(repo: &Repository, created: Option<&str>) {
+    if let Some(mut branch) = created.and_then(|name| repo.find_branch(name, BranchType::Local).ok()) {
+        let _ = branch.delete();
+    }
+}
+
+// Create a branch from any revision (commit, tag or branch), defaulting to
+// HEAD. A checked out branch at HEAD keeps local changes, like
+// `git checkout -b`; one elsewhere is removed again if it cannot be
+// checked out.
+pub fn create_branch(path: &Path, branch_name: &str, start_point: Option<&str>, checkout: bool) -> Result<(), String> {
+    let repo = open_repo(path)?;
+
+    let commit = repo
+        .revparse_single(start_point.unwrap_or("HEAD"))
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| format!("Invalid start point {}: {}", start_point.unwrap_or("HEAD"), e.message()))?;
+
+    let branch = repo.branch(branch_name, &commit, false)
+        .map_err(|e| e.to_string())?;
+
+    if !checkout {
+        return Ok(());
+    }
+
+    let at_head = repo
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .is_some_and(|head| head == commit.id());
+    if at_head {
+        let refname = branch.get().name().unwrap_or_default().to_string();
+        return repo.set_head(&refname).map_err(|e| e.to_string());
+    }
+
+    if let Err(e) = checkout_branch(path, branch_name, None) {
+        delete_created(&repo, Some(branch_name));
+        return Err(e);
+    }
+    Ok(())
+}
+
+pub fn rename_branch(path: &Path, old_name: &str, new_name: &str, force: bool) -> Result<(), String> {
+    let repo = open_repo(path)?;
+    let mut branch = repo
+        .find_branch(old_name, BranchType::Local)
+        .map_err(|_| format!("Branch not found: {}", old_name))?;
+
+    branch.rename(new_name, force).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Delete a local branch. Unless `force` is set, a branch whose commits are
+// not contained in HEAD or its upstream is kept.
+pub fn delete_branch(path: &Path, branch_name: &str, force: bool) -> Result<(), String> {
+    let repo = open_repo(path)?;
+    let mut branch = repo
+        .find_branch(branch_name, BranchType::Local)
+        .map_err(|_| format!("Branch not found: {}", branch_name))?;
+
+    if branch.is_head() {
+        return Err(format!("Cannot delete the checked out branch {}", branch_name));
+    }
+
+    if !force {
+        let tip = branch
+            .get()
+            .target()
+            .ok_or_else(|| format!("Branch {} has no target", branch_name))?;
+
+        let mut merged_into = Vec::new();
+        if let Some(head) = repo.head().ok().and_then(|head| head.target()) {
+            merged_into.push(head);
+        }
+        if let Some(upstream) = branch.upstream().ok().and_then(|upstream| upstream.get().target()) {
+            merged_into.push(upstream);
+        }
+
+        let merged = merged_into.iter().any(|&target| {
+            target == tip || repo.graph_descendant_of(target, tip).unwrap_or(false)
+        });
+        if !merged {
+            return Err(format!("Branch {} is not fully merged", branch_name));
+        }
+    }
+
+    branch.delete().map_err(|e| e.to_string())
+}
+
+// Set the upstream of a local branch (e.g. "origin/main"), or unset it with `None`
+pub fn set_upstream(path: &Path, branch_name: &str, upstream: Option<&str>) -> Result<(), String> {
+    let repo = open_repo(path)?;
+    let mut branch = repo
+        .find_branch(branch_name, BranchType::Local)
+        .map_err(|_| format!("Branch not found: {}", branch_name))?;
+
+    branch.set_upstream(upstream).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+    use std::fs;
+
+    #[test]
+    fn checkout_with_changes_needs_a_stash() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        git(path, &["branch", "other"]);
+        fs::write(path.join("a.txt"), "changed\n").unwrap();
+
+        let error = checkout_branch(path, "other", None).unwrap_err();
+        assert!(error.contains("uncommitted changes"));
+        assert_eq!(git(path, &["branch", "--show-current"]), "main\n");
+    }
+
+    #[test]
+    fn auto_stash_uses_the_configured_identity() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        git(path, &["branch", "other"]);
+        fs::write(path.join("a.txt"), "changed\n").unwrap();
+        let identity = GitConfig {
+            author_name: Some("Settings Name".to_string()),
+            author_email: Some("settings@example.com".to_string()),
+        };
+
+        checkout_branch(path, "other", Some(&identity)).unwrap();
+        assert_eq!(git(path, &["branch", "--show-current"]), "other\n");
+        assert_eq!(fs::read_to_string(path.join("a.txt")).unwrap(), "a\n");
+        assert_eq!(
+            git(path, &["log", "-1", "--format=%an <%ae>|%s", "refs/stash"]),
+            "Settings Name <settings@example.com>|On main: Auto-stash before checkout of other\n"
+        );
+    }
+
+    #[test]
+    fn failed_checkout_keeps_the_changes_in_place() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        git(path, &["checkout", "-q", "-b", "other"]);
+        commit_file(path, "b.txt", "from other\n", "add b");
+        git(path, &["checkout", "-q", "main"]);
+        fs::write(path.join("a.txt"), "changed\n").unwrap();
+        let identity = GitConfig::default();
+
+        let error = checkout_branch(path, "missing", Some(&identity)).unwrap_err();
+        assert_eq!(error, "Branch not found: missing");
+        assert_eq!(git(path, &["stash", "list"]), "");
+
+        // An untracked file in the way makes the checkout itself fail
+        // after the stash was taken
+        fs::write(path.join("b.txt"), "untracked\n").unwrap();
+        assert!(checkout_branch(path, "other", Some(&identity)).is_err());
+        assert_eq!(git(path, &["branch", "--show-current"]), "main\n");
+        assert_eq!(fs::read_to_string(path.join("a.txt")).unwrap(), "changed\n");
+        assert_eq!(git(path, &["stash", "list"]), "");
+    }
+
+    #[test]
+    fn create_and_checkout_carries_local_changes_from_head() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        commit_file(path, "a.txt", "a2\n", "second");
+        fs::write(path.join("a.txt"), "changed\n").unwrap();
+
+        create_branch(path, "topic", None, true).unwrap();
+        assert_eq!(git(path, &["branch", "--show-current"]), "topic\n");
+        assert_eq!(fs::read_to_string(path.join("a.txt")).unwrap(), "changed\n");
+
+        // Elsewhere the checkout is refused and the branch is not kept
+        assert!(create_branch(path, "old", Some("HEAD~1"), true).unwrap_err().contains("uncommitted changes"));
+        assert!(get_branches(path).unwrap().iter().all(|branch| branch.name != "old"));
+    }
+
+    #[test]
+    fn checkout_of_a_remote_branch_creates_a_tracking_branch() {
+        let upstream = init_repo();
+        commit_file(upstream.path(), "a.txt", "a\n", "initial");
+        git(upstream.path(), &["branch", "feature"]);
+        let dir = tempfile::tempdir().unwrap();
+        let source = upstream.path().to_string_lossy().to_string();
+        git(dir.path(), &["clone", "-q", &source, "clone"]);
+        let path = dir.path().join("clone");
+
+        // Refused with changes, without leaving the tracking branch behind
+        fs::write(path.join("a.txt"), "changed\n").unwrap();
+        assert!(checkout_branch(&path, "origin/feature", None).is_err());
+        assert!(get_branches(&path).unwrap().iter().all(|branch| branch.name != "feature"));
+        git(&path, &["checkout", "-q", "a.txt"]);
+
+        checkout_branch(&path, "origin/feature", None).unwrap();
+        assert_eq!(git(&path, &["branch", "--show-current"]), "feature\n");
+        let branches = get_branches(&path).unwrap();
+        let feature = branches.iter().find(|branch| branch.name == "feature").unwrap();
+        assert!(feature.is_current);
+        assert_eq!(feature.upstream.as_deref(), Some("origin/feature"));
+    }
+
+    #[test]
+    fn unmerged_branches_are_only_deleted_with_force() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        create_branch(path, "topic", None, true).unwrap();
+        commit_file(path, "b.txt", "b\n", "topic work");
+        checkout_branch(path, "main", None).unwrap();
+
+        assert!(delete_branch(path, "topic", false).unwrap_err().contains("not fully merged"));
+        delete_branch(path, "topic", true).unwrap();
+        assert!(get_branches(path).unwrap().iter().all(|branch| branch.name != "topic"));
+    }
+}
@@ -0,0 +1,270 @@
+use git2::build::{CheckoutBuilder, CloneLocal, RepoBuilder};
+use git2::{FetchOptions, Repository, RepositoryInitOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::git::sync::{remote_callbacks, CredentialProvider, GitProgress, ProgressReporter};
+
+// Create a repository, optionally with a different initial branch than the
+// one configured by `init.defaultBranch`
+pub fn init_repository(path: &Path, initial_branch: Option<&str>, bare: bool) -> Result<PathBuf, String> {
+    if let Ok(existing) = Repository::open(path) {
+        return Err(format!("{} is already a git repository", existing.path().display()));
+    }
+
+    let mut options = RepositoryInitOptions::new();
+    options.bare(bare).mkpath(true);
+    if let Some(branch) = initial_branch.filter(|branch| !branch.trim().is_empty()) {
+        if !git2::Branch::name_is_valid(branch).unwrap_or(false) {
+            return Err(format!("Invalid branch name: {}", branch));
+        }
+        options.initial_head(branch);
+    }
+
+    let repo = Repository::init_opts(path, &options).map_err(|e| e.to_string())?;
+    Ok(repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf())
+}
+
+// Cancellation flags of running clones by the id the frontend gave them
+pub struct CloneRegistry {
+    clones: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl CloneRegistry {
+    pub fn new() -> Self {
+        CloneRegistry {
+            clones: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn register(&self, id: &str) -> Result<Arc<AtomicBool>, String> {
+        let mut clones = self.clones.lock().unwrap();
+        if clones.contains_key(id) {
+            return Err(format!("Clone {} is already running", id));
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        clones.insert(id.to_string(), cancelled.clone());
+        Ok(cancelled)
+    }
+
+    pub fn finish(&self, id: &str) {
+        self.clones.lock().unwrap().remove(id);
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        match self.clones.lock().unwrap().get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            None => Err(format!("No clone running with id {}", id)),
+        }
+    }
+}
+
+// Clone a repository from a local path or `file://` URL into `destination`,
+// which must not exist or be empty. A cancelled clone removes what it wrote.
+pub fn clone_repository(
+    source: &str,
+    destination: &Path,
+    branch: Option<&str>,
+    cancelled: &AtomicBool,
+    progress: &dyn Fn(GitProgress),
+    credentials: &dyn CredentialProvider,
+) -> Result<PathBuf, String> {
+    let url = local_clone_url(source)?;
+
+    let created = !destination.exists();
+    if !created {
+        let empty = fs::read_dir(destination)
+            .map_err(|e| e.to_string())?
+            .next()
+            .is_none();
+        if !empty {
+            return Err(format!("{} already exists and is not empty", destination.display()));
+        }
+    }
+
+    let reporter = ProgressReporter::new("clone", progress).with_cancel(cancelled);
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(&reporter, credentials));
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout.progress(|_, current, total| {
+        reporter.report("checkout", current, total, 0, None);
+    });
+
+    let mut builder = RepoBuilder::new();
+    builder.fetch_options(fetch_options).with_checkout(checkout);
+    // Copying the object database directly would skip the transfer progress
+    // and with it the chance to cancel
+    builder.clone_local(CloneLocal::None);
+    if let Some(branch) = branch.filter(|branch| !branch.trim().is_empty()) {
+        builder.branch(branch);
+    }
+
+    match builder.clone(&url, destination) {
+        Ok(repo) => Ok(repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf()),
+        Err(e) => {
+            // Leave nothing half-cloned behind
+            if created {
+                let _ = fs::remove_dir_all(destination);
+            } else if let Ok(entries) = fs::read_dir(destination) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    let _ = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+                }
+            }
+
+            if cancelled.load(Ordering::SeqCst) {
+                Err("Clone cancelled".to_string())
+            } else {
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
+// Only local repositories can be cloned for now. Returns the URL to give
+// libgit2 for a local path or `file://` URL.
+fn local_clone_url(source: &str) -> Result<String, String> {
+    let path = local_source_path(source)
+        .ok_or_else(|| format!("Only local paths and file:// URLs can be cloned: {}", source.trim()))?;
+
+    if Repository::open(&path).is_err() && Repository::open_bare(&path).is_err() {
+        return Err(format!("{} is not a git repository", path.display()));
+    }
+
+    let path = path.canonicalize().map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+// The path a clone source refers to, or None for a remote URL
+pub fn local_source_path(source: &str) -> Option<PathBuf> {
+    let source = source.trim();
+    match source.strip_prefix("file://") {
+        Some(path) => Some(PathBuf::from(path)),
+        None if source.contains("://") || is_scp_like(source) => None,
+        None => Some(PathBuf::from(source)),
+    }
+}
+
+// `user@host:path` style SSH remotes; Windows drive letters are not
+fn is_scp_like(source: &str) -> bool {
+    match source.find(':') {
+        Some(index) => index > 1 && !source[..index].contains(['/', '\\']),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::sync::DefaultCredentials;
+    use crate::git::tests::{commit_file, git, init_repo};
+    use tempfile::TempDir;
+
+    // A repository with `main` and a `feature` branch
+    fn source() -> TempDir {
+        let dir = init_repo();
+        commit_file(dir.path(), "a.txt", "a\n", "initial");
+        git(dir.path(), &["branch", "feature"]);
+        git(dir.path(), &["checkout", "-q", "feature"]);
+        commit_file(dir.path(), "b.txt", "b\n", "feature");
+        git(dir.path(), &["checkout", "-q", "main"]);
+        dir
+    }
+
+    fn clone(source: &str, destination: &Path, branch: Option<&str>) -> Result<PathBuf, String> {
+        let cancelled = AtomicBool::new(false);
+        clone_repository(source, destination, branch, &cancelled, &|_| {}, &DefaultCredentials::global())
+    }
+
+    #[test]
+    fn init_with_an_initial_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new/project");
+
+        let workdir = init_repository(&path, Some("trunk"), false).unwrap();
+        assert_eq!(workdir.canonicalize().unwrap(), path.canonicalize().unwrap());
+        assert_eq!(git(&path, &["symbolic-ref", "HEAD"]).trim(), "refs/heads/trunk");
+        assert!(init_repository(&path, None, false).unwrap_err().contains("already a git repository"));
+
+        let bare = dir.path().join("bare.git");
+        init_repository(&bare, Some("  "), true).unwrap();
+        assert!(Repository::open_bare(&bare).unwrap().is_bare());
+        assert!(init_repository(&dir.path().join("other"), Some("bad..name"), false)
+            .unwrap_err()
+            .starts_with("Invalid branch name"));
+    }
+
+    #[test]
+    fn clone_from_a_path_and_a_file_url() {
+        let source = source();
+        let dir = tempfile::tempdir().unwrap();
+
+        let from_path = dir.path().join("from-path");
+        clone(&source.path().to_string_lossy(), &from_path, None).unwrap();
+        assert_eq!(fs::read_to_string(from_path.join("a.txt")).unwrap(), "a\n");
+        assert_eq!(git(&from_path, &["rev-parse", "--abbrev-ref", "HEAD"]).trim(), "main");
+
+        let from_url = dir.path().join("from-url");
+        let url = format!("file://{}", source.path().display());
+        clone(&url, &from_url, Some("feature")).unwrap();
+        assert!(from_url.join("b.txt").is_file());
+        assert_eq!(git(&from_url, &["rev-parse", "--abbrev-ref", "HEAD"]).trim(), "feature");
+
+        // An empty directory is fine
+        let empty = dir.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        clone(&url, &empty, None).unwrap();
+        assert!(empty.join("a.txt").is_file());
+    }
+
+    #[test]
+    fn non_empty_destinations_and_remote_urls_are_refused() {
+        let source = source();
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("keep.txt"), "keep").unwrap();
+
+        let error = clone(&source.path().to_string_lossy(), dir.path(), None).unwrap_err();
+        assert!(error.ends_with("already exists and is not empty"));
+        assert_eq!(fs::read_to_string(dir.path().join("keep.txt")).unwrap(), "keep");
+
+        let destination = dir.path().join("clone");
+        assert!(clone("https://example.com/a.git", &destination, None).unwrap_err().starts_with("Only local paths"));
+        assert!(clone(&dir.path().to_string_lossy(), &destination, None).unwrap_err().ends_with("is not a git repository"));
+        assert!(!destination.exists());
+    }
+
+    #[test]
+    fn a_failed_clone_leaves_nothing_behind() {
+        let source = source();
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("clone");
+
+        assert!(clone(&source.path().to_string_lossy(), &destination, Some("missing")).is_err());
+        assert!(!destination.exists());
+    }
+
+    #[test]
+    fn local_sources_and_scp_like_remotes() {
+        assert_eq!(local_source_path("file:///srv/repo.git"), Some(PathBuf::from("/srv/repo.git")));
+        assert_eq!(local_source_path("  ../repo "), Some(PathBuf::from("../repo")));
+        assert_eq!(local_source_path("https://example.com/repo.git"), None);
+        assert_eq!(local_source_path("ssh://git@example.com/repo.git"), None);
+        assert_eq!(local_source_path("git@example.com:team/repo.git"), None);
+
+        assert!(is_scp_like("git@example.com:repo.git"));
+        assert!(is_scp_like("example.com:repo.git"));
+        // Drive letters and paths with a colon after a separator are local
+        assert!(!is_scp_like("C:\\src\\repo"));
+        assert!(!is_scp_like("C:/src/repo"));
+        assert!(!is_scp_like("./odd:name"));
+        assert!(!is_scp_like("src\\odd:name"));
+        assert!(!is_scp_like("repo"));
+        assert_eq!(local_source_path("C:\\src\\repo"), Some(PathBuf::from("C:\\src\\repo")));
+    }
+}
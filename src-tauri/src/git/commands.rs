@@ -1,75 +1,839 @@
-use tauri::command;
+use tauri::{command, AppHandle, Emitter, State};
 use std::path::{Path, PathBuf};
-use crate::git::{StatusEntry, BranchInfo, CommitInfo, get_repo_status, get_branches, get_commits};
+use crate::git::{StatusEntry, BranchInfo, CommitInfo, get_repo_status, open_repo, open_with_path};
+use crate::git::blame::{self, BlameCache, BlameLine};
+use crate::git::branch;
+use crate::git::clone::{self, CloneRegistry};
+use crate::git::diff::{self, DiffSettings, FileDiff, GutterChange};
+use crate::git::log::{self, LogPage, LogQuery};
+use crate::git::commit::{create_commit, CommitError, CommitOptions, CommitResult};
+use crate::git::conflict::{self, ConflictChoice, ConflictFile, ConflictRegion, RegionResolution};
+use crate::config::ConfigManager;
+use crate::editor::DocumentManager;
+use crate::git::rebase::{self, RebasePlan};
+use crate::git::remote::{self, RemoteInfo};
+use crate::git::repo_config::{self, ConfigEntry};
+use crate::git::sequencer::{self, SequenceOptions};
+use crate::git::stage::{self, Selection};
+use crate::git::stash::{self, StashApplyResult, StashEntry, StashPushOptions};
+use crate::git::state::{self, OperationResult, RepositoryStateInfo};
+use crate::git::sync::{self, GitProgress, PullOptions, PullResult, PushResult, PushSettings};
+use crate::git::tag::{self, TagInfo};
+use crate::git::watch::GitStatusService;
+use crate::git::worktree::{self, WorktreeInfo};
+use crate::workspace::WorkspaceTrust;
 
 #[command]
-pub async fn get_status(path: String) -> Result<Vec<StatusEntry>, String> {
-    get_repo_status(Path::new(&path))
+pub async fn get_status(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    include_ignored: Option<bool>,
+) -> Result<Vec<StatusEntry>, String> {
+    check_repo(&trust, "get_status", &path)?;
+    get_repo_status(Path::new(&path), include_ignored.unwrap_or(false))
 }
 
 #[command]
-pub async fn get_all_branches(path: String) -> Result<Vec<BranchInfo>, String> {
-    get_branches(Path::new(&path))
+pub async fn get_all_branches(trust: State<'_, WorkspaceTrust>, path: String) -> Result<Vec<BranchInfo>, String> {
+    check_repo(&trust, "get_all_branches", &path)?;
+    branch::get_branches(Path::new(&path))
 }
 
 #[command]
-pub async fn get_recent_commits(path: String, limit: usize) -> Result<Vec<CommitInfo>, String> {
-    get_commits(Path::new(&path), limit)
+pub async fn get_recent_commits(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    limit: usize,
+) -> Result<Vec<CommitInfo>, String> {
+    check_repo(&trust, "get_recent_commits", &path)?;
+    let query = LogQuery {
+        limit: Some(limit),
+        ..LogQuery::default()
+    };
+    let page = tauri::async_runtime::spawn_blocking(move || log::get_commits(Path::new(&path), &query))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(page.commits.into_iter().map(|entry| entry.commit).collect())
 }
 
 #[command]
-pub async fn checkout_branch(path: String, branch_name: String) -> Result<(), String> {
-    // Mock implementation
-    // In a real application, this would use libgit2 to perform the operation
-    Ok(())
+pub async fn get_commit_log(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    query: LogQuery,
+) -> Result<LogPage, String> {
+    check_repo(&trust, "get_commit_log", &path)?;
+    // Path filters diff every commit walked
+    tauri::async_runtime::spawn_blocking(move || log::get_commits(Path::new(&path), &query))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[command]
-pub async fn create_branch(path: String, branch_name: String) -> Result<(), String> {
-    // Mock implementation
-    // In a real application, this would use libgit2 to perform the operation
-    Ok(())
+pub async fn get_workdir_diff(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    settings: Option<DiffSettings>,
+) -> Result<Vec<FileDiff>, String> {
+    check_repo(&trust, "get_workdir_diff", &path)?;
+    diff::diff_workdir(Path::new(&path), &settings.unwrap_or_default())
 }
 
 #[command]
-pub async fn commit(path: String, message: String) -> Result<CommitInfo, String> {
-    // Mock implementation
-    // In a real application, this would use libgit2 to perform the operation
-    
-    // Return mock data for now
-    Ok(CommitInfo {
-        hash: "0000000000000000000000000000000000000000".to_string(),
-        short_hash: "0000000".to_string(),
-        author: "Current User <user@example.com>".to_string(),
-        date: chrono::Utc::now().to_rfc3339(),
-        message,
+pub async fn get_staged_diff(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    settings: Option<DiffSettings>,
+) -> Result<Vec<FileDiff>, String> {
+    check_repo(&trust, "get_staged_diff", &path)?;
+    diff::diff_staged(Path::new(&path), &settings.unwrap_or_default())
+}
+
+#[command]
+pub async fn get_revision_diff(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    from: String,
+    to: String,
+    settings: Option<DiffSettings>,
+) -> Result<Vec<FileDiff>, String> {
+    check_repo(&trust, "get_revision_diff", &path)?;
+    diff::diff_revisions(Path::new(&path), &from, &to, &settings.unwrap_or_default())
+}
+
+#[command]
+pub async fn get_gutter_diff(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    content: Option<String>,
+) -> Result<Vec<GutterChange>, String> {
+    check_repo(&trust, "get_gutter_diff", &path)?;
+    trust.check("get_gutter_diff", &file_in_repo(&path, &file_path))?;
+    diff::gutter_diff(Path::new(&path), &file_path, content.as_deref())
+}
+
+// Blame a file, or with `document_uri` the unsaved contents of an open
+// document. `revision` blames the file as committed there instead.
+#[command]
+pub async fn get_blame(
+    blame_cache: State<'_, BlameCache>,
+    documents: State<'_, DocumentManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    revision: Option<String>,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    document_uri: Option<String>,
+) -> Result<Vec<BlameLine>, String> {
+    check_repo(&trust, "get_blame", &path)?;
+    trust.check("get_blame", &file_in_repo(&path, &file_path))?;
+    let content = match (&revision, &document_uri) {
+        (None, Some(uri)) => Some(
+            documents
+                .get_document(uri)
+                .ok_or_else(|| format!("Document not found: {}", uri))?
+                .content,
+        ),
+        _ => None,
+    };
+    let blame_cache = blame_cache.inner().clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        blame::blame_file(
+            &blame_cache,
+            Path::new(&path),
+            &file_path,
+            revision.as_deref(),
+            content.as_deref(),
+            start_line,
+            end_line,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn checkout_branch(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    branch_name: String,
+    stash: Option<bool>,
+) -> Result<(), String> {
+    check_repo(&trust, "checkout_branch", &path)?;
+    let identity = config_manager.get_config().git;
+    branch::checkout_branch(Path::new(&path), &branch_name, stash.unwrap_or(false).then_some(&identity))
+}
+
+#[command]
+pub async fn create_branch(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    branch_name: String,
+    start_point: Option<String>,
+    checkout: Option<bool>,
+) -> Result<(), String> {
+    check_repo(&trust, "create_branch", &path)?;
+    branch::create_branch(
+        Path::new(&path),
+        &branch_name,
+        start_point.as_deref(),
+        checkout.unwrap_or(false),
+    )
+}
+
+#[command]
+pub async fn rename_branch(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    old_name: String,
+    new_name: String,
+    force: Option<bool>,
+) -> Result<(), String> {
+    check_repo(&trust, "rename_branch", &path)?;
+    branch::rename_branch(Path::new(&path), &old_name, &new_name, force.unwrap_or(false))
+}
+
+#[command]
+pub async fn delete_branch(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    branch_name: String,
+    force: Option<bool>,
+) -> Result<(), String> {
+    check_repo(&trust, "delete_branch", &path)?;
+    branch::delete_branch(Path::new(&path), &branch_name, force.unwrap_or(false))
+}
+
+#[command]
+pub async fn set_branch_upstream(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    branch_name: String,
+    upstream: Option<String>,
+) -> Result<(), String> {
+    check_repo(&trust, "set_branch_upstream", &path)?;
+    branch::set_upstream(Path::new(&path), &branch_name, upstream.as_deref())
+}
+
+#[command]
+pub async fn commit(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    message: String,
+    options: Option<CommitOptions>,
+) -> Result<CommitResult, CommitError> {
+    check_repo(&trust, "commit", &path)?;
+    let identity = config_manager.get_config().git;
+
+    // Hooks may run for a long time
+    tauri::async_runtime::spawn_blocking(move || {
+        let repo = open_repo(Path::new(&path))?;
+        create_commit(&repo, &message, &options.unwrap_or_default(), &identity)
+    })
+    .await
+    .map_err(|e| CommitError::from(e.to_string()))?
+}
+
+#[command]
+pub async fn fetch(
+    app: AppHandle,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    remote: String,
+) -> Result<Vec<String>, String> {
+    check_repo(&trust, "fetch", &path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let (repo, credentials) = sync::open_for_transfer(Path::new(&path))?;
+        let progress = |event: GitProgress| {
+            let _ = app.emit(sync::PROGRESS_EVENT, event);
+        };
+        sync::fetch(&repo, &remote, &progress, &credentials)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn pull(
+    app: AppHandle,
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    remote: String,
+    branch: String,
+    options: Option<PullOptions>,
+) -> Result<PullResult, String> {
+    check_repo(&trust, "pull", &path)?;
+    let identity = config_manager.get_config().git;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let (repo, credentials) = sync::open_for_transfer(Path::new(&path))?;
+        let progress = |event: GitProgress| {
+            let _ = app.emit(sync::PROGRESS_EVENT, event);
+        };
+        sync::pull(
+            &repo,
+            &remote,
+            &branch,
+            &options.unwrap_or_default(),
+            &identity,
+            &progress,
+            &credentials,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn push(
+    app: AppHandle,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    remote: String,
+    branch: String,
+    settings: Option<PushSettings>,
+) -> Result<PushResult, String> {
+    check_repo(&trust, "push", &path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let (repo, credentials) = sync::open_for_transfer(Path::new(&path))?;
+        let progress = |event: GitProgress| {
+            let _ = app.emit(sync::PROGRESS_EVENT, event);
+        };
+        sync::push(
+            &repo,
+            &remote,
+            &branch,
+            &settings.unwrap_or_default(),
+            &progress,
+            &credentials,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn stage_file(trust: State<'_, WorkspaceTrust>, path: String, file_path: String) -> Result<(), String> {
+    check_repo(&trust, "stage_file", &path)?;
+    trust.check("stage_file", &file_in_repo(&path, &file_path))?;
+    let (repo, file) = open_with_path(Path::new(&path), &file_path)?;
+    stage::stage(&repo, &file, &Selection::All)
+}
+
+#[command]
+pub async fn unstage_file(trust: State<'_, WorkspaceTrust>, path: String, file_path: String) -> Result<(), String> {
+    check_repo(&trust, "unstage_file", &path)?;
+    trust.check("unstage_file", &file_in_repo(&path, &file_path))?;
+    let (repo, file) = open_with_path(Path::new(&path), &file_path)?;
+    stage::unstage(&repo, &file, &Selection::All)
+}
+
+#[command]
+pub async fn stage_hunk(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    hunk_index: usize,
+) -> Result<(), String> {
+    check_repo(&trust, "stage_hunk", &path)?;
+    trust.check("stage_hunk", &file_in_repo(&path, &file_path))?;
+    let (repo, file) = open_with_path(Path::new(&path), &file_path)?;
+    stage::stage(&repo, &file, &Selection::Hunk(hunk_index))
+}
+
+#[command]
+pub async fn unstage_hunk(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    hunk_index: usize,
+) -> Result<(), String> {
+    check_repo(&trust, "unstage_hunk", &path)?;
+    trust.check("unstage_hunk", &file_in_repo(&path, &file_path))?;
+    let (repo, file) = open_with_path(Path::new(&path), &file_path)?;
+    stage::unstage(&repo, &file, &Selection::Hunk(hunk_index))
+}
+
+#[command]
+pub async fn stage_lines(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    start_line: u32,
+    end_line: u32,
+) -> Result<(), String> {
+    check_repo(&trust, "stage_lines", &path)?;
+    trust.check("stage_lines", &file_in_repo(&path, &file_path))?;
+    let (repo, file) = open_with_path(Path::new(&path), &file_path)?;
+    stage::stage(&repo, &file, &Selection::Lines { start: start_line, end: end_line })
+}
+
+#[command]
+pub async fn unstage_lines(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    start_line: u32,
+    end_line: u32,
+) -> Result<(), String> {
+    check_repo(&trust, "unstage_lines", &path)?;
+    trust.check("unstage_lines", &file_in_repo(&path, &file_path))?;
+    let (repo, file) = open_with_path(Path::new(&path), &file_path)?;
+    stage::unstage(&repo, &file, &Selection::Lines { start: start_line, end: end_line })
+}
+
+#[command]
+pub async fn discard_hunk(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    hunk_index: usize,
+) -> Result<(), String> {
+    check_repo(&trust, "discard_hunk", &path)?;
+    trust.check("discard_hunk", &file_in_repo(&path, &file_path))?;
+    let (repo, file) = open_with_path(Path::new(&path), &file_path)?;
+    stage::discard(&repo, &file, &Selection::Hunk(hunk_index))
+}
+
+#[command]
+pub async fn get_conflicts(trust: State<'_, WorkspaceTrust>, path: String) -> Result<Vec<ConflictFile>, String> {
+    check_repo(&trust, "get_conflicts", &path)?;
+    conflict::list_conflicts(Path::new(&path))
+}
+
+#[command]
+pub async fn parse_conflict_markers(content: String) -> Result<Vec<ConflictRegion>, String> {
+    Ok(conflict::parse_conflicts(&content))
+}
+
+#[command]
+pub async fn resolve_conflict_regions(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    resolutions: Vec<RegionResolution>,
+) -> Result<(), String> {
+    check_repo(&trust, "resolve_conflict_regions", &path)?;
+    trust.check("resolve_conflict_regions", &file_in_repo(&path, &file_path))?;
+    conflict::resolve_file_regions(Path::new(&path), &file_path, &resolutions)
+}
+
+#[command]
+pub async fn resolve_conflict_file(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    choice: ConflictChoice,
+) -> Result<(), String> {
+    check_repo(&trust, "resolve_conflict_file", &path)?;
+    trust.check("resolve_conflict_file", &file_in_repo(&path, &file_path))?;
+    conflict::resolve_file(Path::new(&path), &file_path, choice)
+}
+
+#[command]
+pub async fn mark_resolved(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    file_path: String,
+    force: Option<bool>,
+) -> Result<(), String> {
+    check_repo(&trust, "mark_resolved", &path)?;
+    trust.check("mark_resolved", &file_in_repo(&path, &file_path))?;
+    conflict::mark_resolved(Path::new(&path), &file_path, force.unwrap_or(false))
+}
+
+#[command]
+pub async fn get_repository_state(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+) -> Result<RepositoryStateInfo, String> {
+    check_repo(&trust, "get_repository_state", &path)?;
+    state::get_repository_state(Path::new(&path))
+}
+
+#[command]
+pub async fn abort_operation(trust: State<'_, WorkspaceTrust>, path: String) -> Result<(), String> {
+    check_repo(&trust, "abort_operation", &path)?;
+    state::abort_operation(Path::new(&path))
+}
+
+#[command]
+pub async fn continue_operation(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    message: Option<String>,
+) -> Result<OperationResult, String> {
+    check_repo(&trust, "continue_operation", &path)?;
+    let identity = config_manager.get_config().git;
+    // Continuing a sequence or rebase applies and commits the remaining steps
+    tauri::async_runtime::spawn_blocking(move || {
+        state::continue_operation(Path::new(&path), message.as_deref(), &identity)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn list_stashes(trust: State<'_, WorkspaceTrust>, path: String) -> Result<Vec<StashEntry>, String> {
+    check_repo(&trust, "list_stashes", &path)?;
+    stash::list_stashes(Path::new(&path))
+}
+
+#[command]
+pub async fn push_stash(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    options: Option<StashPushOptions>,
+) -> Result<StashEntry, String> {
+    check_repo(&trust, "push_stash", &path)?;
+    let identity = config_manager.get_config().git;
+    stash::push_stash(Path::new(&path), &options.unwrap_or_default(), &identity)
+}
+
+#[command]
+pub async fn apply_stash(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    index: usize,
+    reinstate_index: Option<bool>,
+) -> Result<StashApplyResult, String> {
+    check_repo(&trust, "apply_stash", &path)?;
+    stash::apply_stash(Path::new(&path), index, reinstate_index.unwrap_or(false))
+}
+
+#[command]
+pub async fn pop_stash(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    index: usize,
+    reinstate_index: Option<bool>,
+) -> Result<StashApplyResult, String> {
+    check_repo(&trust, "pop_stash", &path)?;
+    stash::pop_stash(Path::new(&path), index, reinstate_index.unwrap_or(false))
+}
+
+#[command]
+pub async fn drop_stash(trust: State<'_, WorkspaceTrust>, path: String, index: usize) -> Result<(), String> {
+    check_repo(&trust, "drop_stash", &path)?;
+    stash::drop_stash(Path::new(&path), index)
+}
+
+#[command]
+pub async fn show_stash(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    index: usize,
+    settings: Option<DiffSettings>,
+) -> Result<Vec<FileDiff>, String> {
+    check_repo(&trust, "show_stash", &path)?;
+    stash::show_stash(Path::new(&path), index, &settings.unwrap_or_default())
+}
+
+#[command]
+pub async fn cherry_pick(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    commits: Vec<String>,
+    options: Option<SequenceOptions>,
+) -> Result<OperationResult, String> {
+    check_repo(&trust, "cherry_pick", &path)?;
+    let identity = config_manager.get_config().git;
+    tauri::async_runtime::spawn_blocking(move || {
+        sequencer::cherry_pick(Path::new(&path), &commits, &options.unwrap_or_default(), &identity)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn revert_commits(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    commits: Vec<String>,
+    options: Option<SequenceOptions>,
+) -> Result<OperationResult, String> {
+    check_repo(&trust, "revert_commits", &path)?;
+    let identity = config_manager.get_config().git;
+    tauri::async_runtime::spawn_blocking(move || {
+        sequencer::revert(Path::new(&path), &commits, &options.unwrap_or_default(), &identity)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn get_rebase_commits(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    upstream: String,
+) -> Result<Vec<CommitInfo>, String> {
+    check_repo(&trust, "get_rebase_commits", &path)?;
+    tauri::async_runtime::spawn_blocking(move || rebase::rebase_commits(Path::new(&path), &upstream))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn start_interactive_rebase(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    plan: RebasePlan,
+) -> Result<OperationResult, String> {
+    check_repo(&trust, "start_interactive_rebase", &path)?;
+    let identity = config_manager.get_config().git;
+    // `git rebase` runs until it finishes or stops
+    tauri::async_runtime::spawn_blocking(move || rebase::start_interactive_rebase(Path::new(&path), &plan, &identity))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn list_tags(trust: State<'_, WorkspaceTrust>, path: String) -> Result<Vec<TagInfo>, String> {
+    check_repo(&trust, "list_tags", &path)?;
+    tag::list_tags(Path::new(&path))
+}
+
+#[command]
+pub async fn create_tag(
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    name: String,
+    target: Option<String>,
+    message: Option<String>,
+    force: Option<bool>,
+) -> Result<TagInfo, String> {
+    check_repo(&trust, "create_tag", &path)?;
+    let identity = config_manager.get_config().git;
+    tag::create_tag(
+        Path::new(&path),
+        &name,
+        target.as_deref(),
+        message.as_deref(),
+        force.unwrap_or(false),
+        &identity,
+    )
+}
+
+#[command]
+pub async fn delete_tag(trust: State<'_, WorkspaceTrust>, path: String, name: String) -> Result<(), String> {
+    check_repo(&trust, "delete_tag", &path)?;
+    tag::delete_tag(Path::new(&path), &name)
+}
+
+#[command]
+pub async fn list_remotes(trust: State<'_, WorkspaceTrust>, path: String) -> Result<Vec<RemoteInfo>, String> {
+    check_repo(&trust, "list_remotes", &path)?;
+    remote::list_remotes(Path::new(&path))
+}
+
+#[command]
+pub async fn add_remote(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    name: String,
+    url: String,
+) -> Result<(), String> {
+    check_repo(&trust, "add_remote", &path)?;
+    remote::add_remote(Path::new(&path), &name, &url)
+}
+
+#[command]
+pub async fn rename_remote(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    old_name: String,
+    new_name: String,
+) -> Result<Vec<String>, String> {
+    check_repo(&trust, "rename_remote", &path)?;
+    remote::rename_remote(Path::new(&path), &old_name, &new_name)
+}
+
+#[command]
+pub async fn remove_remote(trust: State<'_, WorkspaceTrust>, path: String, name: String) -> Result<(), String> {
+    check_repo(&trust, "remove_remote", &path)?;
+    remote::remove_remote(Path::new(&path), &name)
+}
+
+#[command]
+pub async fn set_remote_url(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    name: String,
+    url: String,
+    push: Option<bool>,
+) -> Result<(), String> {
+    check_repo(&trust, "set_remote_url", &path)?;
+    remote::set_remote_url(Path::new(&path), &name, &url, push.unwrap_or(false))
+}
+
+#[command]
+pub async fn get_repo_config(trust: State<'_, WorkspaceTrust>, path: String) -> Result<Vec<ConfigEntry>, String> {
+    check_repo(&trust, "get_repo_config", &path)?;
+    repo_config::list_config(Path::new(&path))
+}
+
+#[command]
+pub async fn get_repo_config_value(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    key: String,
+) -> Result<Option<String>, String> {
+    check_repo(&trust, "get_repo_config_value", &path)?;
+    repo_config::get_config_value(Path::new(&path), &key)
+}
+
+#[command]
+pub async fn set_repo_config_value(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    check_repo(&trust, "set_repo_config_value", &path)?;
+    repo_config::set_config_value(Path::new(&path), &key, &value)
+}
+
+#[command]
+pub async fn unset_repo_config_value(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    key: String,
+) -> Result<(), String> {
+    check_repo(&trust, "unset_repo_config_value", &path)?;
+    repo_config::unset_config_value(Path::new(&path), &key)
+}
+
+#[command]
+pub async fn init_repository(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    initial_branch: Option<String>,
+    bare: Option<bool>,
+) -> Result<String, String> {
+    trust.check("init_repository", Path::new(&path))?;
+    clone::init_repository(Path::new(&path), initial_branch.as_deref(), bare.unwrap_or(false))
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+// Progress is reported through `git-progress` events; `clone_id` lets the
+// frontend cancel the clone with `cancel_clone`
+#[command]
+pub async fn clone_repository(
+    app: AppHandle,
+    clones: State<'_, CloneRegistry>,
+    trust: State<'_, WorkspaceTrust>,
+    clone_id: String,
+    source: String,
+    destination: String,
+    branch: Option<String>,
+) -> Result<String, String> {
+    trust.check("clone_repository", Path::new(&destination))?;
+    if let Some(source_path) = clone::local_source_path(&source) {
+        trust.check("clone_repository", &source_path)?;
+    }
+    let cancelled = clones.register(&clone_id)?;
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let credentials = sync::DefaultCredentials::global();
+        let progress = |event: GitProgress| {
+            let _ = app.emit(sync::PROGRESS_EVENT, event);
+        };
+        clone::clone_repository(
+            &source,
+            Path::new(&destination),
+            branch.as_deref(),
+            &cancelled,
+            &progress,
+            &credentials,
+        )
     })
+    .await
+    .map_err(|e| e.to_string());
+
+    clones.finish(&clone_id);
+    result?.map(|path| path.to_string_lossy().to_string())
+}
+
+#[command]
+pub async fn cancel_clone(clones: State<'_, CloneRegistry>, clone_id: String) -> Result<(), String> {
+    clones.cancel(&clone_id)
 }
 
 #[command]
-pub async fn pull(path: String, remote: String, branch: String) -> Result<(), String> {
-    // Mock implementation
-    // In a real application, this would use libgit2 to perform the operation
-    Ok(())
+pub async fn list_worktrees(trust: State<'_, WorkspaceTrust>, path: String) -> Result<Vec<WorktreeInfo>, String> {
+    check_repo(&trust, "list_worktrees", &path)?;
+    worktree::list_worktrees(Path::new(&path))
 }
 
 #[command]
-pub async fn push(path: String, remote: String, branch: String) -> Result<(), String> {
-    // Mock implementation
-    // In a real application, this would use libgit2 to perform the operation
-    Ok(())
+pub async fn add_worktree(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    worktree_path: String,
+    branch: Option<String>,
+) -> Result<WorktreeInfo, String> {
+    check_repo(&trust, "add_worktree", &path)?;
+    trust.check("add_worktree", Path::new(&worktree_path))?;
+    worktree::add_worktree(Path::new(&path), Path::new(&worktree_path), branch.as_deref())
 }
 
 #[command]
-pub async fn stage_file(path: String, file_path: String) -> Result<(), String> {
-    // Mock implementation
-    // In a real application, this would use libgit2 to perform the operation
-    Ok(())
+pub async fn remove_worktree(
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+    name: String,
+    force: Option<bool>,
+) -> Result<(), String> {
+    check_repo(&trust, "remove_worktree", &path)?;
+    // Removing a worktree deletes its directory
+    trust.check("remove_worktree", &worktree::worktree_path(Path::new(&path), &name)?)?;
+    worktree::remove_worktree(Path::new(&path), &name, force.unwrap_or(false))
 }
 
+// Emit `git-status-changed` events for the repository until unwatched.
+// Returns the current status to start from.
 #[command]
-pub async fn unstage_file(path: String, file_path: String) -> Result<(), String> {
-    // Mock implementation
-    // In a real application, this would use libgit2 to perform the operation
-    Ok(())
-} 
\ No newline at end of file
+pub async fn watch_git_status(
+    git_status: State<'_, GitStatusService>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+) -> Result<Vec<StatusEntry>, String> {
+    check_repo(&trust, "watch_git_status", &path)?;
+    git_status.watch(Path::new(&path))
+}
+
+#[command]
+pub async fn unwatch_git_status(git_status: State<'_, GitStatusService>, path: String) -> Result<(), String> {
+    git_status.unwatch(Path::new(&path))
+}
+
+// Trust both `path` and the repository found from it, which git may have
+// discovered in a parent directory outside the workspace
+fn check_repo(trust: &WorkspaceTrust, operation: &str, path: &str) -> Result<(), String> {
+    trust.check(operation, Path::new(path))?;
+    match open_repo(Path::new(path)) {
+        Ok(repo) => trust.check(operation, repo.workdir().unwrap_or_else(|| repo.path())),
+        Err(_) => Ok(()),
+    }
+}
+
+// A file path from the UI as git will use it: relative paths are relative
+// to the working tree, which may be above `path`
+fn file_in_repo(path: &str, file_path: &str) -> PathBuf {
+    match open_repo(Path::new(path)).ok().and_then(|repo| repo.workdir().map(Path::to_path_buf)) {
+        Some(workdir) => workdir.join(file_path),
+        None => Path::new(path).join(file_path),
+    }
+}
\ No newline at end of file
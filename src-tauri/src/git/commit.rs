@@ -0,0 +1,507 @@
+use git2::{Repository, RepositoryState, Signature};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::config::GitConfig;
+use crate::git::{commit_info, CommitInfo};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommitOptions {
+    pub amend: bool,
+    pub signoff: bool,
+    pub allow_empty: bool,
+    // Skip the pre-commit and commit-msg hooks, like `git commit --no-verify`
+    pub no_verify: bool,
+}
+
+// Output of a hook that ran while committing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookOutput {
+    pub hook: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Errors of `create_commit`, tagged so the UI can show what a hook that
+// rejected the commit printed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommitError {
+    // Every hook that ran, the failed one last
+    HookFailed { hook: String, hooks: Vec<HookOutput> },
+    Failed { message: String },
+}
+
+impl From<String> for CommitError {
+    fn from(message: String) -> Self {
+        CommitError::Failed { message }
+    }
+}
+
+impl std::fmt::Display for CommitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitError::HookFailed { hook, hooks } => {
+                write!(f, "{} hook failed", hook)?;
+                if let Some(output) = hooks.last() {
+                    if let Some(code) = output.exit_code {
+                        write!(f, " (exit code {})", code)?;
+                    }
+                    let text = format!("{}{}", output.stdout, output.stderr);
+                    write!(f, ":\n{}", text.trim_end())?;
+                }
+                Ok(())
+            }
+            CommitError::Failed { message } => f.write_str(message),
+        }
+    }
+}
+
+// For callers that report errors as plain messages
+impl From<CommitError> for String {
+    fn from(error: CommitError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommitResult {
+    pub commit: CommitInfo,
+    pub hooks: Vec<HookOutput>,
+}
+
+// Resolve the identity used for new commits. Values stored in the app
+// settings win over `user.name`/`user.email` from the repository and
+// global git configuration.
+pub fn resolve_signature(repo: &Repository, overrides: &GitConfig) -> Result<Signature<'static>, String> {
+    let config = repo.config().map_err(|e| e.to_string())?;
+
+    let name = overrides
+        .author_name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .or_else(|| config.get_string("user.name").ok());
+    let email = overrides
+        .author_email
+        .clone()
+        .filter(|email| !email.trim().is_empty())
+        .or_else(|| config.get_string("user.email").ok());
+
+    match (name, email) {
+        (Some(name), Some(email)) => Signature::now(&name, &email).map_err(|e| e.to_string()),
+        _ => Err("Git author identity is not configured. Set user.name and user.email \
+                  in git or in the TurkCode settings."
+            .to_string()),
+    }
+}
+
+// Create a commit from the current index. During a merge the merged
+// commits become additional parents.
+pub fn create_commit(
+    repo: &Repository,
+    message: &str,
+    options: &CommitOptions,
+    identity: &GitConfig,
+) -> Result<CommitResult, CommitError> {
+    create_commit_as(repo, message, options, identity, None)
+}
+
+// Like `create_commit`, but keeps `author` (e.g. of a cherry-picked commit)
+// instead of using the committer identity for both
+pub fn create_commit_as(
+    repo: &Repository,
+    message: &str,
+    options: &CommitOptions,
+    identity: &GitConfig,
+    author: Option<&Signature<'_>>,
+) -> Result<CommitResult, CommitError> {
+    if repo.is_bare() {
+        return Err("Cannot commit in a bare repository".to_string().into());
+    }
+
+    let signature = resolve_signature(repo, identity)?;
+    let mut hooks = Vec::new();
+
+    // Staged changes may come from outside, e.g. git on the command line
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    index.read(false).map_err(|e| e.to_string())?;
+    if index.has_conflicts() {
+        return Err("Cannot commit while there are unresolved conflicts".to_string().into());
+    }
+
+    let head = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit().map_err(|e| e.to_string())?),
+        Err(_) => None,
+    };
+
+    if options.amend && head.is_none() {
+        return Err("There is no commit to amend".to_string().into());
+    }
+
+    let mut merge_heads = Vec::new();
+    if repo.state() == RepositoryState::Merge {
+        if options.amend {
+            return Err("Cannot amend while a merge is in progress".to_string().into());
+        }
+        merge_heads = merge_head_ids(repo)?;
+    }
+
+    // A merge commit is worth recording even if the tree did not change
+    let must_change = !options.amend && !options.allow_empty && merge_heads.is_empty();
+    let check_changed = |tree_id: git2::Oid| -> Result<(), CommitError> {
+        let unchanged = match &head {
+            Some(parent) => parent.tree_id() == tree_id,
+            None => repo.find_tree(tree_id).map_err(|e| e.to_string())?.is_empty(),
+        };
+        if must_change && unchanged {
+            return Err("Nothing to commit".to_string().into());
+        }
+        Ok(())
+    };
+
+    // Checked before the hooks so they do not run for nothing
+    let mut tree_id = index.write_tree().map_err(|e| e.to_string())?;
+    check_changed(tree_id)?;
+
+    if !options.no_verify {
+        if !run_hook(repo, "pre-commit", &[], &mut hooks)? {
+            return Err(hook_failed("pre-commit", hooks));
+        }
+        // The hook may have staged or unstaged changes
+        index.read(true).map_err(|e| e.to_string())?;
+        tree_id = index.write_tree().map_err(|e| e.to_string())?;
+        check_changed(tree_id)?;
+    }
+
+    let tree = repo.find_tree(tree_id).map_err(|e| e.to_string())?;
+
+    let mut message = git2::message_prettify(message, Some(b'#')).map_err(|e| e.to_string())?;
+    if options.signoff {
+        message = add_signoff(&message, &signature);
+    }
+
+    if !options.no_verify {
+        let message_file = repo.path().join("COMMIT_EDITMSG");
+        fs::write(&message_file, &message).map_err(|e| e.to_string())?;
+        if !run_hook(repo, "commit-msg", &[message_file.as_path()], &mut hooks)? {
+            return Err(hook_failed("commit-msg", hooks));
+        }
+
+        // The hook is allowed to rewrite the message
+        let edited = fs::read_to_string(&message_file).map_err(|e| e.to_string())?;
+        message = git2::message_prettify(edited, Some(b'#')).map_err(|e| e.to_string())?;
+    }
+
+    if message.trim().is_empty() {
+        return Err("Aborting commit due to empty commit message".to_string().into());
+    }
+
+    let author = author.unwrap_or(&signature);
+
+    let oid = match (&head, options.amend) {
+        (Some(head), true) => head
+            .amend(Some("HEAD"), None, Some(&signature), None, Some(&message), Some(&tree))
+            .map_err(|e| e.to_string())?,
+        _ => {
+            let mut parents = Vec::new();
+            if let Some(head) = head {
+                parents.push(head);
+            }
+            for oid in merge_heads {
+                parents.push(repo.find_commit(oid).map_err(|e| e.to_string())?);
+            }
+            let parents: Vec<&git2::Commit<'_>> = parents.iter().collect();
+
+            repo.commit(Some("HEAD"), author, &signature, &message, &tree, &parents)
+                .map_err(|e| e.to_string())?
+        }
+    };
+
+    // A finished merge or cherry-pick leaves nothing to continue
+    clear_operation_files(repo)?;
+
+    if !options.no_verify {
+        // post-commit cannot abort the commit, so its failure is only reported
+        let _ = run_hook(repo, "post-commit", &[], &mut hooks);
+    }
+
+    let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+
+    Ok(CommitResult {
+        commit: commit_info(&commit),
+        hooks,
+    })
+}
+
+// Remove the state of a single merge, cherry-pick or revert. Unlike
+// `cleanup_state` this keeps a rebase or a cherry-pick sequence going.
+pub fn clear_operation_files(repo: &Repository) -> Result<(), String> {
+    for name in ["MERGE_HEAD", "MERGE_MODE", "MERGE_MSG", "CHERRY_PICK_HEAD", "REVERT_HEAD"] {
+        let file = repo.path().join(name);
+        if file.exists() {
+            fs::remove_file(&file).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+// Commits listed in MERGE_HEAD by an in-progress merge
+pub fn merge_head_ids(repo: &Repository) -> Result<Vec<git2::Oid>, String> {
+    let content = match fs::read_to_string(repo.path().join("MERGE_HEAD")) {
+        Ok(content) => content,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| git2::Oid::from_str(line.trim()).map_err(|e| e.to_string()))
+        .collect()
+}
+
+// Append a `Signed-off-by` trailer unless the message already ends with it
+fn add_signoff(message: &str, signature: &Signature<'_>) -> String {
+    let trailer = format!(
+        "Signed-off-by: {} <{}>",
+        signature.name().unwrap_or_default(),
+        signature.email().unwrap_or_default()
+    );
+
+    let body = message.trim_end();
+    if body.lines().last() == Some(trailer.as_str()) {
+        return format!("{}\n", body);
+    }
+
+    // Join an existing trailer block instead of starting a new paragraph
+    let last_paragraph = body.rsplit("\n\n").next().unwrap_or_default();
+    let has_trailers = body.contains("\n\n")
+        && last_paragraph
+            .lines()
+            .all(|line| line.split_once(": ").map_or(false, |(key, _)| !key.contains(' ')));
+
+    if has_trailers {
+        format!("{}\n{}\n", body, trailer)
+    } else {
+        format!("{}\n\n{}\n", body, trailer)
+    }
+}
+
+// Directory holding the hooks, honouring `core.hooksPath`
+pub fn hooks_dir(repo: &Repository) -> PathBuf {
+    if let Ok(config) = repo.config() {
+        if let Ok(hooks_path) = config.get_path("core.hooksPath") {
+            return match repo.workdir() {
+                Some(workdir) if hooks_path.is_relative() => workdir.join(hooks_path),
+                _ => hooks_path,
+            };
+        }
+    }
+
+    common_dir(repo).join("hooks")
+}
+
+// The `.git` directory shared by all worktrees of the repository
+pub fn common_dir(repo: &Repository) -> PathBuf {
+    let git_dir = repo.path();
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => {
+            let common = Path::new(common.trim());
+            if common.is_relative() {
+                git_dir.join(common)
+            } else {
+                common.to_path_buf()
+            }
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+fn hook_failed(hook: &str, hooks: Vec<HookOutput>) -> CommitError {
+    CommitError::HookFailed {
+        hook: hook.to_string(),
+        hooks,
+    }
+}
+
+// Run a hook if it exists, recording its output. Returns whether the hook
+// succeeded, which it does when there is none.
+pub fn run_hook(
+    repo: &Repository,
+    name: &str,
+    args: &[&Path],
+    outputs: &mut Vec<HookOutput>,
+) -> Result<bool, String> {
+    let hook = hooks_dir(repo).join(name);
+    if !is_executable(&hook) {
+        return Ok(true);
+    }
+
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("sh");
+        command.arg(&hook);
+        command
+    } else {
+        Command::new(&hook)
+    };
+
+    let output = command
+        .args(args)
+        .current_dir(workdir)
+        .env("GIT_INDEX_FILE", repo.path().join("index"))
+        .env("GIT_EDITOR", ":")
+        .output()
+        .map_err(|e| format!("Failed to run {} hook: {}", name, e))?;
+
+    let success = output.status.success();
+    outputs.push(HookOutput {
+        hook: name.to_string(),
+        success,
+        exit_code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    });
+    Ok(success)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    fn stage_file(dir: &Path, name: &str, content: &str) {
+        fs::write(dir.join(name), content).unwrap();
+        git(dir, &["add", name]);
+    }
+
+    #[cfg(unix)]
+    fn write_hook(repo: &Repository, name: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let hook = hooks_dir(repo).join(name);
+        fs::create_dir_all(hook.parent().unwrap()).unwrap();
+        fs::write(&hook, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn commit_uses_the_identity_from_the_settings() {
+        let dir = init_repo();
+        let repo = Repository::open(dir.path()).unwrap();
+        stage_file(dir.path(), "a.txt", "a\n");
+        let identity = GitConfig {
+            author_name: Some("Settings Name".to_string()),
+            author_email: Some("settings@example.com".to_string()),
+        };
+
+        let result = create_commit(&repo, "First", &CommitOptions::default(), &identity).unwrap();
+        assert_eq!(result.commit.author, "Settings Name <settings@example.com>");
+        assert_eq!(result.commit.message, "First\n");
+    }
+
+    #[test]
+    fn signoff_adds_a_trailer_once() {
+        let dir = init_repo();
+        let repo = Repository::open(dir.path()).unwrap();
+        stage_file(dir.path(), "a.txt", "a\n");
+        let options = CommitOptions { signoff: true, ..Default::default() };
+
+        let result = create_commit(&repo, "Add a", &options, &GitConfig::default()).unwrap();
+        assert_eq!(result.commit.message, "Add a\n\nSigned-off-by: Test <test@example.com>\n");
+
+        let amend = CommitOptions { amend: true, signoff: true, ..Default::default() };
+        let result = create_commit(&repo, &result.commit.message, &amend, &GitConfig::default()).unwrap();
+        assert_eq!(result.commit.message, "Add a\n\nSigned-off-by: Test <test@example.com>\n");
+    }
+
+    #[test]
+    fn signoff_joins_an_existing_trailer_block() {
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        assert_eq!(
+            add_signoff("Fix\n\nReviewed-by: Someone <s@example.com>\n", &signature),
+            "Fix\n\nReviewed-by: Someone <s@example.com>\nSigned-off-by: Test <test@example.com>\n"
+        );
+    }
+
+    #[test]
+    fn nothing_to_commit_is_refused() {
+        let dir = init_repo();
+        commit_file(dir.path(), "a.txt", "a\n", "initial");
+        let repo = Repository::open(dir.path()).unwrap();
+
+        let error = create_commit(&repo, "Empty", &CommitOptions::default(), &GitConfig::default()).unwrap_err();
+        assert_eq!(error.to_string(), "Nothing to commit");
+
+        let options = CommitOptions { allow_empty: true, ..Default::default() };
+        assert!(create_commit(&repo, "Empty", &options, &GitConfig::default()).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_pre_commit_hook_is_reported_with_its_output() {
+        let dir = init_repo();
+        let repo = Repository::open(dir.path()).unwrap();
+        write_hook(&repo, "pre-commit", "echo 'lint failed' >&2\nexit 3");
+        stage_file(dir.path(), "a.txt", "a\n");
+
+        match create_commit(&repo, "Add a", &CommitOptions::default(), &GitConfig::default()) {
+            Err(CommitError::HookFailed { hook, hooks }) => {
+                assert_eq!(hook, "pre-commit");
+                assert_eq!(hooks.len(), 1);
+                assert_eq!(hooks[0].exit_code, Some(3));
+                assert_eq!(hooks[0].stderr, "lint failed\n");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(repo.head().is_err());
+
+        let options = CommitOptions { no_verify: true, ..Default::default() };
+        assert!(create_commit(&repo, "Add a", &options, &GitConfig::default()).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pre_commit_hook_does_not_run_without_changes() {
+        let dir = init_repo();
+        commit_file(dir.path(), "a.txt", "a\n", "initial");
+        let repo = Repository::open(dir.path()).unwrap();
+        write_hook(&repo, "pre-commit", "touch ran");
+
+        assert!(create_commit(&repo, "Empty", &CommitOptions::default(), &GitConfig::default()).is_err());
+        assert!(!dir.path().join("ran").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commit_msg_hook_may_rewrite_the_message() {
+        let dir = init_repo();
+        let repo = Repository::open(dir.path()).unwrap();
+        write_hook(&repo, "commit-msg", "echo 'Issue: 42' >> \"$1\"");
+        write_hook(&repo, "post-commit", "exit 1");
+        stage_file(dir.path(), "a.txt", "a\n");
+
+        let result = create_commit(&repo, "Add a", &CommitOptions::default(), &GitConfig::default()).unwrap();
+        assert_eq!(result.commit.message, "Add a\nIssue: 42\n");
+        let hooks: Vec<(&str, bool)> = result.hooks.iter().map(|output| (output.hook.as_str(), output.success)).collect();
+        assert_eq!(hooks, [("commit-msg", true), ("post-commit", false)]);
+    }
+}
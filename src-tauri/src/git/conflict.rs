@@ -0,0 +1,433 @@
+use git2::build::CheckoutBuilder;
+use git2::{IndexEntry, Repository};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use crate::git::{open_repo, open_with_path};
+
+// A conflicted file with the three versions stored in the index. A side is
+// `None` when the file does not exist there (e.g. deleted by them).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConflictFile {
+    pub path: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    pub binary: bool,
+}
+
+// One `<<<<<<<` ... `>>>>>>>` block in a conflicted file. Line numbers are
+// 1-based and include the marker lines.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConflictRegion {
+    pub index: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub ours_label: String,
+    pub ours: String,
+    // Only present for diff3-style markers
+    pub base: Option<String>,
+    pub theirs_label: String,
+    pub theirs: String,
+    // Unset when the file ends before the `>>>>>>>` marker. Such a region
+    // runs to the end of the file and is never resolved.
+    pub terminated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConflictChoice {
+    Ours,
+    Theirs,
+    // Ours followed by theirs
+    Both,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegionResolution {
+    pub index: usize,
+    pub choice: ConflictChoice,
+}
+
+const OURS_MARKER: &str = "<<<<<<<";
+const BASE_MARKER: &str = "|||||||";
+const SEPARATOR_MARKER: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>>";
+
+// List conflicted files with their base, ours and theirs contents
+pub fn list_conflicts(path: &Path) -> Result<Vec<ConflictFile>, String> {
+    let repo = open_repo(path)?;
+    let index = repo.index().map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+
+    for conflict in index.conflicts().map_err(|e| e.to_string())? {
+        let conflict = conflict.map_err(|e| e.to_string())?;
+        let path = [&conflict.our, &conflict.their, &conflict.ancestor]
+            .iter()
+            .find_map(|entry| entry.as_ref())
+            .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+            .unwrap_or_default();
+
+        let base = entry_content(&repo, conflict.ancestor.as_ref())?;
+        let ours = entry_content(&repo, conflict.our.as_ref())?;
+        let theirs = entry_content(&repo, conflict.their.as_ref())?;
+
+        let binary = [&base, &ours, &theirs]
+            .iter()
+            .any(|content| content.as_ref().map_or(false, |bytes| bytes.contains(&0)));
+
+        let to_text = |content: Option<Vec<u8>>| {
+            if binary {
+                None
+            } else {
+                content.map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            }
+        };
+
+        files.push(ConflictFile {
+            path,
+            base: to_text(base),
+            ours: to_text(ours),
+            theirs: to_text(theirs),
+            binary,
+        });
+    }
+
+    Ok(files)
+}
+
+fn entry_content(repo: &Repository, entry: Option<&IndexEntry>) -> Result<Option<Vec<u8>>, String> {
+    match entry {
+        Some(entry) => {
+            let blob = repo.find_blob(entry.id).map_err(|e| e.to_string())?;
+            Ok(Some(blob.content().to_vec()))
+        }
+        None => Ok(None),
+    }
+}
+
+fn marker_label<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(marker)?;
+    let rest = rest.trim_end_matches(['\n', '\r']);
+    if rest.is_empty() {
+        Some("")
+    } else {
+        rest.strip_prefix(' ')
+    }
+}
+
+// Parse conflict markers into regions. A region missing its closing marker
+// is returned unterminated rather than dropped, so the file is still seen
+// as conflicted.
+pub fn parse_conflicts(content: &str) -> Vec<ConflictRegion> {
+    enum Section {
+        Outside,
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut regions = Vec::new();
+    let mut section = Section::Outside;
+    let mut current: Option<ConflictRegion> = None;
+
+    for (number, line) in content.split_inclusive('\n').enumerate() {
+        let line_number = number + 1;
+
+        match section {
+            Section::Outside => {
+                if let Some(label) = marker_label(line, OURS_MARKER) {
+                    current = Some(ConflictRegion {
+                        index: regions.len(),
+                        start_line: line_number,
+                        end_line: line_number,
+                        ours_label: label.to_string(),
+                        ours: String::new(),
+                        base: None,
+                        theirs_label: String::new(),
+                        theirs: String::new(),
+                        terminated: false,
+                    });
+                    section = Section::Ours;
+                }
+            }
+            Section::Ours | Section::Base => {
+                let region = current.as_mut().expect("inside a region");
+                if marker_label(line, BASE_MARKER).is_some() {
+                    region.base = Some(String::new());
+                    section = Section::Base;
+                } else if line.trim_end_matches(['\n', '\r']) == SEPARATOR_MARKER {
+                    section = Section::Theirs;
+                } else if let Section::Base = section {
+                    region.base.get_or_insert_with(String::new).push_str(line);
+                } else {
+                    region.ours.push_str(line);
+                }
+            }
+            Section::Theirs => {
+                let region = current.as_mut().expect("inside a region");
+                if let Some(label) = marker_label(line, THEIRS_MARKER) {
+                    region.theirs_label = label.to_string();
+                    region.end_line = line_number;
+                    region.terminated = true;
+                    regions.push(current.take().expect("inside a region"));
+                    section = Section::Outside;
+                } else {
+                    region.theirs.push_str(line);
+                }
+            }
+        }
+    }
+
+    if let Some(mut region) = current {
+        region.end_line = content.split_inclusive('\n').count();
+        regions.push(region);
+    }
+    regions
+}
+
+// Replace the chosen conflict regions of `content`; other regions are kept
+pub fn resolve_regions(content: &str, resolutions: &[RegionResolution]) -> String {
+    let regions = parse_conflicts(content);
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(content.len());
+    let mut line_index = 0;
+
+    for region in &regions {
+        let choice = resolutions
+            .iter()
+            .find(|resolution| resolution.index == region.index && region.terminated)
+            .map(|resolution| resolution.choice);
+        let choice = match choice {
+            Some(choice) => choice,
+            None => continue,
+        };
+
+        let start = region.start_line - 1;
+        for line in &lines[line_index..start] {
+            output.push_str(line);
+        }
+
+        match choice {
+            ConflictChoice::Ours => output.push_str(&region.ours),
+            ConflictChoice::Theirs => output.push_str(&region.theirs),
+            ConflictChoice::Both => {
+                output.push_str(&region.ours);
+                output.push_str(&region.theirs);
+            }
+        }
+
+        line_index = region.end_line;
+    }
+
+    for line in &lines[line_index..] {
+        output.push_str(line);
+    }
+
+    output
+}
+
+pub fn has_conflict_markers(content: &str) -> bool {
+    !parse_conflicts(content).is_empty()
+}
+
+// Resolve individual regions of a conflicted file in the working tree
+pub fn resolve_file_regions(repo_path: &Path, file_path: &str, resolutions: &[RegionResolution]) -> Result<(), String> {
+    let (repo, relative) = open_with_path(repo_path, file_path)?;
+    let full_path = workdir_path(&repo, &relative)?;
+
+    let content = fs::read_to_string(&full_path).map_err(|e| e.to_string())?;
+    let resolved = resolve_regions(&content, resolutions);
+    fs::write(&full_path, resolved).map_err(|e| e.to_string())
+}
+
+// Resolve a whole file. Ours and theirs take that side's version from the
+// index and mark the file resolved; both keeps each side of every region.
+pub fn resolve_file(repo_path: &Path, file_path: &str, choice: ConflictChoice) -> Result<(), String> {
+    let (repo, relative) = open_with_path(repo_path, file_path)?;
+    let full_path = workdir_path(&repo, &relative)?;
+
+    if choice == ConflictChoice::Both {
+        let content = fs::read_to_string(&full_path).map_err(|e| e.to_string())?;
+        let resolutions: Vec<RegionResolution> = parse_conflicts(&content)
+            .iter()
+            .map(|region| RegionResolution {
+                index: region.index,
+                choice,
+            })
+            .collect();
+        fs::write(&full_path, resolve_regions(&content, &resolutions)).map_err(|e| e.to_string())?;
+        return mark_resolved(repo_path, file_path, false);
+    }
+
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    let conflict = index
+        .conflicts()
+        .map_err(|e| e.to_string())?
+        .filter_map(Result::ok)
+        .find(|conflict| {
+            [&conflict.our, &conflict.their, &conflict.ancestor]
+                .iter()
+                .filter_map(|entry| entry.as_ref())
+                .any(|entry| Path::new(&*String::from_utf8_lossy(&entry.path)) == relative)
+        })
+        .ok_or_else(|| format!("{} is not conflicted", relative.display()))?;
+
+    let side = match choice {
+        ConflictChoice::Ours => conflict.our,
+        _ => conflict.their,
+    };
+
+    match side {
+        Some(_) => {
+            // Checked out rather than written as is, so filters such as
+            // `core.autocrlf` and the file mode apply
+            let mut checkout = CheckoutBuilder::new();
+            checkout.force().path(&relative);
+            if choice == ConflictChoice::Ours {
+                checkout.use_ours(true);
+            } else {
+                checkout.use_theirs(true);
+            }
+            repo.checkout_index(Some(&mut index), Some(&mut checkout))
+                .map_err(|e| e.to_string())?;
+            index.add_path(&relative).map_err(|e| e.to_string())?;
+        }
+        None => {
+            // The chosen side deleted the file
+            if full_path.exists() {
+                fs::remove_file(&full_path).map_err(|e| e.to_string())?;
+            }
+            index.remove_path(&relative).map_err(|e| e.to_string())?;
+        }
+    }
+
+    index.write().map_err(|e| e.to_string())
+}
+
+// Stage a file to mark its conflict as resolved. Files that still contain
+// conflict markers are refused unless `force` is set.
+pub fn mark_resolved(repo_path: &Path, file_path: &str, force: bool) -> Result<(), String> {
+    let (repo, relative) = open_with_path(repo_path, file_path)?;
+    let full_path = workdir_path(&repo, &relative)?;
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+
+    if full_path.exists() {
+        if !force {
+            if let Ok(content) = fs::read_to_string(&full_path) {
+                if has_conflict_markers(&content) {
+                    return Err(format!("{} still contains conflict markers", relative.display()));
+                }
+            }
+        }
+        index.add_path(&relative).map_err(|e| e.to_string())?;
+    } else {
+        index.remove_path(&relative).map_err(|e| e.to_string())?;
+    }
+
+    index.write().map_err(|e| e.to_string())
+}
+
+fn workdir_path(repo: &Repository, relative: &Path) -> Result<std::path::PathBuf, String> {
+    repo.workdir()
+        .map(|workdir| workdir.join(relative))
+        .ok_or_else(|| "Repository has no working directory".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_WAY: &str = "\
+start
+<<<<<<< HEAD
+ours
+=======
+theirs 1
+theirs 2
+>>>>>>> topic
+middle
+<<<<<<< HEAD
+=======
+only theirs
+>>>>>>> topic
+end
+";
+
+    fn resolve(content: &str, choices: &[(usize, ConflictChoice)]) -> String {
+        let resolutions: Vec<RegionResolution> = choices
+            .iter()
+            .map(|(index, choice)| RegionResolution { index: *index, choice: *choice })
+            .collect();
+        resolve_regions(content, &resolutions)
+    }
+
+    #[test]
+    fn two_way_markers_are_parsed() {
+        let regions = parse_conflicts(TWO_WAY);
+        assert_eq!(regions.len(), 2);
+
+        let first = &regions[0];
+        assert_eq!((first.start_line, first.end_line), (2, 7));
+        assert_eq!((first.ours_label.as_str(), first.theirs_label.as_str()), ("HEAD", "topic"));
+        assert_eq!(first.ours, "ours\n");
+        assert_eq!(first.theirs, "theirs 1\ntheirs 2\n");
+        assert_eq!(first.base, None);
+        assert!(first.terminated);
+
+        let second = &regions[1];
+        assert_eq!((second.index, second.start_line, second.end_line), (1, 9, 12));
+        assert_eq!(second.ours, "");
+        assert_eq!(second.theirs, "only theirs\n");
+        assert!(has_conflict_markers(TWO_WAY));
+        assert!(!has_conflict_markers("=======\n>>>>>>> topic\n"));
+    }
+
+    #[test]
+    fn diff3_markers_have_a_base() {
+        let content = "<<<<<<< ours\na\n||||||| base\nb\n=======\nc\n>>>>>>> theirs\n";
+        let regions = parse_conflicts(content);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].ours, "a\n");
+        assert_eq!(regions[0].base.as_deref(), Some("b\n"));
+        assert_eq!(regions[0].theirs, "c\n");
+        assert_eq!(resolve(content, &[(0, ConflictChoice::Theirs)]), "c\n");
+    }
+
+    #[test]
+    fn crlf_markers_keep_their_line_endings() {
+        let content = "x\r\n<<<<<<< HEAD\r\na\r\n=======\r\nb\r\n>>>>>>> topic\r\ny\r\n";
+        let regions = parse_conflicts(content);
+        assert_eq!(regions[0].ours_label, "HEAD");
+        assert_eq!(regions[0].theirs_label, "topic");
+        assert_eq!(regions[0].ours, "a\r\n");
+        assert_eq!(resolve(content, &[(0, ConflictChoice::Both)]), "x\r\na\r\nb\r\ny\r\n");
+    }
+
+    #[test]
+    fn unterminated_regions_are_reported_but_not_resolved() {
+        let content = "<<<<<<< HEAD\na\n=======\nb\n>>>>>>> topic\n<<<<<<< HEAD\nc\n=======\nd\n";
+        let regions = parse_conflicts(content);
+        assert_eq!(regions.len(), 2);
+        assert!(regions[0].terminated);
+        assert!(!regions[1].terminated);
+        assert_eq!((regions[1].start_line, regions[1].end_line), (6, 9));
+        assert_eq!(regions[1].theirs, "d\n");
+
+        let resolved = resolve(content, &[(0, ConflictChoice::Ours), (1, ConflictChoice::Ours)]);
+        assert_eq!(resolved, "a\n<<<<<<< HEAD\nc\n=======\nd\n");
+        assert!(has_conflict_markers(&resolved));
+    }
+
+    #[test]
+    fn regions_are_resolved_independently() {
+        assert_eq!(
+            resolve(TWO_WAY, &[(0, ConflictChoice::Ours), (1, ConflictChoice::Theirs)]),
+            "start\nours\nmiddle\nonly theirs\nend\n"
+        );
+        assert_eq!(
+            resolve(TWO_WAY, &[(0, ConflictChoice::Both)]),
+            "start\nours\ntheirs 1\ntheirs 2\nmiddle\n<<<<<<< HEAD\n=======\nonly theirs\n>>>>>>> topic\nend\n"
+        );
+        assert_eq!(resolve(TWO_WAY, &[]), TWO_WAY);
+    }
+}
@@ -0,0 +1,497 @@
+use git2::{Delta, Diff, DiffFindOptions, DiffOptions, Patch, Repository};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use crate::git::stage::checkout_form;
+use crate::git::{open_repo, open_with_path, FileStatus};
+
+// How a diff is computed. Renames are detected unless turned off.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffSettings {
+    pub context_lines: u32,
+    // Ignore all whitespace, like `git diff -w`
+    pub ignore_whitespace: bool,
+    // Ignore changes in the amount of whitespace, like `git diff -b`
+    pub ignore_whitespace_change: bool,
+    pub ignore_whitespace_eol: bool,
+    pub detect_renames: bool,
+    // Limit the diff to these paths
+    pub paths: Vec<String>,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        DiffSettings {
+            context_lines: 3,
+            ignore_whitespace: false,
+            ignore_whitespace_change: false,
+            ignore_whitespace_eol: false,
+            detect_renames: true,
+            paths: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DiffLineKind {
+    Context,
+    Addition,
+    Deletion,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub content: String,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileDiff {
+    pub path: String,
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    pub binary: bool,
+    pub additions: usize,
+    pub deletions: usize,
+    pub hunks: Vec<DiffHunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GutterKind {
+    Added,
+    Modified,
+    // Lines were removed after `start_line` (0 means at the top of the file)
+    Deleted,
+}
+
+// A changed region of a file for the editor gutter, in current line numbers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GutterChange {
+    pub kind: GutterKind,
+    pub start_line: u32,
+    pub end_line: u32,
+}
+
+// Unstaged changes: index against the working tree, including untracked files
+pub fn diff_workdir(path: &Path, settings: &DiffSettings) -> Result<Vec<FileDiff>, String> {
+    let repo = open_repo(path)?;
+    let mut options = diff_options(settings);
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+
+    let diff = repo
+        .diff_index_to_workdir(None, Some(&mut options))
+        .map_err(|e| e.to_string())?;
+    collect_files(diff, settings)
+}
+
+// Staged changes: HEAD against the index
+pub fn diff_staged(path: &Path, settings: &DiffSettings) -> Result<Vec<FileDiff>, String> {
+    let repo = open_repo(path)?;
+    let head_tree = match repo.head() {
+        Ok(head) => Some(head.peel_to_tree().map_err(|e| e.to_string())?),
+        Err(_) => None,
+    };
+
+    let diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut diff_options(settings)))
+        .map_err(|e| e.to_string())?;
+    collect_files(diff, settings)
+}
+
+// Changes between two revisions, e.g. commits, tags or branches
+pub fn diff_revisions(path: &Path, from: &str, to: &str, settings: &DiffSettings) -> Result<Vec<FileDiff>, String> {
+    let repo = open_repo(path)?;
+    let old_tree = revision_tree(&repo, from)?;
+    let new_tree = revision_tree(&repo, to)?;
+
+    let diff = repo
+        .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut diff_options(settings)))
+        .map_err(|e| e.to_string())?;
+    collect_files(diff, settings)
+}
+
+fn revision_tree<'r>(repo: &'r Repository, revision: &str) -> Result<git2::Tree<'r>, String> {
+    repo.revparse_single(revision)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| format!("Invalid revision {}: {}", revision, e.message()))
+}
+
+pub fn diff_options(settings: &DiffSettings) -> DiffOptions {
+    let mut options = DiffOptions::new();
+    options
+        .context_lines(settings.context_lines)
+        .ignore_whitespace(settings.ignore_whitespace)
+        .ignore_whitespace_change(settings.ignore_whitespace_change)
+        .ignore_whitespace_eol(settings.ignore_whitespace_eol);
+
+    for path in &settings.paths {
+        options.pathspec(path);
+    }
+
+    options
+}
+
+pub fn collect_files(mut diff: Diff<'_>, settings: &DiffSettings) -> Result<Vec<FileDiff>, String> {
+    if settings.detect_renames {
+        let mut find = DiffFindOptions::new();
+        find.renames(true).for_untracked(true);
+        diff.find_similar(Some(&mut find)).map_err(|e| e.to_string())?;
+    }
+
+    let mut files = Vec::new();
+
+    for index in 0..diff.deltas().len() {
+        let delta = diff.get_delta(index).expect("delta exists");
+        let status = match delta_status(delta.status()) {
+            Some(status) => status,
+            None => continue,
+        };
+
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let old_path = match delta.status() {
+            Delta::Renamed | Delta::Copied => delta
+                .old_file()
+                .path()
+                .map(|p| p.to_string_lossy().to_string()),
+            _ => None,
+        };
+
+        let mut file = FileDiff {
+            path,
+            old_path,
+            status,
+            binary: delta.flags().is_binary(),
+            additions: 0,
+            deletions: 0,
+            hunks: Vec::new(),
+        };
+
+        if !file.binary {
+            match Patch::from_diff(&diff, index).map_err(|e| e.to_string())? {
+                Some(patch) => {
+                    // Binary detection may only happen once content is loaded
+                    file.binary = patch.delta().flags().is_binary();
+                    if !file.binary {
+                        file.hunks = patch_hunks(&patch)?;
+                        let (_, additions, deletions) = patch.line_stats().map_err(|e| e.to_string())?;
+                        file.additions = additions;
+                        file.deletions = deletions;
+                    }
+                }
+                None => file.binary = true,
+            }
+        }
+
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+fn delta_status(delta: Delta) -> Option<FileStatus> {
+    match delta {
+        Delta::Added => Some(FileStatus::Added),
+        Delta::Deleted => Some(FileStatus::Deleted),
+        Delta::Modified => Some(FileStatus::Modified),
+        Delta::Renamed => Some(FileStatus::Renamed),
+        Delta::Copied => Some(FileStatus::Copied),
+        Delta::Typechange => Some(FileStatus::TypeChanged),
+        Delta::Untracked => Some(FileStatus::Untracked),
+        Delta::Ignored => Some(FileStatus::Ignored),
+        Delta::Conflicted => Some(FileStatus::Conflicted),
+        Delta::Unmodified | Delta::Unreadable => None,
+    }
+}
+
+pub fn patch_hunks(patch: &Patch<'_>) -> Result<Vec<DiffHunk>, String> {
+    let mut hunks = Vec::with_capacity(patch.num_hunks());
+
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_index).map_err(|e| e.to_string())?;
+        let mut lines = Vec::with_capacity(line_count);
+
+        for line_index in 0..line_count {
+            let line = patch
+                .line_in_hunk(hunk_index, line_index)
+                .map_err(|e| e.to_string())?;
+            let kind = match line.origin() {
+                ' ' => DiffLineKind::Context,
+                '+' => DiffLineKind::Addition,
+                '-' => DiffLineKind::Deletion,
+                // "No newline at end of file" markers
+                _ => continue,
+            };
+
+            let content = String::from_utf8_lossy(line.content());
+            lines.push(DiffLine {
+                kind,
+                content: content.trim_end_matches(['\n', '\r']).to_string(),
+                old_line: line.old_lineno(),
+                new_line: line.new_lineno(),
+            });
+        }
+
+        hunks.push(DiffHunk {
+            header: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+            old_start: hunk.old_start(),
+            old_lines: hunk.old_lines(),
+            new_start: hunk.new_start(),
+            new_lines: hunk.new_lines(),
+            lines,
+        });
+    }
+
+    Ok(hunks)
+}
+
+// Changed regions of a file against HEAD for the editor gutter. `content`
+// is the editor buffer; without it the file on disk is used.
+pub fn gutter_diff(repo_path: &Path, file_path: &str, content: Option<&str>) -> Result<Vec<GutterChange>, String> {
+    let (repo, relative) = open_with_path(repo_path, file_path)?;
+
+    let current = match content {
+        Some(content) => content.as_bytes().to_vec(),
+        None => {
+            let workdir = repo
+                .workdir()
+                .ok_or_else(|| "Repository has no working directory".to_string())?;
+            fs::read(workdir.join(&relative)).map_err(|e| e.to_string())?
+        }
+    };
+
+    // Compared in working tree form, so e.g. `core.autocrlf` does not mark
+    // every line as changed
+    let original = match repo.head().and_then(|head| head.peel_to_tree()) {
+        Ok(tree) => match tree.get_path(&relative) {
+            Ok(entry) => {
+                let blob = repo.find_blob(entry.id()).map_err(|e| e.to_string())?;
+                checkout_form(&repo, &relative, &blob, entry.filemode() as u32)?
+            }
+            Err(_) => Vec::new(),
+        },
+        Err(_) => Vec::new(),
+    };
+
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    let patch = Patch::from_buffers(&original, Some(&relative), &current, Some(&relative), Some(&mut options))
+        .map_err(|e| e.to_string())?;
+
+    if patch.delta().flags().is_binary() {
+        return Ok(Vec::new());
+    }
+
+    let mut changes = Vec::with_capacity(patch.num_hunks());
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, _) = patch.hunk(hunk_index).map_err(|e| e.to_string())?;
+        let change = if hunk.new_lines() == 0 {
+            GutterChange {
+                kind: GutterKind::Deleted,
+                start_line: hunk.new_start(),
+                end_line: hunk.new_start(),
+            }
+        } else {
+            GutterChange {
+                kind: if hunk.old_lines() == 0 { GutterKind::Added } else { GutterKind::Modified },
+                start_line: hunk.new_start(),
+                end_line: hunk.new_start() + hunk.new_lines() - 1,
+            }
+        };
+        changes.push(change);
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    fn find<'a>(files: &'a [FileDiff], path: &str) -> &'a FileDiff {
+        files.iter().find(|file| file.path == path).unwrap()
+    }
+
+    fn gutter(kind: GutterKind, start_line: u32, end_line: u32) -> GutterChange {
+        GutterChange { kind, start_line, end_line }
+    }
+
+    #[test]
+    fn workdir_index_and_revisions_are_diffed_separately() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "one\ntwo\nthree\n", "initial");
+        fs::write(path.join("a.txt"), "one\n2\nthree\n").unwrap();
+        fs::write(path.join("b.txt"), "staged\n").unwrap();
+        git(path, &["add", "b.txt"]);
+        fs::write(path.join("u.txt"), "untracked\n").unwrap();
+        let settings = DiffSettings::default();
+
+        let files = diff_workdir(path, &settings).unwrap();
+        assert_eq!(files.len(), 2);
+        let a = find(&files, "a.txt");
+        assert_eq!((&a.status, a.additions, a.deletions), (&FileStatus::Modified, 1, 1));
+        let lines: Vec<_> = a.hunks[0]
+            .lines
+            .iter()
+            .map(|line| (line.kind.clone(), line.content.as_str(), line.old_line, line.new_line))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (DiffLineKind::Context, "one", Some(1), Some(1)),
+                (DiffLineKind::Deletion, "two", Some(2), None),
+                (DiffLineKind::Addition, "2", None, Some(2)),
+                (DiffLineKind::Context, "three", Some(3), Some(3)),
+            ]
+        );
+        assert_eq!(find(&files, "u.txt").status, FileStatus::Untracked);
+
+        let files = diff_staged(path, &settings).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(find(&files, "b.txt").status, FileStatus::Added);
+
+        git(path, &["add", "-A"]);
+        git(path, &["commit", "-q", "-m", "second"]);
+        let files = diff_revisions(path, "HEAD~1", "HEAD", &settings).unwrap();
+        let mut statuses: Vec<_> = files.iter().map(|file| (file.path.as_str(), file.status.clone())).collect();
+        statuses.sort_by_key(|(path, _)| *path);
+        assert_eq!(
+            statuses,
+            vec![("a.txt", FileStatus::Modified), ("b.txt", FileStatus::Added), ("u.txt", FileStatus::Added)]
+        );
+        assert!(diff_revisions(path, "HEAD", "missing", &settings).is_err());
+    }
+
+    #[test]
+    fn renames_are_detected_unless_turned_off() {
+        let dir = init_repo();
+        let path = dir.path();
+        let content: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        commit_file(path, "old.txt", &content, "initial");
+        git(path, &["mv", "old.txt", "new.txt"]);
+        git(path, &["commit", "-q", "-m", "rename"]);
+
+        let files = diff_revisions(path, "HEAD~1", "HEAD", &DiffSettings::default()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].status, FileStatus::Renamed);
+        assert_eq!(files[0].path, "new.txt");
+        assert_eq!(files[0].old_path.as_deref(), Some("old.txt"));
+
+        let settings = DiffSettings {
+            detect_renames: false,
+            ..Default::default()
+        };
+        let files = diff_revisions(path, "HEAD~1", "HEAD", &settings).unwrap();
+        assert_eq!(find(&files, "old.txt").status, FileStatus::Deleted);
+        assert_eq!(find(&files, "new.txt").status, FileStatus::Added);
+    }
+
+    #[test]
+    fn whitespace_changes_can_be_ignored() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "let a = 1;\n", "initial");
+        fs::write(path.join("a.txt"), "let  a =\t1;\n").unwrap();
+
+        let files = diff_workdir(path, &DiffSettings::default()).unwrap();
+        assert_eq!((files[0].additions, files[0].deletions), (1, 1));
+
+        let settings = DiffSettings {
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let files = diff_workdir(path, &settings).unwrap();
+        assert!(files.iter().all(|file| file.hunks.is_empty() && file.additions == 0));
+    }
+
+    #[test]
+    fn binary_files_have_no_hunks() {
+        let dir = init_repo();
+        let path = dir.path();
+        fs::write(path.join("data.bin"), b"\0\x01\x02").unwrap();
+        git(path, &["add", "data.bin"]);
+        git(path, &["commit", "-q", "-m", "initial"]);
+        fs::write(path.join("data.bin"), b"\0\x01\x03").unwrap();
+
+        let files = diff_workdir(path, &DiffSettings::default()).unwrap();
+        assert!(files[0].binary);
+        assert!(files[0].hunks.is_empty());
+        assert_eq!(gutter_diff(path, "data.bin", None).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn gutter_marks_added_modified_and_deleted_lines() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "1\n2\n3\n4\n5\n", "initial");
+
+        let changes = gutter_diff(path, "a.txt", Some("0\n1\nX\n3\n5\n")).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                gutter(GutterKind::Added, 1, 1),
+                gutter(GutterKind::Modified, 3, 3),
+                gutter(GutterKind::Deleted, 4, 4),
+            ]
+        );
+
+        // Without a buffer the file on disk is compared
+        fs::write(path.join("a.txt"), "2\n3\n4\n5\n").unwrap();
+        assert_eq!(gutter_diff(path, "a.txt", None).unwrap(), vec![gutter(GutterKind::Deleted, 0, 0)]);
+
+        // A file that is not in HEAD is all new
+        assert_eq!(gutter_diff(path, "new.txt", Some("a\nb\n")).unwrap(), vec![gutter(GutterKind::Added, 1, 2)]);
+    }
+
+    #[test]
+    fn gutter_compares_through_line_ending_filters() {
+        let dir = init_repo();
+        let path = dir.path();
+        fs::write(path.join(".gitattributes"), "*.crlf eol=crlf\n").unwrap();
+        commit_file(path, "a.crlf", "a\nb\n", "initial");
+        commit_file(path, "b.txt", "a\nb\n", "second");
+        git(path, &["config", "core.autocrlf", "true"]);
+        fs::remove_file(path.join("a.crlf")).unwrap();
+        fs::remove_file(path.join("b.txt")).unwrap();
+        git(path, &["checkout", "--", "a.crlf", "b.txt"]);
+        assert_eq!(fs::read(path.join("b.txt")).unwrap(), b"a\r\nb\r\n");
+
+        for file in ["a.crlf", "b.txt"] {
+            assert_eq!(gutter_diff(path, file, None).unwrap(), Vec::new());
+            assert_eq!(
+                gutter_diff(path, file, Some("a\r\nc\r\n")).unwrap(),
+                vec![gutter(GutterKind::Modified, 2, 2)]
+            );
+        }
+
+        // The scratch checkouts are cleaned up
+        let scratch = fs::read_dir(path.join(".git"))
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("turkcode-checkout"))
+            .count();
+        assert_eq!(scratch, 0);
+    }
+}
@@ -0,0 +1,445 @@
+use git2::{Commit, DiffFindOptions, DiffOptions, Oid, Repository, Sort};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use crate::git::{commit_info, open_repo, relative_path, CommitInfo};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+// Filters and paging for a history query. All filters are optional.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogQuery {
+    // Revision to start from; defaults to HEAD
+    pub revision: Option<String>,
+    // Walk every local and remote-tracking branch instead of one revision
+    pub all_branches: bool,
+    // Opaque cursor from a previous page
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    // Only commits touching this file or directory
+    pub path: Option<String>,
+    // Keep following `path` across renames
+    pub follow: bool,
+    // Case-insensitive match on author name or email
+    pub author: Option<String>,
+    // Case-insensitive match on the commit message
+    pub message: Option<String>,
+    // Committer date bounds, RFC 3339 or YYYY-MM-DD
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+// A connection drawn below a commit's row, from a lane in this row to a
+// lane in the next one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    #[serde(flatten)]
+    pub commit: CommitInfo,
+    pub parents: Vec<String>,
+    pub lane: usize,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogPage {
+    pub commits: Vec<LogEntry>,
+    // Pass back as `cursor` to fetch the next page; `None` at the end
+    pub next_cursor: Option<String>,
+}
+
+// Walker state carried between pages: the commit each graph lane is waiting
+// for, tips not reached yet, and the followed path after any renames
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cursor {
+    lanes: Vec<Option<String>>,
+    tips: Vec<String>,
+    path: Option<String>,
+}
+
+// Walk the commit history
+pub fn get_commits(path: &Path, query: &LogQuery) -> Result<LogPage, String> {
+    let repo = open_repo(path)?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+
+    let cursor = match &query.cursor {
+        Some(cursor) => Some(
+            serde_json::from_str::<Cursor>(cursor).map_err(|_| "Invalid history cursor".to_string())?,
+        ),
+        None => None,
+    };
+
+    let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+    revwalk
+        .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+        .map_err(|e| e.to_string())?;
+
+    let mut lanes: Vec<Option<Oid>> = Vec::new();
+    let mut followed_path = match &query.path {
+        Some(file) => Some(relative_path(&repo, file)?),
+        None => None,
+    };
+
+    // Branch tips not yet reached by the walk; they are not in any lane
+    let mut pending_tips: Vec<Oid> = Vec::new();
+
+    match &cursor {
+        Some(cursor) => {
+            // Every commit not yet returned is an ancestor of a pending lane
+            // or of a tip the walk has not reached
+            for lane in &cursor.lanes {
+                lanes.push(lane.as_deref().map(Oid::from_str).transpose().map_err(|e| e.to_string())?);
+            }
+            for tip in &cursor.tips {
+                pending_tips.push(Oid::from_str(tip).map_err(|e| e.to_string())?);
+            }
+            for oid in lanes.iter().flatten().chain(pending_tips.iter()) {
+                revwalk.push(*oid).map_err(|e| e.to_string())?;
+            }
+            if cursor.path.is_some() {
+                followed_path = cursor.path.as_ref().map(PathBuf::from);
+            }
+        }
+        None if query.all_branches => {
+            for reference in repo.references().map_err(|e| e.to_string())? {
+                let reference = reference.map_err(|e| e.to_string())?;
+                if !(reference.is_branch() || reference.is_remote()) || reference.symbolic_target().is_some() {
+                    continue;
+                }
+                if let Ok(commit) = reference.peel_to_commit() {
+                    if !pending_tips.contains(&commit.id()) {
+                        pending_tips.push(commit.id());
+                    }
+                }
+            }
+            for oid in &pending_tips {
+                revwalk.push(*oid).map_err(|e| e.to_string())?;
+            }
+        }
+        None => {
+            let revision = query.revision.as_deref().unwrap_or("HEAD");
+            let start = match repo.revparse_single(revision).and_then(|object| object.peel_to_commit()) {
+                Ok(commit) => commit,
+                // A repository without commits has no history
+                Err(_) if query.revision.is_none() => {
+                    return Ok(LogPage { commits: Vec::new(), next_cursor: None });
+                }
+                Err(e) => return Err(format!("Invalid revision {}: {}", revision, e.message())),
+            };
+            pending_tips.push(start.id());
+            revwalk.push(start.id()).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let since = query.since.as_deref().map(|date| parse_date(date, false)).transpose()?;
+    let until = query.until.as_deref().map(|date| parse_date(date, true)).transpose()?;
+    let author = query.author.as_ref().map(|author| author.to_lowercase());
+    let message = query.message.as_ref().map(|message| message.to_lowercase());
+
+    // Filtered histories skip commits, so a branch graph would not connect;
+    // they are laid out on a single lane instead
+    let filtered = followed_path.is_some()
+        || author.is_some()
+        || message.is_some()
+        || since.is_some()
+        || until.is_some();
+
+    let mut commits = Vec::new();
+    let mut exhausted = true;
+
+    for oid in revwalk {
+        if commits.len() == limit {
+            exhausted = false;
+            break;
+        }
+
+        let oid = oid.map_err(|e| e.to_string())?;
+        let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+        let (lane, edges) = place_commit(&mut lanes, &commit);
+        pending_tips.retain(|tip| *tip != oid);
+
+        // Renames are tracked even through commits hidden by other filters
+        if let Some(file) = followed_path.clone() {
+            if !touches_path(&commit, &file) {
+                continue;
+            }
+            if query.follow {
+                if let Some(old_path) = renamed_from(&repo, &commit, &file)? {
+                    followed_path = Some(old_path);
+                }
+            }
+        }
+
+        let time = commit.committer().when().seconds();
+        if since.map_or(false, |since| time < since) || until.map_or(false, |until| time > until) {
+            continue;
+        }
+
+        if let Some(author) = &author {
+            let signature = commit.author();
+            let name = signature.name().unwrap_or_default().to_lowercase();
+            let email = signature.email().unwrap_or_default().to_lowercase();
+            if !name.contains(author) && !email.contains(author) {
+                continue;
+            }
+        }
+
+        if let Some(message) = &message {
+            if !commit.message().unwrap_or_default().to_lowercase().contains(message) {
+                continue;
+            }
+        }
+
+        let (lane, edges) = if filtered {
+            (0, vec![GraphEdge { from: 0, to: 0 }])
+        } else {
+            (lane, edges)
+        };
+
+        commits.push(LogEntry {
+            commit: commit_info(&commit),
+            parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+            lane,
+            edges,
+        });
+    }
+
+    // The last row of a complete history has nothing below it
+    if exhausted && filtered {
+        if let Some(last) = commits.last_mut() {
+            last.edges.clear();
+        }
+    }
+
+    let next_cursor = if exhausted {
+        None
+    } else {
+        let cursor = Cursor {
+            lanes: lanes.iter().map(|lane| lane.map(|oid| oid.to_string())).collect(),
+            tips: pending_tips.iter().map(Oid::to_string).collect(),
+            path: if query.follow {
+                followed_path.map(|p| p.to_string_lossy().to_string())
+            } else {
+                None
+            },
+        };
+        Some(serde_json::to_string(&cursor).map_err(|e| e.to_string())?)
+    };
+
+    Ok(LogPage { commits, next_cursor })
+}
+
+// Assign a commit to a graph lane and reserve lanes for its parents.
+// Returns the commit's lane and the edges leaving its row.
+fn place_commit(lanes: &mut Vec<Option<Oid>>, commit: &Commit<'_>) -> (usize, Vec<GraphEdge>) {
+    let oid = commit.id();
+
+    // Continue the leftmost lane waiting for this commit, or open a new one
+    let lane = match lanes.iter().position(|waiting| *waiting == Some(oid)) {
+        Some(lane) => lane,
+        None => free_lane(lanes),
+    };
+
+    // Other lanes waiting for this commit merge into it here
+    for waiting in lanes.iter_mut() {
+        if *waiting == Some(oid) {
+            *waiting = None;
+        }
+    }
+
+    let mut edges = Vec::new();
+    let mut own_lane_used = false;
+
+    for parent in commit.parent_ids() {
+        if let Some(existing) = lanes.iter().position(|waiting| *waiting == Some(parent)) {
+            edges.push(GraphEdge { from: lane, to: existing });
+        } else if !own_lane_used {
+            lanes[lane] = Some(parent);
+            own_lane_used = true;
+            edges.push(GraphEdge { from: lane, to: lane });
+        } else {
+            let target = free_lane(lanes);
+            lanes[target] = Some(parent);
+            edges.push(GraphEdge { from: lane, to: target });
+        }
+    }
+
+    // Lanes passing by this row continue straight down
+    for (index, waiting) in lanes.iter().enumerate() {
+        if waiting.is_some() && !edges.iter().any(|edge| edge.to == index) {
+            edges.push(GraphEdge { from: index, to: index });
+        }
+    }
+
+    while lanes.last() == Some(&None) {
+        lanes.pop();
+    }
+
+    edges.sort_by_key(|edge| (edge.from, edge.to));
+    (lane, edges)
+}
+
+fn free_lane(lanes: &mut Vec<Option<Oid>>) -> usize {
+    match lanes.iter().position(Option::is_none) {
+        Some(lane) => lane,
+        None => {
+            lanes.push(None);
+            lanes.len() - 1
+        }
+    }
+}
+
+// Whether a commit changed `path` compared to its parents. Merges only
+// count when they differ from every parent, like `git log -- <path>`.
+fn touches_path(commit: &Commit<'_>, path: &Path) -> bool {
+    let entry_id = |commit: &Commit<'_>| {
+        commit
+            .tree()
+            .ok()
+            .and_then(|tree| tree.get_path(path).ok())
+            .map(|entry| entry.id())
+    };
+
+    let current = entry_id(commit);
+    let parents: Vec<Option<Oid>> = commit.parents().map(|parent| entry_id(&parent)).collect();
+
+    if parents.is_empty() {
+        return current.is_some();
+    }
+
+    parents.iter().all(|parent| *parent != current)
+}
+
+// If `path` first appeared in this commit as a rename, return its old path
+fn renamed_from(repo: &Repository, commit: &Commit<'_>, path: &Path) -> Result<Option<PathBuf>, String> {
+    if commit.parent_count() != 1 {
+        return Ok(None);
+    }
+
+    let parent = commit.parent(0).map_err(|e| e.to_string())?;
+    let parent_tree = parent.tree().map_err(|e| e.to_string())?;
+    if parent_tree.get_path(path).is_ok() {
+        return Ok(None);
+    }
+
+    let tree = commit.tree().map_err(|e| e.to_string())?;
+    let mut diff = repo
+        .diff_tree_to_tree(Some(&parent_tree), Some(&tree), Some(&mut DiffOptions::new()))
+        .map_err(|e| e.to_string())?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(|e| e.to_string())?;
+
+    let old_path = diff
+        .deltas()
+        .find(|delta| {
+            delta.status() == git2::Delta::Renamed && delta.new_file().path() == Some(path)
+        })
+        .and_then(|delta| delta.old_file().path().map(Path::to_path_buf));
+
+    Ok(old_path)
+}
+
+// Parse an RFC 3339 timestamp or a plain date into seconds since the epoch.
+// A plain date covers the whole day, so `end_of_day` picks its last second.
+fn parse_date(value: &str, end_of_day: bool) -> Result<i64, String> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.timestamp());
+    }
+
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {}", value))?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+
+    Ok(time.unwrap_or_default().and_utc().timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    fn messages(page: &LogPage) -> Vec<String> {
+        page.commits.iter().map(|entry| entry.commit.message.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn pages_follow_the_cursor_to_the_end() {
+        let dir = init_repo();
+        for number in 1..=5 {
+            commit_file(dir.path(), "f.txt", &format!("{}\n", number), &format!("commit {}", number));
+        }
+
+        let mut query = LogQuery { limit: Some(2), ..Default::default() };
+        let mut seen = Vec::new();
+        loop {
+            let page = get_commits(dir.path(), &query).unwrap();
+            assert!(page.commits.len() <= 2);
+            seen.extend(messages(&page));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(seen, ["commit 5", "commit 4", "commit 3", "commit 2", "commit 1"]);
+    }
+
+    #[test]
+    fn lanes_continue_across_pages() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "base.txt", "base\n", "base");
+        git(path, &["checkout", "-q", "-b", "side"]);
+        commit_file(path, "side.txt", "side\n", "side");
+        git(path, &["checkout", "-q", "main"]);
+        commit_file(path, "main.txt", "main\n", "main");
+        git(path, &["merge", "-q", "--no-edit", "side"]);
+
+        let all = get_commits(path, &LogQuery::default()).unwrap();
+        let first = get_commits(path, &LogQuery { limit: Some(2), ..Default::default() }).unwrap();
+        let rest = get_commits(path, &LogQuery { cursor: first.next_cursor.clone(), ..Default::default() }).unwrap();
+
+        let paged: Vec<(String, usize)> = first
+            .commits
+            .iter()
+            .chain(rest.commits.iter())
+            .map(|entry| (entry.commit.hash.clone(), entry.lane))
+            .collect();
+        let whole: Vec<(String, usize)> = all.commits.iter().map(|entry| (entry.commit.hash.clone(), entry.lane)).collect();
+        assert_eq!(paged, whole);
+        assert_eq!(all.commits[0].parents.len(), 2);
+    }
+
+    #[test]
+    fn path_filter_follows_renames() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "old.txt", "content that is kept across the rename\n", "create");
+        commit_file(path, "other.txt", "other\n", "unrelated");
+        git(path, &["mv", "old.txt", "new.txt"]);
+        git(path, &["commit", "-q", "-m", "rename"]);
+
+        let query = LogQuery { path: Some("new.txt".to_string()), ..Default::default() };
+        assert_eq!(messages(&get_commits(path, &query).unwrap()), ["rename"]);
+
+        let query = LogQuery { path: Some("new.txt".to_string()), follow: true, ..Default::default() };
+        assert_eq!(messages(&get_commits(path, &query).unwrap()), ["rename", "create"]);
+    }
+
+    #[test]
+    fn invalid_cursor_is_rejected() {
+        let dir = init_repo();
+        commit_file(dir.path(), "f.txt", "f\n", "initial");
+        let query = LogQuery { cursor: Some("not a cursor".to_string()), ..Default::default() };
+        assert_eq!(get_commits(dir.path(), &query).unwrap_err(), "Invalid history cursor");
+    }
+}
@@ -1,24 +1,60 @@
+pub mod blame;
+pub mod branch;
+pub mod clone;
 pub mod commands;
+pub mod commit;
+pub mod conflict;
+pub mod diff;
+pub mod log;
+pub mod rebase;
+pub mod remote;
+pub mod repo_config;
+pub mod sequencer;
+pub mod stage;
+pub mod stash;
+pub mod state;
+pub mod sync;
+pub mod tag;
+pub mod watch;
+pub mod worktree;
 
+use git2::{Commit, Delta, DiffDelta, Repository, Status, StatusOptions, SubmoduleIgnore, Time};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FileStatus {
     Modified,
     Added,
     Deleted,
     Renamed,
     Copied,
+    TypeChanged,
     Untracked,
     Ignored,
     Conflicted,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// State of a submodule as seen from the superproject
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubmoduleState {
+    pub initialized: bool,
+    pub new_commits: bool,
+    pub modified_content: bool,
+    pub untracked_content: bool,
+}
+
+// A single change in either the index (`staged`) or the working tree.
+// A file that is both staged and further modified yields two entries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StatusEntry {
     pub path: String,
+    pub old_path: Option<String>,
     pub status: FileStatus,
+    pub staged: bool,
+    pub submodule: Option<SubmoduleState>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,6 +63,10 @@ pub struct BranchInfo {
     pub is_current: bool,
     pub is_remote: bool,
     pub remote: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub last_commit: Option<CommitInfo>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,100 +78,366 @@ pub struct CommitInfo {
     pub message: String,
 }
 
-// Check if a directory is a Git repository
+// Open the repository containing `path`, searching parent directories.
+// This also resolves linked worktrees and `.git` files pointing elsewhere.
+pub fn open_repo(path: &Path) -> Result<Repository, String> {
+    Repository::discover(path)
+        .map_err(|e| format!("Not a Git repository: {} ({})", path.display(), e.message()))
+}
+
+// Resolve a file path given by the UI to a path relative to the working tree
+pub fn relative_path(repo: &Repository, file_path: &str) -> Result<PathBuf, String> {
+    let file_path = Path::new(file_path);
+    if file_path.is_relative() {
+        return Ok(file_path.to_path_buf());
+    }
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| "Repository has no working directory".to_string())?;
+
+    if let Ok(relative) = file_path.strip_prefix(workdir) {
+        return Ok(relative.to_path_buf());
+    }
+
+    // The file may be reached through a symlink; compare canonical paths
+    let workdir = workdir.canonicalize().map_err(|e| e.to_string())?;
+    let parent = file_path
+        .parent()
+        .and_then(|parent| parent.canonicalize().ok())
+        .ok_or_else(|| format!("Path is outside the repository: {}", file_path.display()))?;
+    let name = file_path
+        .file_name()
+        .ok_or_else(|| format!("Invalid file path: {}", file_path.display()))?;
+
+    parent
+        .join(name)
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .map_err(|_| format!("Path is outside the repository: {}", file_path.display()))
+}
+
+// Open the repository at `repo_path` and resolve `file_path` inside it
+pub fn open_with_path(repo_path: &Path, file_path: &str) -> Result<(Repository, PathBuf), String> {
+    let repo = open_repo(repo_path)?;
+    let relative = relative_path(&repo, file_path)?;
+    Ok((repo, relative))
+}
+
+// Run the git executable in the repository for what libgit2 cannot do.
+// Returns stdout, or stderr as the error.
+pub fn run_git(repo: &Repository, args: &[&str]) -> Result<String, String> {
+    run_git_with_env(repo, args, &[])
+}
+
+pub fn run_git_with_env(repo: &Repository, args: &[&str], env: &[(&str, &str)]) -> Result<String, String> {
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(workdir)
+        .env("GIT_EDITOR", "true")
+        .envs(env.iter().copied())
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim_end().to_string())
+    }
+}
+
+// Convert a git timestamp to an RFC 3339 string in its original offset
+pub fn format_time(time: Time) -> String {
+    let offset = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+    chrono::DateTime::from_timestamp(time.seconds(), 0)
+        .unwrap_or_default()
+        .with_timezone(&offset)
+        .to_rfc3339()
+}
+
+pub fn commit_info(commit: &Commit<'_>) -> CommitInfo {
+    let hash = commit.id().to_string();
+    let author = commit.author();
+
+    CommitInfo {
+        short_hash: hash[..7].to_string(),
+        hash,
+        author: format!(
+            "{} <{}>",
+            author.name().unwrap_or_default(),
+            author.email().unwrap_or_default()
+        ),
+        date: format_time(author.when()),
+        message: commit.message().unwrap_or_default().to_string(),
+    }
+}
+
+// Check if a directory is inside a Git repository
 pub fn is_git_repo(path: &Path) -> bool {
-    let git_dir = path.join(".git");
-    git_dir.exists() && git_dir.is_dir()
-}
-
-// Get current Git status (mock implementation)
-pub fn get_repo_status(path: &Path) -> Result<Vec<StatusEntry>, String> {
-    // This would normally use libgit2 or subprocess to git
-    // For now, just return mock data
-    
-    if !is_git_repo(path) {
-        return Err("Not a Git repository".to_string());
-    }
-    
-    let mock_status = vec![
-        StatusEntry {
-            path: "src/main.rs".to_string(),
-            status: FileStatus::Modified,
-        },
-        StatusEntry {
-            path: "docs/README.md".to_string(),
-            status: FileStatus::Modified,
-        },
-        StatusEntry {
-            path: "new_file.txt".to_string(),
-            status: FileStatus::Untracked,
-        },
-    ];
-    
-    Ok(mock_status)
-}
-
-// Get list of branches (mock implementation)
-pub fn get_branches(path: &Path) -> Result<Vec<BranchInfo>, String> {
-    // This would normally use libgit2 or subprocess to git
-    // For now, just return mock data
-    
-    if !is_git_repo(path) {
-        return Err("Not a Git repository".to_string());
-    }
-    
-    let mock_branches = vec![
-        BranchInfo {
-            name: "main".to_string(),
-            is_current: true,
-            is_remote: false,
-            remote: None,
-        },
-        BranchInfo {
-            name: "develop".to_string(),
-            is_current: false,
-            is_remote: false,
-            remote: None,
-        },
-        BranchInfo {
-            name: "origin/main".to_string(),
-            is_current: false,
-            is_remote: true,
-            remote: Some("origin".to_string()),
-        },
-    ];
-    
-    Ok(mock_branches)
-}
-
-// Get recent commits (mock implementation)
-pub fn get_commits(path: &Path, limit: usize) -> Result<Vec<CommitInfo>, String> {
-    // This would normally use libgit2 or subprocess to git
-    // For now, just return mock data
-    
-    if !is_git_repo(path) {
-        return Err("Not a Git repository".to_string());
-    }
-    
-    let mock_commits = vec![
-        CommitInfo {
-            hash: "abcdef1234567890abcdef1234567890abcdef12".to_string(),
-            short_hash: "abcdef1".to_string(),
-            author: "John Doe <john@example.com>".to_string(),
-            date: "2025-06-28T12:34:56Z".to_string(),
-            message: "Fix bug in file processing".to_string(),
-        },
-        CommitInfo {
-            hash: "1234567890abcdef1234567890abcdef12345678".to_string(),
-            short_hash: "1234567".to_string(),
-            author: "Jane Smith <jane@example.com>".to_string(),
-            date: "2025-06-27T15:45:23Z".to_string(),
-            message: "Add new feature".to_string(),
-        },
-    ];
-    
-    // Limit the number of commits returned
-    let result = mock_commits.into_iter().take(limit).collect();
-    
-    Ok(result)
-} 
\ No newline at end of file
+    open_repo(path).is_ok()
+}
+
+// Get current Git status of the index and working tree
+pub fn get_repo_status(path: &Path, include_ignored: bool) -> Result<Vec<StatusEntry>, String> {
+    let repo = open_repo(path)?;
+    if repo.is_bare() {
+        return Err("Cannot get status of a bare repository".to_string());
+    }
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(include_ignored)
+        .recurse_ignored_dirs(false)
+        .exclude_submodules(false)
+        .renames_head_to_index(true)
+        .renames_index_to_workdir(true);
+
+    let statuses = repo.statuses(Some(&mut options)).map_err(|e| e.to_string())?;
+
+    // Map submodule paths to names so their detailed state can be looked up
+    let submodules: HashMap<String, String> = repo
+        .submodules()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .filter_map(|sm| {
+            let name = sm.name()?.to_string();
+            Some((sm.path().to_string_lossy().to_string(), name))
+        })
+        .collect();
+
+    let mut entries = Vec::new();
+
+    for entry in statuses.iter() {
+        let flags = entry.status();
+        let path = match entry.path() {
+            Some(path) => path.to_string(),
+            None => continue,
+        };
+
+        let submodule = match submodules.get(&path) {
+            Some(name) => Some(submodule_state(&repo, name)?),
+            None => None,
+        };
+
+        if flags.contains(Status::CONFLICTED) {
+            entries.push(StatusEntry {
+                path,
+                old_path: None,
+                status: FileStatus::Conflicted,
+                staged: false,
+                submodule,
+            });
+            continue;
+        }
+
+        if flags.contains(Status::IGNORED) {
+            entries.push(StatusEntry {
+                path,
+                old_path: None,
+                status: FileStatus::Ignored,
+                staged: false,
+                submodule,
+            });
+            continue;
+        }
+
+        if let Some(status) = index_status(flags) {
+            let (path, old_path) = delta_paths(entry.head_to_index(), &path);
+            entries.push(StatusEntry {
+                path,
+                old_path,
+                status,
+                staged: true,
+                submodule: submodule.clone(),
+            });
+        }
+
+        if let Some(status) = worktree_status(flags) {
+            let (path, old_path) = delta_paths(entry.index_to_workdir(), &path);
+            entries.push(StatusEntry {
+                path,
+                old_path,
+                status,
+                staged: false,
+                submodule,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+fn index_status(flags: Status) -> Option<FileStatus> {
+    if flags.contains(Status::INDEX_NEW) {
+        Some(FileStatus::Added)
+    } else if flags.contains(Status::INDEX_MODIFIED) {
+        Some(FileStatus::Modified)
+    } else if flags.contains(Status::INDEX_DELETED) {
+        Some(FileStatus::Deleted)
+    } else if flags.contains(Status::INDEX_RENAMED) {
+        Some(FileStatus::Renamed)
+    } else if flags.contains(Status::INDEX_TYPECHANGE) {
+        Some(FileStatus::TypeChanged)
+    } else {
+        None
+    }
+}
+
+fn worktree_status(flags: Status) -> Option<FileStatus> {
+    if flags.contains(Status::WT_NEW) {
+        Some(FileStatus::Untracked)
+    } else if flags.contains(Status::WT_MODIFIED) {
+        Some(FileStatus::Modified)
+    } else if flags.contains(Status::WT_DELETED) {
+        Some(FileStatus::Deleted)
+    } else if flags.contains(Status::WT_RENAMED) {
+        Some(FileStatus::Renamed)
+    } else if flags.contains(Status::WT_TYPECHANGE) {
+        Some(FileStatus::TypeChanged)
+    } else {
+        None
+    }
+}
+
+// Extract the new path and, for renames and copies, the old path of a delta
+fn delta_paths(delta: Option<DiffDelta<'_>>, fallback: &str) -> (String, Option<String>) {
+    let delta = match delta {
+        Some(delta) => delta,
+        None => return (fallback.to_string(), None),
+    };
+
+    let new_path = delta
+        .new_file()
+        .path()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| fallback.to_string());
+
+    let old_path = match delta.status() {
+        Delta::Renamed | Delta::Copied => delta
+            .old_file()
+            .path()
+            .map(|p| p.to_string_lossy().to_string()),
+        _ => None,
+    };
+
+    (new_path, old_path)
+}
+
+fn submodule_state(repo: &Repository, name: &str) -> Result<SubmoduleState, String> {
+    let status = repo
+        .submodule_status(name, SubmoduleIgnore::None)
+        .map_err(|e| e.to_string())?;
+
+    Ok(SubmoduleState {
+        initialized: status.contains(git2::SubmoduleStatus::IN_WD)
+            && !status.contains(git2::SubmoduleStatus::WD_UNINITIALIZED),
+        new_commits: status.contains(git2::SubmoduleStatus::WD_MODIFIED),
+        modified_content: status.intersects(
+            git2::SubmoduleStatus::WD_INDEX_MODIFIED | git2::SubmoduleStatus::WD_WD_MODIFIED,
+        ),
+        untracked_content: status.contains(git2::SubmoduleStatus::WD_UNTRACKED),
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // A repository on `main` with a committer identity, shared by the tests
+    // of the git modules
+    pub fn init_repo() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        git(dir.path(), &["config", "user.name", "Test"]);
+        git(dir.path(), &["config", "user.email", "test@example.com"]);
+        git(dir.path(), &["config", "commit.gpgsign", "false"]);
+        dir
+    }
+
+    // Run git in `dir` and return its output, failing the test on errors
+    pub fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    pub fn commit_file(dir: &Path, name: &str, content: &str, message: &str) {
+        fs::write(dir.join(name), content).unwrap();
+        git(dir, &["add", name]);
+        git(dir, &["commit", "-q", "-m", message]);
+    }
+
+    fn find<'a>(entries: &'a [StatusEntry], path: &str, staged: bool) -> Option<&'a StatusEntry> {
+        entries.iter().find(|entry| entry.path == path && entry.staged == staged)
+    }
+
+    #[test]
+    fn status_reports_index_and_working_tree_separately() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "one\n", "initial");
+
+        fs::write(path.join("a.txt"), "two\n").unwrap();
+        git(path, &["add", "a.txt"]);
+        fs::write(path.join("a.txt"), "three\n").unwrap();
+        fs::write(path.join("new.txt"), "new\n").unwrap();
+
+        let entries = get_repo_status(path, false).unwrap();
+        assert_eq!(find(&entries, "a.txt", true).unwrap().status, FileStatus::Modified);
+        assert_eq!(find(&entries, "a.txt", false).unwrap().status, FileStatus::Modified);
+        assert_eq!(find(&entries, "new.txt", false).unwrap().status, FileStatus::Untracked);
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn status_detects_staged_renames() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "old.txt", "some content that stays the same\n", "initial");
+        git(path, &["mv", "old.txt", "new.txt"]);
+
+        let entries = get_repo_status(path, false).unwrap();
+        let renamed = find(&entries, "new.txt", true).unwrap();
+        assert_eq!(renamed.status, FileStatus::Renamed);
+        assert_eq!(renamed.old_path.as_deref(), Some("old.txt"));
+    }
+
+    #[test]
+    fn status_lists_ignored_files_only_when_asked() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, ".gitignore", "*.log\n", "ignore logs");
+        fs::write(path.join("debug.log"), "log\n").unwrap();
+
+        assert!(get_repo_status(path, false).unwrap().is_empty());
+        let entries = get_repo_status(path, true).unwrap();
+        assert_eq!(find(&entries, "debug.log", false).unwrap().status, FileStatus::Ignored);
+    }
+
+    #[test]
+    fn status_reports_conflicts_once() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "f.txt", "base\n", "base");
+        git(path, &["checkout", "-q", "-b", "other"]);
+        commit_file(path, "f.txt", "other\n", "other");
+        git(path, &["checkout", "-q", "main"]);
+        commit_file(path, "f.txt", "main\n", "main");
+        let _ = Command::new("git").args(["merge", "other"]).current_dir(path).output();
+
+        let entries = get_repo_status(path, false).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, FileStatus::Conflicted);
+    }
+}
\ No newline at end of file
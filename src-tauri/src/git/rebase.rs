@@ -0,0 +1,375 @@
+use git2::{Oid, Repository, RepositoryState};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::GitConfig;
+use crate::git::branch::has_local_changes;
+use crate::git::state::OperationResult;
+use crate::git::sync::conflicted_paths;
+use crate::git::{commit_info, open_repo, run_git_with_env, CommitInfo};
+
+// Messages for reworded and squashed commits, inside the git directory
+const MESSAGES_DIR: &str = "turkcode-rebase";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RebaseAction {
+    Pick,
+    // Pick with a new commit message
+    Reword,
+    // Pick and stop so the commit can be amended
+    Edit,
+    // Meld into the previous commit, combining the messages
+    Squash,
+    // Meld into the previous commit, keeping its message
+    Fixup,
+    Drop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebaseStep {
+    pub action: RebaseAction,
+    pub commit: String,
+    // New message for reword, or for the combined commit of a squash
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+// An interactive rebase as submitted by the UI. Steps are applied in order
+// on top of `onto`, or `upstream` when it is not set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebasePlan {
+    pub upstream: String,
+    #[serde(default)]
+    pub onto: Option<String>,
+    pub steps: Vec<RebaseStep>,
+}
+
+// Commits a rebase onto `upstream` would rewrite, oldest first, for the
+// UI to build a plan from
+pub fn rebase_commits(path: &Path, upstream: &str) -> Result<Vec<CommitInfo>, String> {
+    let repo = open_repo(path)?;
+    let upstream_id = resolve_commit(&repo, upstream)?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+
+    let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+    revwalk
+        .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)
+        .map_err(|e| e.to_string())?;
+    revwalk.push(head.id()).map_err(|e| e.to_string())?;
+    revwalk.hide(upstream_id).map_err(|e| e.to_string())?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo
+            .find_commit(oid.map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        // Like `git rebase`, merges are dropped from the rewritten history
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        commits.push(commit_info(&commit));
+    }
+
+    Ok(commits)
+}
+
+fn resolve_commit(repo: &Repository, revision: &str) -> Result<Oid, String> {
+    repo.revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|e| format!("Invalid revision {}: {}", revision, e.message()))
+}
+
+// Run an interactive rebase. libgit2 cannot do these, so the plan is turned
+// into a todo list for `git rebase -i`. Its state lives in the repository
+// and is continued or aborted like any other rebase.
+pub fn start_interactive_rebase(path: &Path, plan: &RebasePlan, identity: &GitConfig) -> Result<OperationResult, String> {
+    let repo = open_repo(path)?;
+
+    if repo.state() != RepositoryState::Clean {
+        return Err("Another operation is in progress. Continue or abort it first.".to_string());
+    }
+    if has_local_changes(&repo)? {
+        return Err("Cannot rebase with uncommitted changes. Commit or stash them first.".to_string());
+    }
+
+    let first_kept = plan.steps.iter().find(|step| step.action != RebaseAction::Drop);
+    if let Some(step) = first_kept {
+        if matches!(step.action, RebaseAction::Squash | RebaseAction::Fixup) {
+            return Err("The first commit cannot be squashed into a previous one".to_string());
+        }
+    }
+
+    let upstream = resolve_commit(&repo, &plan.upstream)?.to_string();
+    let onto = match &plan.onto {
+        Some(onto) => Some(resolve_commit(&repo, onto)?.to_string()),
+        None => None,
+    };
+
+    // Messages are handed to `git commit` through files that must outlive
+    // this call, since the rebase may stop and be continued later
+    let messages_dir = messages_dir(&repo);
+    if messages_dir.exists() {
+        fs::remove_dir_all(&messages_dir).map_err(|e| e.to_string())?;
+    }
+
+    let mut todo = String::new();
+    for (number, step) in plan.steps.iter().enumerate() {
+        let commit = resolve_commit(&repo, &step.commit)?;
+        let action = match step.action {
+            // The message is set by the `exec` below, which needs no editor
+            RebaseAction::Pick | RebaseAction::Reword => "pick",
+            RebaseAction::Edit => "edit",
+            RebaseAction::Squash => "squash",
+            RebaseAction::Fixup => "fixup",
+            RebaseAction::Drop => "drop",
+        };
+        todo.push_str(&format!("{} {}\n", action, commit));
+
+        let message = step
+            .message
+            .as_deref()
+            .filter(|message| !message.trim().is_empty());
+        if let (RebaseAction::Reword | RebaseAction::Squash, Some(message)) = (step.action, message) {
+            fs::create_dir_all(&messages_dir).map_err(|e| e.to_string())?;
+            let message_file = messages_dir.join(format!("{}.txt", number));
+            fs::write(&message_file, message).map_err(|e| e.to_string())?;
+            // The file is looked up by git rather than spelled out, so the
+            // command needs no quoting for the repository's path
+            todo.push_str(&format!(
+                "exec git commit --amend --quiet --allow-empty -F \"$(git rev-parse --git-path {}/{}.txt)\"\n",
+                MESSAGES_DIR, number
+            ));
+        }
+    }
+
+    let todo_file = repo.path().join("TURKCODE_REBASE_TODO");
+    fs::write(&todo_file, todo).map_err(|e| e.to_string())?;
+    // git runs the sequence editor through its shell, on Windows too, with
+    // the path of its todo list appended. Ours is passed in the environment
+    // so that no path has to be quoted.
+    let todo_path = todo_file.to_string_lossy().to_string();
+
+    let identity_args = identity_args(identity);
+    let mut args: Vec<&str> = identity_args.iter().map(String::as_str).collect();
+    args.extend(["rebase", "--interactive"]);
+    if let Some(onto) = &onto {
+        args.extend(["--onto", onto.as_str()]);
+    }
+    args.push(&upstream);
+
+    let env = [
+        ("GIT_SEQUENCE_EDITOR", "cp \"$TURKCODE_REBASE_TODO\""),
+        ("TURKCODE_REBASE_TODO", todo_path.as_str()),
+    ];
+    let output = run_git_with_env(&repo, &args, &env);
+    let _ = fs::remove_file(&todo_file);
+
+    rebase_outcome(&repo, output)
+}
+
+// Continue a rebase run by git, optionally with a new message for the
+// commit it stopped at
+pub fn continue_rebase(repo: &Repository, message: Option<&str>, identity: &GitConfig) -> Result<OperationResult, String> {
+    if let Some(message) = message {
+        // `git rebase --continue` picks up an edited message from here
+        let message_file = repo.path().join("rebase-merge").join("message");
+        if message_file.exists() {
+            fs::write(&message_file, message).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let identity_args = identity_args(identity);
+    let mut args: Vec<&str> = identity_args.iter().map(String::as_str).collect();
+    args.extend(["rebase", "--continue"]);
+
+    let output = run_git_with_env(repo, &args, &[]);
+    rebase_outcome(repo, output)
+}
+
+pub fn abort_rebase(repo: &Repository) -> Result<(), String> {
+    run_git_with_env(repo, &["rebase", "--abort"], &[])?;
+    remove_messages(repo)
+}
+
+// Turn the result of a git rebase command into an operation result. A
+// rebase that stopped for conflicts or an `edit` step is not an error.
+fn rebase_outcome(repo: &Repository, output: Result<String, String>) -> Result<OperationResult, String> {
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    // git rewrote the index behind libgit2's back
+    index.read(true).map_err(|e| e.to_string())?;
+    let in_progress = repo.state() != RepositoryState::Clean;
+
+    if in_progress && index.has_conflicts() {
+        return Ok(OperationResult {
+            completed: false,
+            conflicts: conflicted_paths(&index),
+            commit: None,
+        });
+    }
+    output?;
+
+    if !in_progress {
+        remove_messages(repo)?;
+    }
+
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+    Ok(OperationResult {
+        completed: !in_progress,
+        conflicts: Vec::new(),
+        commit: Some(commit_info(&head)),
+    })
+}
+
+fn messages_dir(repo: &Repository) -> PathBuf {
+    repo.path().join(MESSAGES_DIR)
+}
+
+fn remove_messages(repo: &Repository) -> Result<(), String> {
+    let dir = messages_dir(repo);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// `-c` options applying the identity from the app settings to commits git
+// creates on our behalf
+fn identity_args(identity: &GitConfig) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(name) = identity.author_name.as_deref().filter(|name| !name.trim().is_empty()) {
+        args.push("-c".to_string());
+        args.push(format!("user.name={}", name));
+    }
+    if let Some(email) = identity.author_email.as_deref().filter(|email| !email.trim().is_empty()) {
+        args.push("-c".to_string());
+        args.push(format!("user.email={}", email));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::state::{continue_operation, get_repository_state, OperationKind};
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    fn step(action: RebaseAction, commit: &CommitInfo, message: Option<&str>) -> RebaseStep {
+        RebaseStep {
+            action,
+            commit: commit.hash.clone(),
+            message: message.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn interactive_rebase_reorders_rewords_and_squashes() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "f.txt", "base\n", "base");
+        git(path, &["checkout", "-q", "-b", "feature"]);
+        commit_file(path, "a.txt", "a\n", "one");
+        commit_file(path, "f.txt", "feature\n", "conflicting");
+        commit_file(path, "c.txt", "c\n", "three");
+        git(path, &["checkout", "-q", "main"]);
+        commit_file(path, "f.txt", "main\n", "main change");
+        git(path, &["checkout", "-q", "feature"]);
+
+        let commits = rebase_commits(path, "main").unwrap();
+        assert_eq!(commits.len(), 3);
+        let plan = RebasePlan {
+            upstream: "main".to_string(),
+            onto: None,
+            steps: vec![
+                step(RebaseAction::Pick, &commits[2], None),
+                step(RebaseAction::Reword, &commits[0], Some("uno")),
+                step(RebaseAction::Squash, &commits[1], Some("combined\n\nbody")),
+            ],
+        };
+
+        let result = start_interactive_rebase(path, &plan, &GitConfig::default()).unwrap();
+        assert!(!result.completed);
+        assert_eq!(result.conflicts, ["f.txt"]);
+        let state = get_repository_state(path).unwrap();
+        assert_eq!(state.operation, OperationKind::Rebase);
+        // Each new message adds an `exec` step after its pick
+        assert_eq!((state.step, state.total_steps), (Some(4), Some(5)));
+
+        fs::write(path.join("f.txt"), "resolved\n").unwrap();
+        git(path, &["add", "f.txt"]);
+        let result = continue_operation(path, None, &GitConfig::default()).unwrap();
+        assert!(result.completed);
+        assert_eq!(git(path, &["log", "--format=%s", "-3"]), "combined\nthree\nmain change\n");
+        assert!(!messages_dir(&Repository::open(path).unwrap()).exists());
+    }
+
+    #[test]
+    fn edit_step_stops_without_conflicts() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "base");
+        commit_file(path, "b.txt", "b\n", "two");
+        commit_file(path, "c.txt", "c\n", "three");
+
+        let commits = rebase_commits(path, "HEAD~2").unwrap();
+        let plan = RebasePlan {
+            upstream: "HEAD~2".to_string(),
+            onto: None,
+            steps: vec![
+                step(RebaseAction::Edit, &commits[0], None),
+                step(RebaseAction::Drop, &commits[1], None),
+            ],
+        };
+
+        let result = start_interactive_rebase(path, &plan, &GitConfig::default()).unwrap();
+        assert!(!result.completed && result.conflicts.is_empty());
+        assert_eq!(get_repository_state(path).unwrap().operation, OperationKind::Rebase);
+        assert!(continue_operation(path, None, &GitConfig::default()).unwrap().completed);
+        assert_eq!(git(path, &["log", "--format=%s"]), "two\nbase\n");
+    }
+
+    #[test]
+    fn first_step_cannot_be_a_squash() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "base");
+        commit_file(path, "b.txt", "b\n", "two");
+
+        let commits = rebase_commits(path, "HEAD~1").unwrap();
+        let plan = RebasePlan {
+            upstream: "HEAD~1".to_string(),
+            onto: None,
+            steps: vec![step(RebaseAction::Squash, &commits[0], None)],
+        };
+        assert!(start_interactive_rebase(path, &plan, &GitConfig::default()).is_err());
+    }
+
+    #[test]
+    fn rebase_works_in_paths_that_need_quoting() {
+        let parent = tempfile::tempdir().unwrap();
+        let path = parent.path().join("it's \"quoted\" $HOME");
+        fs::create_dir(&path).unwrap();
+        git(&path, &["init", "-q", "-b", "main"]);
+        git(&path, &["config", "user.name", "Test"]);
+        git(&path, &["config", "user.email", "test@example.com"]);
+        git(&path, &["config", "commit.gpgsign", "false"]);
+        commit_file(&path, "a.txt", "a\n", "base");
+        commit_file(&path, "b.txt", "b\n", "two");
+
+        let commits = rebase_commits(&path, "HEAD~1").unwrap();
+        let plan = RebasePlan {
+            upstream: "HEAD~1".to_string(),
+            onto: None,
+            steps: vec![step(RebaseAction::Reword, &commits[0], Some("it's reworded"))],
+        };
+
+        assert!(start_interactive_rebase(&path, &plan, &GitConfig::default()).unwrap().completed);
+        assert_eq!(git(&path, &["log", "--format=%s"]), "it's reworded\nbase\n");
+    }
+}
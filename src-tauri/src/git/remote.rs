@@ -0,0 +1,152 @@
+use git2::Remote;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::git::open_repo;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoteInfo {
+    pub name: String,
+    pub url: Option<String>,
+    // Only set when pushes go to a different URL
+    pub push_url: Option<String>,
+}
+
+pub fn list_remotes(path: &Path) -> Result<Vec<RemoteInfo>, String> {
+    let repo = open_repo(path)?;
+    let names = repo.remotes().map_err(|e| e.to_string())?;
+    let mut remotes = Vec::new();
+
+    for name in names.iter().flatten() {
+        let remote = repo.find_remote(name).map_err(|e| e.to_string())?;
+        remotes.push(RemoteInfo {
+            name: name.to_string(),
+            url: remote.url().map(str::to_string),
+            push_url: remote.pushurl().map(str::to_string),
+        });
+    }
+
+    Ok(remotes)
+}
+
+pub fn add_remote(path: &Path, name: &str, url: &str) -> Result<(), String> {
+    let repo = open_repo(path)?;
+
+    if !Remote::is_valid_name(name) {
+        return Err(format!("Invalid remote name: {}", name));
+    }
+    if url.trim().is_empty() {
+        return Err("Remote URL cannot be empty".to_string());
+    }
+
+    repo.remote(name, url.trim()).map_err(|e| match e.code() {
+        git2::ErrorCode::Exists => format!("Remote {} already exists", name),
+        _ => e.to_string(),
+    })?;
+    Ok(())
+}
+
+// Rename a remote along with its remote-tracking branches. Returns refspecs
+// that could not be renamed automatically and need updating by hand.
+pub fn rename_remote(path: &Path, old_name: &str, new_name: &str) -> Result<Vec<String>, String> {
+    let repo = open_repo(path)?;
+
+    if !Remote::is_valid_name(new_name) {
+        return Err(format!("Invalid remote name: {}", new_name));
+    }
+
+    let problems = repo.remote_rename(old_name, new_name).map_err(|e| e.to_string())?;
+    Ok(problems.iter().flatten().map(str::to_string).collect())
+}
+
+pub fn remove_remote(path: &Path, name: &str) -> Result<(), String> {
+    let repo = open_repo(path)?;
+    repo.remote_delete(name).map_err(|e| match e.code() {
+        git2::ErrorCode::NotFound => format!("Remote not found: {}", name),
+        _ => e.to_string(),
+    })
+}
+
+// Change the fetch URL, or with `push` the separate push URL. An empty push
+// URL removes it so pushes use the fetch URL again.
+pub fn set_remote_url(path: &Path, name: &str, url: &str, push: bool) -> Result<(), String> {
+    let repo = open_repo(path)?;
+    repo.find_remote(name)
+        .map_err(|_| format!("Remote not found: {}", name))?;
+
+    let url = url.trim();
+    if push {
+        if url.is_empty() {
+            let mut config = repo.config().map_err(|e| e.to_string())?;
+            return match config.remove(&format!("remote.{}.pushurl", name)) {
+                Err(e) if e.code() != git2::ErrorCode::NotFound => Err(e.to_string()),
+                _ => Ok(()),
+            };
+        }
+        repo.remote_set_pushurl(name, Some(url)).map_err(|e| e.to_string())
+    } else {
+        if url.is_empty() {
+            return Err("Remote URL cannot be empty".to_string());
+        }
+        repo.remote_set_url(name, url).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    #[test]
+    fn add_rename_and_remove_remotes() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+
+        add_remote(path, "origin", " https://example.com/a.git ").unwrap();
+        assert_eq!(add_remote(path, "origin", "https://example.com/b.git").unwrap_err(), "Remote origin already exists");
+        assert!(add_remote(path, "bad name", "https://example.com/b.git").unwrap_err().starts_with("Invalid remote name"));
+        assert_eq!(add_remote(path, "other", "  ").unwrap_err(), "Remote URL cannot be empty");
+
+        let remotes = list_remotes(path).unwrap();
+        assert_eq!(remotes.len(), 1);
+        assert_eq!(remotes[0].url.as_deref(), Some("https://example.com/a.git"));
+        assert_eq!(remotes[0].push_url, None);
+
+        // Remote-tracking branches follow the rename
+        git(path, &["update-ref", "refs/remotes/origin/main", "HEAD"]);
+        let problems = rename_remote(path, "origin", "upstream").unwrap();
+        assert!(problems.is_empty());
+        let names: Vec<String> = list_remotes(path).unwrap().into_iter().map(|remote| remote.name).collect();
+        assert_eq!(names, ["upstream"]);
+        git(path, &["rev-parse", "--verify", "refs/remotes/upstream/main"]);
+        assert!(rename_remote(path, "upstream", "bad name").is_err());
+
+        remove_remote(path, "upstream").unwrap();
+        assert_eq!(remove_remote(path, "upstream").unwrap_err(), "Remote not found: upstream");
+        assert!(list_remotes(path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn set_fetch_and_push_urls() {
+        let dir = init_repo();
+        let path = dir.path();
+        add_remote(path, "origin", "https://example.com/a.git").unwrap();
+
+        set_remote_url(path, "origin", "https://example.com/b.git", false).unwrap();
+        set_remote_url(path, "origin", "git@example.com:b.git", true).unwrap();
+        let remote = &list_remotes(path).unwrap()[0];
+        assert_eq!(remote.url.as_deref(), Some("https://example.com/b.git"));
+        assert_eq!(remote.push_url.as_deref(), Some("git@example.com:b.git"));
+
+        // An empty push URL falls back to the fetch URL, twice is fine
+        set_remote_url(path, "origin", "", true).unwrap();
+        set_remote_url(path, "origin", "", true).unwrap();
+        assert_eq!(list_remotes(path).unwrap()[0].push_url, None);
+
+        assert_eq!(set_remote_url(path, "origin", " ", false).unwrap_err(), "Remote URL cannot be empty");
+        assert_eq!(
+            set_remote_url(path, "missing", "https://example.com/c.git", false).unwrap_err(),
+            "Remote not found: missing"
+        );
+    }
+}
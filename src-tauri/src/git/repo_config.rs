@@ -0,0 +1,122 @@
+use git2::{Config, ConfigLevel, Repository};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::git::open_repo;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    pub name: String,
+    pub value: String,
+}
+
+// The repository's own `.git/config`, leaving global settings untouched
+fn local_config(repo: &Repository) -> Result<Config, String> {
+    repo.config()
+        .and_then(|config| config.open_level(ConfigLevel::Local))
+        .map_err(|e| e.to_string())
+}
+
+// All keys set in the repository configuration. Multi-valued keys appear
+// once per value.
+pub fn list_config(path: &Path) -> Result<Vec<ConfigEntry>, String> {
+    let repo = open_repo(path)?;
+    let config = local_config(&repo)?;
+    let mut entries = Vec::new();
+
+    let mut iter = config.entries(None).map_err(|e| e.to_string())?;
+    while let Some(entry) = iter.next() {
+        let entry = entry.map_err(|e| e.to_string())?;
+        entries.push(ConfigEntry {
+            name: entry.name().unwrap_or_default().to_string(),
+            value: entry.value().unwrap_or_default().to_string(),
+        });
+    }
+
+    Ok(entries)
+}
+
+// Read a key as git would see it, i.e. including global and system values
+pub fn get_config_value(path: &Path, key: &str) -> Result<Option<String>, String> {
+    let repo = open_repo(path)?;
+    let config = repo.config().map_err(|e| e.to_string())?;
+
+    match config.get_string(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Settings the UI may change. Keys such as `core.hooksPath`, `core.fsmonitor`
+// or `alias.*` make git run commands, so anything else is refused.
+const WRITABLE_KEYS: &[&str] = &["user.name", "user.email", "commit.gpgsign", "pull.rebase"];
+const WRITABLE_BRANCH_KEYS: &[&str] = &["merge", "remote"];
+
+// Section and key names are case-insensitive, subsections such as branch
+// names are not
+fn check_writable(key: &str) -> Result<(), String> {
+    let writable = match key.split_once('.') {
+        Some((section, rest)) => match rest.rsplit_once('.') {
+            Some((branch, name)) => {
+                section.eq_ignore_ascii_case("branch")
+                    && !branch.is_empty()
+                    && WRITABLE_BRANCH_KEYS.iter().any(|allowed| allowed.eq_ignore_ascii_case(name))
+            }
+            None => WRITABLE_KEYS.iter().any(|allowed| allowed.eq_ignore_ascii_case(key)),
+        },
+        None => false,
+    };
+
+    if writable {
+        Ok(())
+    } else {
+        Err(format!("Changing {} is not allowed", key))
+    }
+}
+
+pub fn set_config_value(path: &Path, key: &str, value: &str) -> Result<(), String> {
+    check_writable(key)?;
+    let repo = open_repo(path)?;
+    let mut config = local_config(&repo)?;
+    config.set_str(key, value).map_err(|e| e.to_string())
+}
+
+pub fn unset_config_value(path: &Path, key: &str) -> Result<(), String> {
+    check_writable(key)?;
+    let repo = open_repo(path)?;
+    let mut config = local_config(&repo)?;
+    match config.remove(key) {
+        Err(e) if e.code() != git2::ErrorCode::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{git, init_repo};
+
+    #[test]
+    fn only_allowed_keys_can_be_written() {
+        let dir = init_repo();
+        let path = dir.path();
+
+        set_config_value(path, "pull.rebase", "true").unwrap();
+        set_config_value(path, "User.Name", "Someone").unwrap();
+        set_config_value(path, "branch.feature/x.remote", "origin").unwrap();
+        assert_eq!(get_config_value(path, "pull.rebase").unwrap().as_deref(), Some("true"));
+        assert_eq!(get_config_value(path, "user.name").unwrap().as_deref(), Some("Someone"));
+        assert_eq!(git(path, &["config", "branch.feature/x.remote"]).trim(), "origin");
+
+        for key in ["core.hooksPath", "core.fsmonitor", "alias.st", "branch..remote", "branch.main.pushRemote", "user"] {
+            assert_eq!(set_config_value(path, key, "x").unwrap_err(), format!("Changing {} is not allowed", key));
+            assert_eq!(unset_config_value(path, key).unwrap_err(), format!("Changing {} is not allowed", key));
+        }
+        assert_eq!(get_config_value(path, "core.hooksPath").unwrap(), None);
+
+        unset_config_value(path, "pull.rebase").unwrap();
+        unset_config_value(path, "pull.rebase").unwrap();
+        assert_eq!(get_config_value(path, "pull.rebase").unwrap(), None);
+        assert!(list_config(path).unwrap().iter().any(|entry| entry.name == "branch.feature/x.remote"));
+    }
+}
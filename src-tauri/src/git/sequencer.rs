@@ -0,0 +1,379 @@
+use git2::{CherrypickOptions, Commit, Oid, Repository, RepositoryState, ResetType, RevertOptions};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::GitConfig;
+use crate::git::branch::has_local_changes;
+use crate::git::commit::{clear_operation_files, create_commit, create_commit_as, CommitOptions};
+use crate::git::state::OperationResult;
+use crate::git::sync::conflicted_paths;
+use crate::git::{commit_info, open_repo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SequenceAction {
+    Pick,
+    Revert,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SequenceOptions {
+    // Parent to diff against when picking or reverting a merge commit
+    pub mainline: Option<u32>,
+}
+
+// One remaining step of a cherry-pick or revert sequence
+struct SequenceStep {
+    action: SequenceAction,
+    commit: Oid,
+}
+
+// Cherry-pick commits onto HEAD in the given order
+pub fn cherry_pick(
+    path: &Path,
+    commits: &[String],
+    options: &SequenceOptions,
+    identity: &GitConfig,
+) -> Result<OperationResult, String> {
+    start_sequence(path, SequenceAction::Pick, commits, options, identity)
+}
+
+// Revert commits in the given order, one revert commit each
+pub fn revert(
+    path: &Path,
+    commits: &[String],
+    options: &SequenceOptions,
+    identity: &GitConfig,
+) -> Result<OperationResult, String> {
+    start_sequence(path, SequenceAction::Revert, commits, options, identity)
+}
+
+// The sequence is kept in `.git/sequencer` in the layout git uses, so it
+// survives a restart and `git cherry-pick --continue` works on it too.
+// The step being applied stays first in `todo` until it is committed.
+fn start_sequence(
+    path: &Path,
+    action: SequenceAction,
+    commits: &[String],
+    options: &SequenceOptions,
+    identity: &GitConfig,
+) -> Result<OperationResult, String> {
+    let repo = open_repo(path)?;
+
+    if commits.is_empty() {
+        return Err("No commits selected".to_string());
+    }
+    if repo.state() != RepositoryState::Clean || has_sequence(&repo) {
+        return Err("Another operation is in progress. Continue or abort it first.".to_string());
+    }
+    if has_local_changes(&repo)? {
+        return Err("Working tree has uncommitted changes. Commit or stash them first.".to_string());
+    }
+
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|_| "There is no commit to apply changes onto".to_string())?;
+
+    let mut steps = Vec::with_capacity(commits.len());
+    for revision in commits {
+        let commit = repo
+            .revparse_single(revision)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| format!("Invalid revision {}: {}", revision, e.message()))?;
+        steps.push(SequenceStep {
+            action,
+            commit: commit.id(),
+        });
+    }
+
+    let dir = sequencer_dir(&repo);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    fs::write(dir.join("head"), format!("{}\n", head.id())).map_err(|e| e.to_string())?;
+    let opts = match options.mainline {
+        Some(mainline) => format!("[options]\n\tmainline = {}\n", mainline),
+        None => String::new(),
+    };
+    fs::write(dir.join("opts"), opts).map_err(|e| e.to_string())?;
+    write_todo(&repo, &steps)?;
+
+    run_sequence(&repo, identity)
+}
+
+pub fn has_sequence(repo: &Repository) -> bool {
+    sequencer_dir(repo).join("todo").exists()
+}
+
+// Action of the step the sequence is at, if one is in progress
+pub fn pending_action(repo: &Repository) -> Option<SequenceAction> {
+    if !has_sequence(repo) {
+        return None;
+    }
+    read_todo(repo).ok()?.first().map(|step| step.action)
+}
+
+fn sequencer_dir(repo: &Repository) -> PathBuf {
+    repo.path().join("sequencer")
+}
+
+fn read_todo(repo: &Repository) -> Result<Vec<SequenceStep>, String> {
+    let content = fs::read_to_string(sequencer_dir(repo).join("todo")).map_err(|e| e.to_string())?;
+    let mut steps = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let action = match parts.next() {
+            Some("pick") | Some("p") => SequenceAction::Pick,
+            Some("revert") => SequenceAction::Revert,
+            _ => return Err(format!("Unsupported sequencer instruction: {}", line)),
+        };
+        let commit = parts
+            .next()
+            .and_then(|id| repo.revparse_single(id).ok())
+            .map(|object| object.id())
+            .ok_or_else(|| format!("Invalid sequencer instruction: {}", line))?;
+
+        steps.push(SequenceStep { action, commit });
+    }
+
+    Ok(steps)
+}
+
+fn write_todo(repo: &Repository, steps: &[SequenceStep]) -> Result<(), String> {
+    let mut content = String::new();
+    for step in steps {
+        let commit = repo.find_commit(step.commit).map_err(|e| e.to_string())?;
+        let action = match step.action {
+            SequenceAction::Pick => "pick",
+            SequenceAction::Revert => "revert",
+        };
+        content.push_str(&format!(
+            "{} {} {}\n",
+            action,
+            step.commit,
+            commit.summary().unwrap_or_default()
+        ));
+    }
+    fs::write(sequencer_dir(repo).join("todo"), content).map_err(|e| e.to_string())
+}
+
+fn read_mainline(repo: &Repository) -> Option<u32> {
+    let opts = fs::read_to_string(sequencer_dir(repo).join("opts")).ok()?;
+    opts.lines()
+        .filter_map(|line| line.trim().strip_prefix("mainline"))
+        .filter_map(|rest| rest.trim().strip_prefix('='))
+        .find_map(|value| value.trim().parse().ok())
+}
+
+// Apply the remaining steps, committing each one. Stops on conflicts with
+// the sequence still in progress.
+fn run_sequence(repo: &Repository, identity: &GitConfig) -> Result<OperationResult, String> {
+    let mainline = read_mainline(repo);
+
+    loop {
+        let mut steps = read_todo(repo)?;
+        if steps.is_empty() {
+            break;
+        }
+
+        let step = &steps[0];
+        let commit = repo.find_commit(step.commit).map_err(|e| e.to_string())?;
+
+        match step.action {
+            SequenceAction::Pick => {
+                let mut options = CherrypickOptions::new();
+                if let Some(mainline) = mainline {
+                    options.mainline(mainline);
+                }
+                repo.cherrypick(&commit, Some(&mut options))
+            }
+            SequenceAction::Revert => {
+                let mut options = RevertOptions::new();
+                if let Some(mainline) = mainline {
+                    options.mainline(mainline);
+                }
+                repo.revert(&commit, Some(&mut options))
+            }
+        }
+        .map_err(|e| format!("Failed to apply {}: {}", commit.id(), e.message()))?;
+
+        let index = repo.index().map_err(|e| e.to_string())?;
+        if index.has_conflicts() {
+            return Ok(OperationResult {
+                completed: false,
+                conflicts: conflicted_paths(&index),
+                commit: None,
+            });
+        }
+
+        commit_step(repo, step.action, &commit, None, identity)?;
+        steps.remove(0);
+        write_todo(repo, &steps)?;
+    }
+
+    fs::remove_dir_all(sequencer_dir(repo)).map_err(|e| e.to_string())?;
+
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+    Ok(OperationResult {
+        completed: true,
+        conflicts: Vec::new(),
+        commit: Some(commit_info(&head)),
+    })
+}
+
+// Commit an applied step. Picks keep their original author. Steps whose
+// changes are already in HEAD are skipped.
+fn commit_step(
+    repo: &Repository,
+    action: SequenceAction,
+    commit: &Commit<'_>,
+    message: Option<&str>,
+    identity: &GitConfig,
+) -> Result<(), String> {
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    let tree_id = index.write_tree().map_err(|e| e.to_string())?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+
+    if head.tree_id() == tree_id {
+        return clear_operation_files(repo);
+    }
+
+    let prepared = fs::read_to_string(repo.path().join("MERGE_MSG")).ok();
+    let message = message.map(str::to_string).or(prepared);
+    let options = CommitOptions::default();
+
+    match action {
+        SequenceAction::Pick => {
+            let message = message.unwrap_or_else(|| commit.message().unwrap_or_default().to_string());
+            create_commit_as(repo, &message, &options, identity, Some(&commit.author()))?;
+        }
+        SequenceAction::Revert => {
+            let message = message.unwrap_or_else(|| {
+                format!(
+                    "Revert \"{}\"\n\nThis reverts commit {}.\n",
+                    commit.summary().unwrap_or_default(),
+                    commit.id()
+                )
+            });
+            create_commit(repo, &message, &options, identity)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Commit the resolved step and apply the rest of the sequence
+pub fn continue_sequence(repo: &Repository, message: Option<&str>, identity: &GitConfig) -> Result<OperationResult, String> {
+    let mut steps = read_todo(repo)?;
+
+    if let Some(step) = steps.first() {
+        // The step was already committed if its *_HEAD file is gone
+        let pending = repo.path().join("CHERRY_PICK_HEAD").exists() || repo.path().join("REVERT_HEAD").exists();
+        if pending {
+            let commit = repo.find_commit(step.commit).map_err(|e| e.to_string())?;
+            commit_step(repo, step.action, &commit, message, identity)?;
+        }
+        steps.remove(0);
+        write_todo(repo, &steps)?;
+    }
+
+    run_sequence(repo, identity)
+}
+
+// Stop the sequence and return to the commit it started from
+pub fn abort_sequence(repo: &Repository) -> Result<(), String> {
+    let head = fs::read_to_string(sequencer_dir(repo).join("head")).map_err(|e| e.to_string())?;
+    let head = Oid::from_str(head.trim())
+        .and_then(|oid| repo.find_object(oid, None))
+        .map_err(|e| e.to_string())?;
+
+    repo.reset(&head, ResetType::Hard, None).map_err(|e| e.to_string())?;
+    repo.cleanup_state().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::state::{abort_operation, continue_operation, get_repository_state, OperationKind};
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    // `feature` has three commits on top of `base`, the second of which
+    // conflicts with the commit on `main`
+    fn diverged() -> tempfile::TempDir {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "f.txt", "base\n", "base");
+        git(path, &["checkout", "-q", "-b", "feature"]);
+        commit_file(path, "a.txt", "a\n", "one");
+        commit_file(path, "f.txt", "feature\n", "conflicting");
+        commit_file(path, "c.txt", "c\n", "three");
+        git(path, &["checkout", "-q", "main"]);
+        commit_file(path, "f.txt", "main\n", "main change");
+        dir
+    }
+
+    fn feature_commits() -> Vec<String> {
+        ["feature~2", "feature~1", "feature"].iter().map(|revision| revision.to_string()).collect()
+    }
+
+    #[test]
+    fn cherry_pick_sequence_stops_on_conflicts_and_continues() {
+        let dir = diverged();
+        let path = dir.path();
+        let identity = GitConfig::default();
+
+        let result = cherry_pick(path, &feature_commits(), &SequenceOptions::default(), &identity).unwrap();
+        assert!(!result.completed);
+        assert_eq!(result.conflicts, ["f.txt"]);
+        let state = get_repository_state(path).unwrap();
+        assert_eq!(state.operation, OperationKind::CherryPick);
+        assert_eq!(state.conflicts, ["f.txt"]);
+
+        fs::write(path.join("f.txt"), "resolved\n").unwrap();
+        git(path, &["add", "f.txt"]);
+        let result = continue_operation(path, None, &identity).unwrap();
+        assert!(result.completed);
+        assert_eq!(git(path, &["log", "--format=%s", "-4"]), "three\nconflicting\none\nmain change\n");
+        assert_eq!(get_repository_state(path).unwrap().operation, OperationKind::None);
+        assert!(!path.join(".git/sequencer").exists());
+    }
+
+    #[test]
+    fn revert_sequence_creates_one_commit_each() {
+        let dir = diverged();
+        let path = dir.path();
+        git(path, &["checkout", "-q", "feature"]);
+
+        let commits = ["HEAD".to_string(), "HEAD~2".to_string()];
+        let result = revert(path, &commits, &SequenceOptions::default(), &GitConfig::default()).unwrap();
+        assert!(result.completed);
+        assert_eq!(git(path, &["log", "--format=%s", "-2"]), "Revert \"one\"\nRevert \"three\"\n");
+        assert!(!path.join("a.txt").exists() && !path.join("c.txt").exists());
+    }
+
+    #[test]
+    fn abort_restores_head() {
+        let dir = diverged();
+        let path = dir.path();
+        let before = git(path, &["rev-parse", "HEAD"]);
+
+        let result = cherry_pick(path, &feature_commits(), &SequenceOptions::default(), &GitConfig::default()).unwrap();
+        assert!(!result.completed);
+        abort_operation(path).unwrap();
+
+        assert_eq!(git(path, &["rev-parse", "HEAD"]), before);
+        assert_eq!(git(path, &["status", "--porcelain"]), "");
+        assert_eq!(get_repository_state(path).unwrap().operation, OperationKind::None);
+    }
+}
@@ -0,0 +1,531 @@
+use git2::build::CheckoutBuilder;
+use git2::{Blob, Diff, DiffOptions, Index, IndexEntry, IndexTime, Patch, Repository};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Distinguishes the scratch directories of checkouts running at the same time
+static CHECKOUT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Lines of a file's diff that should be acted on
+pub enum Selection {
+    // Every change in the file
+    All,
+    // A single hunk, counted from zero in diff order
+    Hunk(usize),
+    // Changed lines whose position in the new side of the diff falls
+    // within `start..=end` (1-based); removed lines are positioned at the
+    // new-side line they precede
+    Lines { start: u32, end: u32 },
+}
+
+// Stage the whole file, including deletions
+pub fn stage_path(repo: &Repository, path: &Path) -> Result<(), String> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| "Repository has no working directory".to_string())?;
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+
+    if workdir.join(path).symlink_metadata().is_ok() {
+        index.add_path(path).map_err(|e| e.to_string())?;
+    } else {
+        index.remove_path(path).map_err(|e| e.to_string())?;
+    }
+
+    index.write().map_err(|e| e.to_string())
+}
+
+// Reset the index entry of a file back to HEAD
+pub fn unstage_path(repo: &Repository, path: &Path) -> Result<(), String> {
+    match repo.head().ok().and_then(|head| head.peel_to_commit().ok()) {
+        Some(commit) => repo
+            .reset_default(Some(commit.as_object()), [path])
+            .map_err(|e| e.to_string()),
+        None => {
+            // Nothing has been committed yet, so unstaging means removal
+            let mut index = repo.index().map_err(|e| e.to_string())?;
+            index.remove_path(path).map_err(|e| e.to_string())?;
+            index.write().map_err(|e| e.to_string())
+        }
+    }
+}
+
+// Stage the selected working tree changes of a file
+pub fn stage(repo: &Repository, path: &Path, selection: &Selection) -> Result<(), String> {
+    if let Selection::All = selection {
+        return stage_path(repo, path);
+    }
+
+    let index = repo.index().map_err(|e| e.to_string())?;
+    let diff = repo
+        .diff_index_to_workdir(Some(&index), Some(&mut file_diff_options(path)))
+        .map_err(|e| e.to_string())?;
+    let patch = single_patch(&diff, path)?;
+
+    if selects_everything(&patch, selection)? {
+        return stage_path(repo, path);
+    }
+
+    let base = index_content(repo, path)?;
+    let staged = apply_patch(&base, &patch, |line| line.is_selected(selection))?;
+    write_index_content(repo, path, &staged)
+}
+
+// Remove the selected changes of a file from the index
+pub fn unstage(repo: &Repository, path: &Path, selection: &Selection) -> Result<(), String> {
+    if let Selection::All = selection {
+        return unstage_path(repo, path);
+    }
+
+    let index = repo.index().map_err(|e| e.to_string())?;
+    let head_tree = match repo.head() {
+        Ok(head) => Some(head.peel_to_tree().map_err(|e| e.to_string())?),
+        Err(_) => None,
+    };
+    let diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), Some(&index), Some(&mut file_diff_options(path)))
+        .map_err(|e| e.to_string())?;
+    let patch = single_patch(&diff, path)?;
+
+    if selects_everything(&patch, selection)? {
+        return unstage_path(repo, path);
+    }
+
+    // Rebuild the index entry from HEAD with only the unselected changes
+    let base = match &head_tree {
+        Some(tree) => tree_content(repo, tree, path)?,
+        None => Vec::new(),
+    };
+    let staged = apply_patch(&base, &patch, |line| !line.is_selected(selection))?;
+    write_index_content(repo, path, &staged)
+}
+
+// Revert the selected working tree changes of a file to the indexed version
+pub fn discard(repo: &Repository, path: &Path, selection: &Selection) -> Result<(), String> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| "Repository has no working directory".to_string())?
+        .to_path_buf();
+    let index = repo.index().map_err(|e| e.to_string())?;
+    let diff = repo
+        .diff_index_to_workdir(Some(&index), Some(&mut file_diff_options(path)))
+        .map_err(|e| e.to_string())?;
+    let patch = single_patch(&diff, path)?;
+
+    if index.get_path(path, 0).is_none() && selects_everything(&patch, selection)? {
+        // Discarding every line of an untracked file removes it
+        return fs::remove_file(workdir.join(path)).map_err(|e| e.to_string());
+    }
+
+    // The diff compares content as git stores it, i.e. after filters such
+    // as `core.autocrlf`, so the result is checked out rather than written
+    // as is
+    let base = index_content(repo, path)?;
+    let reverted = apply_patch(&base, &patch, |line| !line.is_selected(selection))?;
+    let mode = match index.get_path(path, 0) {
+        Some(entry) => entry.mode,
+        None => workdir_mode(repo, path),
+    };
+    checkout_content(repo, path, &reverted, mode)
+}
+
+// Write `content` to the working tree file through the checkout filters,
+// leaving the index alone
+fn checkout_content(repo: &Repository, path: &Path, content: &[u8], mode: u32) -> Result<(), String> {
+    let mut entry = index_entry(path, mode);
+    entry.id = repo.blob(content).map_err(|e| e.to_string())?;
+    entry.file_size = content.len() as u32;
+
+    let mut index = Index::new().map_err(|e| e.to_string())?;
+    index.add(&entry).map_err(|e| e.to_string())?;
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force().update_index(false).path(path);
+    repo.checkout_index(Some(&mut index), Some(&mut checkout))
+        .map_err(|e| e.to_string())
+}
+
+// `blob` as a checkout of `path` would write it, i.e. through filters such
+// as `core.autocrlf` and the `eol` attribute. The checkout goes to a scratch
+// directory in the git directory; the working tree is left alone.
+pub fn checkout_form(repo: &Repository, path: &Path, blob: &Blob<'_>, mode: u32) -> Result<Vec<u8>, String> {
+    // Links are checked out as links, not as a file with their content
+    if mode == 0o120000 {
+        return Ok(blob.content().to_vec());
+    }
+
+    let mut entry = index_entry(path, mode);
+    entry.id = blob.id();
+    entry.file_size = blob.size() as u32;
+    let mut index = Index::new().map_err(|e| e.to_string())?;
+    index.add(&entry).map_err(|e| e.to_string())?;
+
+    let target = repo.path().join(format!(
+        "turkcode-checkout-{}-{}",
+        std::process::id(),
+        CHECKOUT_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force().update_index(false).target_dir(&target);
+    let content = repo
+        .checkout_index(Some(&mut index), Some(&mut checkout))
+        .map_err(|e| e.to_string())
+        .and_then(|_| fs::read(target.join(path)).map_err(|e| e.to_string()));
+    let _ = fs::remove_dir_all(&target);
+    content
+}
+
+// `content`, read from the working tree or an editor buffer, as the
+// repository stores `path`. libgit2 cannot run the filters in that
+// direction, so they are inferred from `blob`, the committed version: when
+// checking it out turns LF into CRLF, CRLF is turned back into LF.
+pub fn repository_form(repo: &Repository, path: &Path, content: &[u8], blob: &Blob<'_>, mode: u32) -> Result<Vec<u8>, String> {
+    let stored = blob.content();
+    let checked_out = checkout_form(repo, path, blob, mode)?;
+    if checked_out != stored && checked_out == lf_to_crlf(stored) {
+        return Ok(crlf_to_lf(content));
+    }
+    Ok(content.to_vec())
+}
+
+fn lf_to_crlf(content: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(content.len());
+    for (index, byte) in content.iter().enumerate() {
+        if *byte == b'\n' && (index == 0 || content[index - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(*byte);
+    }
+    converted
+}
+
+fn crlf_to_lf(content: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(content.len());
+    for (index, byte) in content.iter().enumerate() {
+        if *byte == b'\r' && content.get(index + 1) == Some(&b'\n') {
+            continue;
+        }
+        converted.push(*byte);
+    }
+    converted
+}
+
+fn file_diff_options(path: &Path) -> DiffOptions {
+    let mut options = DiffOptions::new();
+    options
+        .pathspec(path)
+        .disable_pathspec_match(true)
+        .include_untracked(true)
+        .show_untracked_content(true)
+        .recurse_untracked_dirs(true);
+    options
+}
+
+fn single_patch<'r>(diff: &Diff<'r>, path: &Path) -> Result<Patch<'r>, String> {
+    if diff.deltas().len() == 0 {
+        return Err(format!("No changes in {}", path.display()));
+    }
+
+    let delta = diff.get_delta(0).expect("delta exists");
+    if delta.old_file().is_binary() || delta.new_file().is_binary() {
+        return Err(format!("Cannot partially stage binary file {}", path.display()));
+    }
+
+    Patch::from_diff(diff, 0)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Cannot partially stage binary file {}", path.display()))
+}
+
+// A changed line of a patch with its position in the new side of the diff
+struct ChangedLine {
+    hunk: usize,
+    anchor: u32,
+}
+
+impl ChangedLine {
+    fn is_selected(&self, selection: &Selection) -> bool {
+        match selection {
+            Selection::All => true,
+            Selection::Hunk(index) => self.hunk == *index,
+            Selection::Lines { start, end } => self.anchor >= *start && self.anchor <= *end,
+        }
+    }
+}
+
+// Changed lines of a patch in diff order
+fn changed_lines(patch: &Patch<'_>) -> Result<Vec<ChangedLine>, String> {
+    let mut lines = Vec::new();
+
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_index).map_err(|e| e.to_string())?;
+        let mut anchor = hunk.new_start();
+
+        for line_index in 0..line_count {
+            let line = patch
+                .line_in_hunk(hunk_index, line_index)
+                .map_err(|e| e.to_string())?;
+            match line.origin() {
+                '+' => {
+                    lines.push(ChangedLine { hunk: hunk_index, anchor });
+                    anchor += 1;
+                }
+                '-' => lines.push(ChangedLine { hunk: hunk_index, anchor }),
+                ' ' => anchor += 1,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(lines)
+}
+
+fn selects_everything(patch: &Patch<'_>, selection: &Selection) -> Result<bool, String> {
+    let lines = changed_lines(patch)?;
+
+    if !lines.iter().any(|line| line.is_selected(selection)) {
+        return Err("Selection does not contain any changes".to_string());
+    }
+
+    Ok(lines.iter().all(|line| line.is_selected(selection)))
+}
+
+// Apply the changed lines of `patch` accepted by `select` to `base`, which
+// must be the content of the old side of the patch. Unaccepted additions are
+// dropped and unaccepted removals are kept.
+fn apply_patch<F>(base: &[u8], patch: &Patch<'_>, mut select: F) -> Result<Vec<u8>, String>
+where
+    F: FnMut(&ChangedLine) -> bool,
+{
+    let base_lines: Vec<&[u8]> = base.split_inclusive(|b| *b == b'\n').collect();
+    let mut output: Vec<u8> = Vec::with_capacity(base.len());
+    let mut old_cursor = 0usize;
+
+    // Only the last line of a file lacks a newline. When a selected line
+    // ends up after such a line, it is terminated the way the other lines
+    // of the file are, not with a bare `\n` that would mix line endings.
+    let push_line = |output: &mut Vec<u8>, line: &[u8]| {
+        if !output.is_empty() && !output.ends_with(b"\n") {
+            output.extend_from_slice(line_ending(&base_lines, line));
+        }
+        output.extend_from_slice(line);
+    };
+
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_index).map_err(|e| e.to_string())?;
+
+        // Copy the untouched lines before this hunk
+        let hunk_start = if hunk.old_lines() == 0 {
+            hunk.old_start() as usize
+        } else {
+            (hunk.old_start() as usize).saturating_sub(1)
+        };
+        while old_cursor < hunk_start && old_cursor < base_lines.len() {
+            push_line(&mut output, base_lines[old_cursor]);
+            old_cursor += 1;
+        }
+
+        let mut anchor = hunk.new_start();
+        for line_index in 0..line_count {
+            let line = patch
+                .line_in_hunk(hunk_index, line_index)
+                .map_err(|e| e.to_string())?;
+            let changed = ChangedLine { hunk: hunk_index, anchor };
+
+            match line.origin() {
+                ' ' => {
+                    if let Some(old) = base_lines.get(old_cursor) {
+                        push_line(&mut output, old);
+                    }
+                    old_cursor += 1;
+                    anchor += 1;
+                }
+                '+' => {
+                    if select(&changed) {
+                        push_line(&mut output, line.content());
+                    }
+                    anchor += 1;
+                }
+                '-' => {
+                    if !select(&changed) {
+                        if let Some(old) = base_lines.get(old_cursor) {
+                            push_line(&mut output, old);
+                        }
+                    }
+                    old_cursor += 1;
+                }
+                // End-of-file newline markers carry no content of their own
+                _ => {}
+            }
+        }
+    }
+
+    while old_cursor < base_lines.len() {
+        push_line(&mut output, base_lines[old_cursor]);
+        old_cursor += 1;
+    }
+
+    Ok(output)
+}
+
+// The line ending of `line`, or of the file when `line` has none
+fn line_ending(base_lines: &[&[u8]], line: &[u8]) -> &'static [u8] {
+    let crlf = match line.last() {
+        Some(b'\n') => line.ends_with(b"\r\n"),
+        _ => base_lines.first().is_some_and(|first| first.ends_with(b"\r\n")),
+    };
+    if crlf {
+        b"\r\n"
+    } else {
+        b"\n"
+    }
+}
+
+fn index_content(repo: &Repository, path: &Path) -> Result<Vec<u8>, String> {
+    let index = repo.index().map_err(|e| e.to_string())?;
+    match index.get_path(path, 0) {
+        Some(entry) => {
+            let blob = repo.find_blob(entry.id).map_err(|e| e.to_string())?;
+            Ok(blob.content().to_vec())
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+fn tree_content(repo: &Repository, tree: &git2::Tree<'_>, path: &Path) -> Result<Vec<u8>, String> {
+    match tree.get_path(path) {
+        Ok(entry) => {
+            let blob = repo.find_blob(entry.id()).map_err(|e| e.to_string())?;
+            Ok(blob.content().to_vec())
+        }
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+fn write_index_content(repo: &Repository, path: &Path, content: &[u8]) -> Result<(), String> {
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+
+    let mode = match index.get_path(path, 0) {
+        Some(entry) => entry.mode,
+        None => workdir_mode(repo, path),
+    };
+
+    index.add_frombuffer(&index_entry(path, mode), content).map_err(|e| e.to_string())?;
+    index.write().map_err(|e| e.to_string())
+}
+
+// Stat data is zeroed so the entry is never mistaken for the working tree file
+fn index_entry(path: &Path, mode: u32) -> IndexEntry {
+    IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode,
+        uid: 0,
+        gid: 0,
+        file_size: 0,
+        id: git2::Oid::zero(),
+        flags: 0,
+        flags_extended: 0,
+        path: path.to_string_lossy().replace('\\', "/").into_bytes(),
+    }
+}
+
+#[cfg(unix)]
+fn workdir_mode(repo: &Repository, path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    let executable = repo
+        .workdir()
+        .and_then(|workdir| workdir.join(path).metadata().ok())
+        .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false);
+
+    if executable { 0o100755 } else { 0o100644 }
+}
+
+#[cfg(not(unix))]
+fn workdir_mode(_repo: &Repository, _path: &Path) -> u32 {
+    0o100644
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    // Lines 1 to 20, one per line
+    fn numbered() -> String {
+        (1..=20).map(|n| format!("{}\n", n)).collect()
+    }
+
+    // A repository where line 2 and line 18 of `f.txt` were changed, giving
+    // two hunks
+    fn two_hunks() -> (tempfile::TempDir, Repository) {
+        let dir = init_repo();
+        commit_file(dir.path(), "f.txt", &numbered(), "initial");
+        let changed = numbered().replace("\n2\n", "\ntwo\n").replace("\n18\n", "\neighteen\n");
+        fs::write(dir.path().join("f.txt"), changed).unwrap();
+        let repo = Repository::open(dir.path()).unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn stage_and_unstage_single_hunks() {
+        let (dir, repo) = two_hunks();
+        let path = Path::new("f.txt");
+
+        stage(&repo, path, &Selection::Hunk(1)).unwrap();
+        let staged = git(dir.path(), &["show", ":f.txt"]);
+        assert!(staged.contains("eighteen") && staged.contains("\n2\n"));
+
+        stage(&repo, path, &Selection::Hunk(0)).unwrap();
+        unstage(&repo, path, &Selection::Hunk(1)).unwrap();
+        let staged = git(dir.path(), &["show", ":f.txt"]);
+        assert!(staged.contains("two") && staged.contains("\n18\n"));
+        assert!(fs::read_to_string(dir.path().join("f.txt")).unwrap().contains("eighteen"));
+    }
+
+    #[test]
+    fn stage_selected_lines_of_a_hunk() {
+        let dir = init_repo();
+        commit_file(dir.path(), "f.txt", "a\nb\n", "initial");
+        fs::write(dir.path().join("f.txt"), "a\nb\nc\nd\n").unwrap();
+        let repo = Repository::open(dir.path()).unwrap();
+
+        stage(&repo, Path::new("f.txt"), &Selection::Lines { start: 4, end: 4 }).unwrap();
+        assert_eq!(git(dir.path(), &["show", ":f.txt"]), "a\nb\nd\n");
+    }
+
+    #[test]
+    fn stage_lines_keeps_crlf_line_endings() {
+        let dir = init_repo();
+        commit_file(dir.path(), "f.txt", "a\r\nb", "initial");
+        fs::write(dir.path().join("f.txt"), "a\r\nb\r\nc").unwrap();
+        let repo = Repository::open(dir.path()).unwrap();
+
+        stage(&repo, Path::new("f.txt"), &Selection::Lines { start: 3, end: 3 }).unwrap();
+        assert_eq!(git(dir.path(), &["show", ":f.txt"]), "a\r\nb\r\nc");
+    }
+
+    #[test]
+    fn stage_lines_of_an_untracked_file() {
+        let dir = init_repo();
+        commit_file(dir.path(), "other.txt", "x\n", "initial");
+        fs::write(dir.path().join("new.txt"), "a\nb\n").unwrap();
+        let repo = Repository::open(dir.path()).unwrap();
+
+        stage(&repo, Path::new("new.txt"), &Selection::Lines { start: 1, end: 1 }).unwrap();
+        assert_eq!(git(dir.path(), &["show", ":new.txt"]), "a\n");
+    }
+
+    #[test]
+    fn discard_a_hunk_leaves_the_others() {
+        let (dir, repo) = two_hunks();
+
+        discard(&repo, Path::new("f.txt"), &Selection::Hunk(0)).unwrap();
+        let content = fs::read_to_string(dir.path().join("f.txt")).unwrap();
+        assert!(!content.contains("two") && content.contains("eighteen"));
+        assert_eq!(git(dir.path(), &["diff", "--cached"]), "");
+    }
+}
@@ -0,0 +1,268 @@
+use git2::{Repository, StashApplyOptions, StashFlags};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::config::GitConfig;
+use crate::git::commit::resolve_signature;
+use crate::git::diff::{collect_files, diff_options, DiffSettings, FileDiff};
+use crate::git::sync::conflicted_paths;
+use crate::git::{format_time, open_repo, run_git};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StashEntry {
+    // Position in the stash list, 0 being the most recent
+    pub index: usize,
+    pub message: String,
+    // Branch the stash was created on
+    pub branch: Option<String>,
+    pub hash: String,
+    pub date: String,
+    pub has_untracked: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StashPushOptions {
+    pub message: Option<String>,
+    pub include_untracked: bool,
+    // Leave staged changes in place after stashing
+    pub keep_index: bool,
+    // Only stash changes to these paths
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StashApplyResult {
+    // Files left with conflicts by applying the stash
+    pub conflicts: Vec<String>,
+}
+
+pub fn list_stashes(path: &Path) -> Result<Vec<StashEntry>, String> {
+    let mut repo = open_repo(path)?;
+
+    let mut stashes = Vec::new();
+    repo.stash_foreach(|index, message, oid| {
+        stashes.push((index, message.to_string(), *oid));
+        true
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::with_capacity(stashes.len());
+    for (index, message, oid) in stashes {
+        let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+        entries.push(StashEntry {
+            index,
+            branch: stash_branch(&message),
+            message,
+            hash: oid.to_string(),
+            date: format_time(commit.time()),
+            // The untracked files are stored as a third parent
+            has_untracked: commit.parent_count() > 2,
+        });
+    }
+
+    Ok(entries)
+}
+
+// Stash messages look like "WIP on main: abc123 subject" or "On main: message"
+fn stash_branch(message: &str) -> Option<String> {
+    let rest = message
+        .strip_prefix("WIP on ")
+        .or_else(|| message.strip_prefix("On "))?;
+    let (branch, _) = rest.split_once(':')?;
+    Some(branch.to_string())
+}
+
+// Stash local changes. Returns the new stash entry.
+pub fn push_stash(path: &Path, options: &StashPushOptions, identity: &GitConfig) -> Result<StashEntry, String> {
+    let mut repo = open_repo(path)?;
+    let stasher = resolve_signature(&repo, identity)?;
+
+    let mut flags = StashFlags::DEFAULT;
+    if options.include_untracked {
+        flags |= StashFlags::INCLUDE_UNTRACKED;
+    }
+    if options.keep_index {
+        flags |= StashFlags::KEEP_INDEX;
+    }
+
+    let message = options
+        .message
+        .as_deref()
+        .map(str::trim)
+        .filter(|message| !message.is_empty());
+
+    if options.paths.is_empty() {
+        match repo.stash_save2(&stasher, message, Some(flags)) {
+            Ok(_) => {}
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                return Err("No local changes to stash".to_string());
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    } else {
+        // libgit2 resets the whole working tree after a path-limited stash,
+        // losing changes outside the paths, so git does these instead
+        let before = repo.refname_to_id("refs/stash").ok();
+
+        let mut args = vec!["stash", "push"];
+        if options.include_untracked {
+            args.push("--include-untracked");
+        }
+        if options.keep_index {
+            args.push("--keep-index");
+        }
+        if let Some(message) = message {
+            args.extend(["--message", message]);
+        }
+        args.push("--");
+        args.extend(options.paths.iter().map(String::as_str));
+        run_git(&repo, &args)?;
+
+        if repo.refname_to_id("refs/stash").ok() == before {
+            return Err("No local changes to stash".to_string());
+        }
+    }
+
+    list_stashes(path)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Stash was not created".to_string())
+}
+
+// Apply a stash to the working tree. With `reinstate_index` staged changes
+// are staged again.
+pub fn apply_stash(path: &Path, index: usize, reinstate_index: bool) -> Result<StashApplyResult, String> {
+    let mut repo = open_repo(path)?;
+    apply(&mut repo, index, reinstate_index)
+}
+
+// Apply a stash and drop it, unless applying it caused conflicts
+pub fn pop_stash(path: &Path, index: usize, reinstate_index: bool) -> Result<StashApplyResult, String> {
+    let mut repo = open_repo(path)?;
+    let result = apply(&mut repo, index, reinstate_index)?;
+    if result.conflicts.is_empty() {
+        repo.stash_drop(index).map_err(|e| e.to_string())?;
+    }
+    Ok(result)
+}
+
+fn apply(repo: &mut Repository, index: usize, reinstate_index: bool) -> Result<StashApplyResult, String> {
+    let mut options = StashApplyOptions::new();
+    if reinstate_index {
+        options.reinstantiate_index();
+    }
+
+    repo.stash_apply(index, Some(&mut options)).map_err(|e| match e.code() {
+        git2::ErrorCode::NotFound => format!("Stash not found: {}", index),
+        git2::ErrorCode::Uncommitted => {
+            "Cannot apply a stash while changes are staged. Commit or unstage them first.".to_string()
+        }
+        git2::ErrorCode::Conflict => {
+            "Applying the stash would overwrite local changes. Commit or stash them first.".to_string()
+        }
+        _ => e.to_string(),
+    })?;
+
+    let conflicts = conflicted_paths(&repo.index().map_err(|e| e.to_string())?);
+    Ok(StashApplyResult { conflicts })
+}
+
+pub fn drop_stash(path: &Path, index: usize) -> Result<(), String> {
+    let mut repo = open_repo(path)?;
+    repo.stash_drop(index).map_err(|e| e.to_string())
+}
+
+// The changes stored in a stash, including its untracked files
+pub fn show_stash(path: &Path, index: usize, settings: &DiffSettings) -> Result<Vec<FileDiff>, String> {
+    let repo = open_repo(path)?;
+    let stash = repo
+        .revparse_single(&format!("stash@{{{}}}", index))
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| format!("Stash not found: {}", index))?;
+
+    let base = stash.parent(0).and_then(|parent| parent.tree()).map_err(|e| e.to_string())?;
+    let tree = stash.tree().map_err(|e| e.to_string())?;
+    let diff = repo
+        .diff_tree_to_tree(Some(&base), Some(&tree), Some(&mut diff_options(settings)))
+        .map_err(|e| e.to_string())?;
+    let mut files = collect_files(diff, settings)?;
+
+    if let Ok(untracked) = stash.parent(2) {
+        let untracked_tree = untracked.tree().map_err(|e| e.to_string())?;
+        let diff = repo
+            .diff_tree_to_tree(None, Some(&untracked_tree), Some(&mut diff_options(settings)))
+            .map_err(|e| e.to_string())?;
+        files.extend(collect_files(diff, settings)?);
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+    use std::fs;
+
+    #[test]
+    fn push_paths_and_untracked_files_then_pop() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        commit_file(path, "b.txt", "b\n", "second");
+        fs::write(path.join("a.txt"), "a2\n").unwrap();
+        fs::write(path.join("b.txt"), "b2\n").unwrap();
+        fs::write(path.join("new.txt"), "new\n").unwrap();
+
+        let options = StashPushOptions {
+            message: Some("only a".to_string()),
+            paths: vec!["a.txt".to_string()],
+            ..Default::default()
+        };
+        let entry = push_stash(path, &options, &GitConfig::default()).unwrap();
+        assert_eq!(entry.message, "On main: only a");
+        assert_eq!(entry.branch.as_deref(), Some("main"));
+        assert_eq!(fs::read_to_string(path.join("a.txt")).unwrap(), "a\n");
+        assert_eq!(fs::read_to_string(path.join("b.txt")).unwrap(), "b2\n");
+
+        let options = StashPushOptions { include_untracked: true, ..Default::default() };
+        let entry = push_stash(path, &options, &GitConfig::default()).unwrap();
+        assert!(entry.has_untracked);
+        assert!(!path.join("new.txt").exists());
+        assert_eq!(list_stashes(path).unwrap().len(), 2);
+
+        let result = pop_stash(path, 1, false).unwrap();
+        assert!(result.conflicts.is_empty());
+        assert_eq!(fs::read_to_string(path.join("a.txt")).unwrap(), "a2\n");
+        assert_eq!(list_stashes(path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn nothing_to_stash_is_an_error() {
+        let dir = init_repo();
+        commit_file(dir.path(), "a.txt", "a\n", "initial");
+        let error = push_stash(dir.path(), &StashPushOptions::default(), &GitConfig::default()).unwrap_err();
+        assert_eq!(error, "No local changes to stash");
+    }
+
+    #[test]
+    fn apply_errors_say_what_went_wrong() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        commit_file(path, "b.txt", "b\n", "second");
+        fs::write(path.join("a.txt"), "stashed\n").unwrap();
+        git(path, &["stash", "-q"]);
+
+        assert_eq!(apply_stash(path, 3, false).unwrap_err(), "Stash not found: 3");
+
+        fs::write(path.join("b.txt"), "staged\n").unwrap();
+        git(path, &["add", "b.txt"]);
+        assert!(apply_stash(path, 0, false).unwrap_err().contains("changes are staged"));
+
+        git(path, &["reset", "-q", "--hard"]);
+        fs::write(path.join("a.txt"), "local\n").unwrap();
+        assert!(apply_stash(path, 0, false).unwrap_err().contains("overwrite local changes"));
+        assert_eq!(list_stashes(path).unwrap().len(), 1);
+    }
+}
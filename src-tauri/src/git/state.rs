@@ -0,0 +1,266 @@
+use git2::{ErrorCode, Rebase, Repository, RepositoryState, Signature};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use crate::config::GitConfig;
+use crate::git::commit::{create_commit, create_commit_as, resolve_signature, CommitOptions};
+use crate::git::sync::conflicted_paths;
+use crate::git::rebase;
+use crate::git::sequencer::{self, SequenceAction};
+use crate::git::{commit_info, open_repo, CommitInfo};
+
+// Multi-step operation the repository is in the middle of
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OperationKind {
+    None,
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+    ApplyMailbox,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepositoryStateInfo {
+    pub operation: OperationKind,
+    // Files that still have conflicts in the index
+    pub conflicts: Vec<String>,
+    // Current branch, or `None` when HEAD is detached
+    pub head: Option<String>,
+    // Prepared message for the commit that continues the operation
+    pub message: Option<String>,
+    // 1-based step and step count of a rebase
+    pub step: Option<usize>,
+    pub total_steps: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationResult {
+    // Whether the operation finished; otherwise it stopped on new conflicts
+    pub completed: bool,
+    pub conflicts: Vec<String>,
+    pub commit: Option<CommitInfo>,
+}
+
+pub fn operation_kind(state: RepositoryState) -> OperationKind {
+    match state {
+        RepositoryState::Clean => OperationKind::None,
+        RepositoryState::Merge => OperationKind::Merge,
+        RepositoryState::Revert | RepositoryState::RevertSequence => OperationKind::Revert,
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => OperationKind::CherryPick,
+        RepositoryState::Bisect => OperationKind::Bisect,
+        RepositoryState::Rebase | RepositoryState::RebaseInteractive | RepositoryState::RebaseMerge => {
+            OperationKind::Rebase
+        }
+        RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => OperationKind::ApplyMailbox,
+    }
+}
+
+// The operation in progress, including a cherry-pick or revert sequence
+// whose current step was committed outside of `continue_operation`
+pub fn current_operation(repo: &Repository) -> OperationKind {
+    match operation_kind(repo.state()) {
+        OperationKind::None => match sequencer::pending_action(repo) {
+            Some(SequenceAction::Pick) => OperationKind::CherryPick,
+            Some(SequenceAction::Revert) => OperationKind::Revert,
+            None => OperationKind::None,
+        },
+        operation => operation,
+    }
+}
+
+pub fn get_repository_state(path: &Path) -> Result<RepositoryStateInfo, String> {
+    let repo = open_repo(path)?;
+    let operation = current_operation(&repo);
+    let index = repo.index().map_err(|e| e.to_string())?;
+
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head.shorthand().map(str::to_string),
+        _ => None,
+    };
+
+    let message = fs::read_to_string(repo.path().join("MERGE_MSG")).ok();
+
+    // Read the progress files directly; libgit2 cannot open rebases started
+    // by `git rebase -i`, which is also what plain `git rebase` uses now
+    let (step, total_steps) = if operation == OperationKind::Rebase {
+        let read_number = |name: &str| {
+            ["rebase-merge", "rebase-apply"].iter().find_map(|dir| {
+                fs::read_to_string(repo.path().join(dir).join(name))
+                    .ok()
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+            })
+        };
+        let step = read_number("msgnum").or_else(|| read_number("next"));
+        let total = read_number("end").or_else(|| read_number("last"));
+        (step, total)
+    } else {
+        (None, None)
+    };
+
+    Ok(RepositoryStateInfo {
+        operation,
+        conflicts: conflicted_paths(&index),
+        head,
+        message,
+        step,
+        total_steps,
+    })
+}
+
+// Abort the operation in progress and restore the state before it started
+pub fn abort_operation(path: &Path) -> Result<(), String> {
+    let repo = open_repo(path)?;
+
+    match current_operation(&repo) {
+        OperationKind::None => Err("No operation in progress".to_string()),
+        OperationKind::Rebase => match repo.open_rebase(None) {
+            Ok(mut rebase) => rebase.abort().map_err(|e| e.to_string()),
+            Err(_) => rebase::abort_rebase(&repo),
+        },
+        OperationKind::CherryPick | OperationKind::Revert if sequencer::has_sequence(&repo) => {
+            sequencer::abort_sequence(&repo)
+        }
+        OperationKind::Merge | OperationKind::CherryPick | OperationKind::Revert => {
+            // Only paths touched by the operation are reset, like `git merge --abort`
+            let head = repo
+                .head()
+                .and_then(|head| head.peel_to_tree())
+                .map_err(|e| e.to_string())?;
+            let index = repo.index().map_err(|e| e.to_string())?;
+            let mut paths = conflicted_paths(&index);
+
+            let diff = repo
+                .diff_tree_to_index(Some(&head), Some(&index), None)
+                .map_err(|e| e.to_string())?;
+            for delta in diff.deltas() {
+                for file in [delta.old_file(), delta.new_file()] {
+                    if let Some(path) = file.path() {
+                        let path = path.to_string_lossy().to_string();
+                        if !paths.contains(&path) {
+                            paths.push(path);
+                        }
+                    }
+                }
+            }
+
+            if !paths.is_empty() {
+                let mut checkout = git2::build::CheckoutBuilder::new();
+                checkout.force().remove_untracked(false);
+                for path in &paths {
+                    checkout.path(path);
+                }
+                repo.checkout_head(Some(&mut checkout)).map_err(|e| e.to_string())?;
+            }
+
+            repo.cleanup_state().map_err(|e| e.to_string())
+        }
+        other => Err(format!("Cannot abort {:?} from TurkCode", other)),
+    }
+}
+
+// Commit the resolved state and carry on with the operation in progress.
+// `message` overrides the prepared commit message.
+pub fn continue_operation(path: &Path, message: Option<&str>, identity: &GitConfig) -> Result<OperationResult, String> {
+    let repo = open_repo(path)?;
+    let index = repo.index().map_err(|e| e.to_string())?;
+
+    if index.has_conflicts() {
+        return Err("Resolve all conflicts before continuing".to_string());
+    }
+
+    let explicit = message.map(str::to_string);
+    let prepared = fs::read_to_string(repo.path().join("MERGE_MSG")).ok();
+    let message = explicit.clone().or(prepared);
+    let options = CommitOptions {
+        // Resolving everything in favour of HEAD leaves nothing to commit
+        allow_empty: true,
+        ..CommitOptions::default()
+    };
+
+    match current_operation(&repo) {
+        OperationKind::None => Err("No operation in progress".to_string()),
+        OperationKind::CherryPick | OperationKind::Revert if sequencer::has_sequence(&repo) => {
+            sequencer::continue_sequence(&repo, explicit.as_deref(), identity)
+        }
+        OperationKind::Merge | OperationKind::Revert => {
+            let message = message.ok_or_else(|| "A commit message is required".to_string())?;
+            let result = create_commit(&repo, &message, &options, identity)?;
+            Ok(OperationResult {
+                completed: true,
+                conflicts: Vec::new(),
+                commit: Some(result.commit),
+            })
+        }
+        OperationKind::CherryPick => {
+            let picked = fs::read_to_string(repo.path().join("CHERRY_PICK_HEAD"))
+                .map_err(|_| "Cherry-pick state is missing CHERRY_PICK_HEAD".to_string())?;
+            let picked = git2::Oid::from_str(picked.trim())
+                .and_then(|oid| repo.find_commit(oid))
+                .map_err(|e| e.to_string())?;
+
+            let message = message.unwrap_or_else(|| picked.message().unwrap_or_default().to_string());
+            let result = create_commit_as(&repo, &message, &options, identity, Some(&picked.author()))?;
+            Ok(OperationResult {
+                completed: true,
+                conflicts: Vec::new(),
+                commit: Some(result.commit),
+            })
+        }
+        OperationKind::Rebase => {
+            let signature = resolve_signature(&repo, identity)?;
+            let mut rebase = match repo.open_rebase(None) {
+                Ok(rebase) => rebase,
+                Err(_) => return rebase::continue_rebase(&repo, explicit.as_deref(), identity),
+            };
+
+            if rebase.operation_current().is_some() {
+                match rebase.commit(None, &signature, explicit.as_deref()) {
+                    Ok(_) => {}
+                    Err(e) if e.code() == ErrorCode::Applied => {}
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+
+            drive_rebase(&repo, &mut rebase, &signature)
+        }
+        other => Err(format!("Cannot continue {:?} from TurkCode", other)),
+    }
+}
+
+// Apply the remaining rebase steps. Stops with the rebase still in progress
+// when a step conflicts; otherwise finishes it.
+pub fn drive_rebase(repo: &Repository, rebase: &mut Rebase<'_>, signature: &Signature<'_>) -> Result<OperationResult, String> {
+    while let Some(operation) = rebase.next() {
+        operation.map_err(|e| e.to_string())?;
+
+        let index = repo.index().map_err(|e| e.to_string())?;
+        if index.has_conflicts() {
+            return Ok(OperationResult {
+                completed: false,
+                conflicts: conflicted_paths(&index),
+                commit: None,
+            });
+        }
+
+        match rebase.commit(None, signature, None) {
+            Ok(_) => {}
+            // The change is already upstream, nothing to commit
+            Err(e) if e.code() == ErrorCode::Applied => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+
+    rebase.finish(Some(signature)).map_err(|e| e.to_string())?;
+
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+    Ok(OperationResult {
+        completed: true,
+        conflicts: Vec::new(),
+        commit: Some(commit_info(&head)),
+    })
+}
@@ -0,0 +1,702 @@
+use git2::{
+    AnnotatedCommit, AutotagOption, BranchType, Cred, CredentialType, ErrorCode, FetchOptions,
+    Oid, PushOptions, RemoteCallbacks, Repository,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::config::GitConfig;
+use crate::git::branch::has_local_changes;
+use crate::git::commit::resolve_signature;
+use crate::git::state::drive_rebase;
+use crate::git::{commit_info, open_repo, CommitInfo};
+
+// Event emitted to the frontend while talking to a remote
+pub const PROGRESS_EVENT: &str = "git-progress";
+
+// Minimum time between two progress reports of the same operation
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// Authentication attempts before giving up on a remote
+const MAX_CREDENTIAL_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitProgress {
+    // "fetch", "push" or "clone"
+    pub operation: String,
+    // "receiving", "resolving", "packing", "sending", "remote" or "checkout"
+    pub phase: String,
+    pub current: usize,
+    pub total: usize,
+    pub bytes: usize,
+    // Text printed by the remote, e.g. "Counting objects..."
+    pub message: Option<String>,
+}
+
+// Supplies credentials for authenticated remotes. The IDE can provide its
+// own implementation, e.g. one that prompts the user.
+pub trait CredentialProvider: Send + Sync {
+    fn credentials(
+        &self,
+        url: &str,
+        username: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error>;
+}
+
+// Uses the SSH agent and the git credential helpers configured for the repository
+pub struct DefaultCredentials {
+    repo_path: PathBuf,
+}
+
+impl DefaultCredentials {
+    pub fn new(repo: &Repository) -> Self {
+        DefaultCredentials {
+            repo_path: repo.path().to_path_buf(),
+        }
+    }
+
+    // Only the global git configuration, e.g. before a clone has created
+    // the repository
+    pub fn global() -> Self {
+        DefaultCredentials {
+            repo_path: PathBuf::new(),
+        }
+    }
+}
+
+impl CredentialProvider for DefaultCredentials {
+    fn credentials(
+        &self,
+        url: &str,
+        username: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        if allowed.contains(CredentialType::SSH_KEY) {
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            let config = Repository::open(&self.repo_path)
+                .and_then(|repo| repo.config())
+                .or_else(|_| git2::Config::open_default())?;
+            return Cred::credential_helper(&config, url, username);
+        }
+
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username.unwrap_or("git"));
+        }
+
+        Cred::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PullStrategy {
+    Merge,
+    Rebase,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PullOptions {
+    pub strategy: PullStrategy,
+    // Refuse to pull when a merge or rebase would be needed
+    pub fast_forward_only: bool,
+}
+
+impl Default for PullOptions {
+    fn default() -> Self {
+        PullOptions {
+            strategy: PullStrategy::Merge,
+            fast_forward_only: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PullOutcome {
+    UpToDate,
+    FastForward,
+    Merged,
+    Rebased,
+    // The merge or rebase stopped; resolve the files and continue
+    Conflicts,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullResult {
+    pub outcome: PullOutcome,
+    pub commit: Option<CommitInfo>,
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PushSettings {
+    // Overwrite the remote branch, but only if it still points where our
+    // remote-tracking branch says it does
+    pub force_with_lease: bool,
+    pub tags: bool,
+    pub set_upstream: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RejectedRef {
+    pub refname: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushResult {
+    pub pushed: Vec<String>,
+    pub rejected: Vec<RejectedRef>,
+}
+
+// Rate-limits progress reports so the frontend is not flooded
+pub struct ProgressReporter<'a> {
+    operation: &'a str,
+    sink: &'a dyn Fn(GitProgress),
+    last: Cell<Option<Instant>>,
+    cancelled: Option<&'a AtomicBool>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub fn new(operation: &'a str, sink: &'a dyn Fn(GitProgress)) -> Self {
+        ProgressReporter {
+            operation,
+            sink,
+            last: Cell::new(None),
+            cancelled: None,
+        }
+    }
+
+    // Abort the transfer once `cancelled` is set
+    pub fn with_cancel(mut self, cancelled: &'a AtomicBool) -> Self {
+        self.cancelled = Some(cancelled);
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.is_some_and(|cancelled| cancelled.load(Ordering::SeqCst))
+    }
+
+    pub fn report(&self, phase: &str, current: usize, total: usize, bytes: usize, message: Option<String>) {
+        let finished = total > 0 && current >= total;
+        let due = self
+            .last
+            .get()
+            .map_or(true, |last| last.elapsed() >= PROGRESS_INTERVAL);
+
+        if finished || due || message.is_some() {
+            self.last.set(Some(Instant::now()));
+            (self.sink)(GitProgress {
+                operation: self.operation.to_string(),
+                phase: phase.to_string(),
+                current,
+                total,
+                bytes,
+                message,
+            });
+        }
+    }
+}
+
+// Callbacks shared by fetch, push and clone: progress and credentials
+pub fn remote_callbacks<'a>(
+    reporter: &'a ProgressReporter<'a>,
+    credentials: &'a dyn CredentialProvider,
+) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    let attempts = Cell::new(0);
+
+    callbacks.credentials(move |url, username, allowed| {
+        attempts.set(attempts.get() + 1);
+        if attempts.get() > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str(&format!("Authentication failed for {}", url)));
+        }
+        credentials.credentials(url, username, allowed)
+    });
+
+    callbacks.transfer_progress(move |stats| {
+        if stats.received_objects() < stats.total_objects() {
+            reporter.report(
+                "receiving",
+                stats.received_objects(),
+                stats.total_objects(),
+                stats.received_bytes(),
+                None,
+            );
+        } else {
+            reporter.report(
+                "resolving",
+                stats.indexed_deltas(),
+                stats.total_deltas(),
+                stats.received_bytes(),
+                None,
+            );
+        }
+        !reporter.is_cancelled()
+    });
+
+    callbacks.sideband_progress(move |data| {
+        let text = String::from_utf8_lossy(data).trim().to_string();
+        if !text.is_empty() {
+            reporter.report("remote", 0, 0, 0, Some(text));
+        }
+        !reporter.is_cancelled()
+    });
+
+    callbacks.pack_progress(move |_stage, current, total| {
+        reporter.report("packing", current, total, 0, None);
+    });
+
+    callbacks.push_transfer_progress(move |current, total, bytes| {
+        reporter.report("sending", current, total, bytes, None);
+    });
+
+    callbacks
+}
+
+// Fetch from a remote, returning the names of the refs that changed
+pub fn fetch(
+    repo: &Repository,
+    remote_name: &str,
+    progress: &dyn Fn(GitProgress),
+    credentials: &dyn CredentialProvider,
+) -> Result<Vec<String>, String> {
+    let mut remote = repo
+        .find_remote(remote_name)
+        .map_err(|_| format!("Remote not found: {}", remote_name))?;
+
+    let reporter = ProgressReporter::new("fetch", progress);
+    let updated = RefCell::new(Vec::new());
+
+    let mut callbacks = remote_callbacks(&reporter, credentials);
+    callbacks.update_tips(|refname, _old, _new| {
+        updated.borrow_mut().push(refname.to_string());
+        true
+    });
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks).download_tags(AutotagOption::Auto);
+
+    remote
+        .fetch::<&str>(&[], Some(&mut options), None)
+        .map_err(|e| format!("Fetch from {} failed: {}", remote_name, e.message()))?;
+
+    drop(options);
+    Ok(updated.into_inner())
+}
+
+// Fetch `branch` from a remote and integrate it into the current branch
+pub fn pull(
+    repo: &Repository,
+    remote_name: &str,
+    branch: &str,
+    options: &PullOptions,
+    identity: &GitConfig,
+    progress: &dyn Fn(GitProgress),
+    credentials: &dyn CredentialProvider,
+) -> Result<PullResult, String> {
+    fetch(repo, remote_name, progress, credentials)?;
+
+    let upstream_ref = repo
+        .find_branch(&format!("{}/{}", remote_name, branch), BranchType::Remote)
+        .map_err(|_| format!("Remote branch not found: {}/{}", remote_name, branch))?;
+    let upstream = repo
+        .reference_to_annotated_commit(upstream_ref.get())
+        .map_err(|e| e.to_string())?;
+
+    let (analysis, _) = repo.merge_analysis(&[&upstream]).map_err(|e| e.to_string())?;
+
+    if analysis.is_up_to_date() {
+        return Ok(PullResult {
+            outcome: PullOutcome::UpToDate,
+            commit: None,
+            conflicts: Vec::new(),
+        });
+    }
+
+    if analysis.is_fast_forward() || analysis.is_unborn() {
+        return fast_forward(repo, &upstream);
+    }
+
+    if options.fast_forward_only {
+        return Err(format!(
+            "Cannot fast-forward to {}/{}; the branches have diverged",
+            remote_name, branch
+        ));
+    }
+
+    let signature = resolve_signature(repo, identity)?;
+
+    match options.strategy {
+        PullStrategy::Merge => {
+            if has_local_changes(repo)? {
+                return Err("Cannot merge with uncommitted changes. Commit or stash them first.".to_string());
+            }
+
+            repo.merge(&[&upstream], None, None).map_err(|e| e.to_string())?;
+
+            let mut index = repo.index().map_err(|e| e.to_string())?;
+            if index.has_conflicts() {
+                return Ok(PullResult {
+                    outcome: PullOutcome::Conflicts,
+                    commit: None,
+                    conflicts: conflicted_paths(&index),
+                });
+            }
+
+            let tree_id = index.write_tree().map_err(|e| e.to_string())?;
+            let tree = repo.find_tree(tree_id).map_err(|e| e.to_string())?;
+            let head = repo
+                .head()
+                .and_then(|head| head.peel_to_commit())
+                .map_err(|e| e.to_string())?;
+            let theirs = repo.find_commit(upstream.id()).map_err(|e| e.to_string())?;
+            let url = repo
+                .find_remote(remote_name)
+                .ok()
+                .and_then(|remote| remote.url().map(str::to_string))
+                .unwrap_or_else(|| remote_name.to_string());
+            let message = format!("Merge branch '{}' of {}", branch, url);
+
+            let oid = repo
+                .commit(Some("HEAD"), &signature, &signature, &message, &tree, &[&head, &theirs])
+                .map_err(|e| e.to_string())?;
+            repo.cleanup_state().map_err(|e| e.to_string())?;
+
+            let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+            Ok(PullResult {
+                outcome: PullOutcome::Merged,
+                commit: Some(commit_info(&commit)),
+                conflicts: Vec::new(),
+            })
+        }
+        PullStrategy::Rebase => {
+            if has_local_changes(repo)? {
+                return Err("Cannot rebase with uncommitted changes. Commit or stash them first.".to_string());
+            }
+
+            let mut rebase = repo
+                .rebase(None, Some(&upstream), None, None)
+                .map_err(|e| e.to_string())?;
+
+            // The rebase stays in progress until continued or aborted
+            let result = drive_rebase(repo, &mut rebase, &signature)?;
+            Ok(PullResult {
+                outcome: if result.completed { PullOutcome::Rebased } else { PullOutcome::Conflicts },
+                commit: result.commit,
+                conflicts: result.conflicts,
+            })
+        }
+    }
+}
+
+fn fast_forward(repo: &Repository, target: &AnnotatedCommit<'_>) -> Result<PullResult, String> {
+    let commit = repo.find_commit(target.id()).map_err(|e| e.to_string())?;
+
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.safe();
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout))
+        .map_err(|e| e.to_string())?;
+
+    // HEAD may point at a branch that does not exist yet
+    let head_ref = repo.find_reference("HEAD").map_err(|e| e.to_string())?;
+    let branch_ref = head_ref
+        .symbolic_target()
+        .map(str::to_string)
+        .ok_or_else(|| "Cannot pull into a detached HEAD".to_string())?;
+    repo.reference(&branch_ref, commit.id(), true, "pull: fast-forward")
+        .map_err(|e| e.to_string())?;
+
+    Ok(PullResult {
+        outcome: PullOutcome::FastForward,
+        commit: Some(commit_info(&commit)),
+        conflicts: Vec::new(),
+    })
+}
+
+pub fn conflicted_paths(index: &git2::Index) -> Vec<String> {
+    let mut paths = Vec::new();
+    if let Ok(conflicts) = index.conflicts() {
+        for conflict in conflicts.flatten() {
+            let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+            if let Some(entry) = entry {
+                paths.push(String::from_utf8_lossy(&entry.path).to_string());
+            }
+        }
+    }
+    paths
+}
+
+// Push a local branch to the branch of the same name on a remote
+pub fn push(
+    repo: &Repository,
+    remote_name: &str,
+    branch: &str,
+    settings: &PushSettings,
+    progress: &dyn Fn(GitProgress),
+    credentials: &dyn CredentialProvider,
+) -> Result<PushResult, String> {
+    let mut remote = repo
+        .find_remote(remote_name)
+        .map_err(|_| format!("Remote not found: {}", remote_name))?;
+    let refname = format!("refs/heads/{}", branch);
+
+    if repo.find_reference(&refname).is_err() {
+        return Err(format!("Branch not found: {}", branch));
+    }
+
+    let reporter = ProgressReporter::new("push", progress);
+
+    // Where our remote-tracking branch says the remote branch is; a branch
+    // the remote does not have is advertised as the zero oid
+    let lease = settings.force_with_lease.then(|| {
+        repo.find_reference(&format!("refs/remotes/{}/{}", remote_name, branch))
+            .ok()
+            .and_then(|reference| reference.target())
+            .unwrap_or_else(Oid::zero)
+    });
+
+    let mut refspecs = vec![if settings.force_with_lease {
+        format!("+{}:{}", refname, refname)
+    } else {
+        format!("{}:{}", refname, refname)
+    }];
+
+    if settings.tags {
+        for tag in repo.tag_names(None).map_err(|e| e.to_string())?.iter().flatten() {
+            refspecs.push(format!("refs/tags/{}:refs/tags/{}", tag, tag));
+        }
+    }
+
+    let statuses = RefCell::new(Vec::new());
+    let stale = Cell::new(false);
+    let mut callbacks = remote_callbacks(&reporter, credentials);
+    if let Some(expected) = lease {
+        // Checked against the refs the remote advertised for this very push.
+        // The update is sent with that old value, so the remote also refuses
+        // it if the branch moves before the update lands.
+        let (target, stale) = (refname.as_str(), &stale);
+        callbacks.push_negotiation(move |updates| {
+            let moved = updates
+                .iter()
+                .any(|update| update.dst_refname() == Some(target) && update.src() != expected);
+            if moved {
+                stale.set(true);
+                return Err(git2::Error::from_str("stale info"));
+            }
+            Ok(())
+        });
+    }
+    callbacks.push_update_reference(|refname, status| {
+        statuses
+            .borrow_mut()
+            .push((refname.to_string(), status.map(str::to_string)));
+        Ok(())
+    });
+
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+
+    let pushed = remote.push(&refspecs, Some(&mut options));
+    drop(options);
+
+    let mut result = PushResult {
+        pushed: Vec::new(),
+        rejected: Vec::new(),
+    };
+
+    match pushed {
+        Ok(()) => {}
+        Err(_) if stale.get() => {
+            result.rejected.push(RejectedRef {
+                refname,
+                reason: "stale info: the remote branch changed since the last fetch".to_string(),
+            });
+            return Ok(result);
+        }
+        // libgit2 refuses these itself instead of reporting them per ref
+        Err(e) if e.code() == ErrorCode::NotFastForward => {
+            result.rejected.push(RejectedRef {
+                refname,
+                reason: "non-fast-forward: pull the remote changes first".to_string(),
+            });
+            return Ok(result);
+        }
+        Err(e) => return Err(format!("Push to {} failed: {}", remote_name, e.message())),
+    }
+    for (refname, status) in statuses.into_inner() {
+        match status {
+            None => result.pushed.push(refname),
+            Some(reason) => result.rejected.push(RejectedRef { refname, reason }),
+        }
+    }
+
+    if settings.set_upstream && result.rejected.is_empty() {
+        let mut local = repo
+            .find_branch(branch, BranchType::Local)
+            .map_err(|e| e.to_string())?;
+        local
+            .set_upstream(Some(&format!("{}/{}", remote_name, branch)))
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(result)
+}
+
+// Open a repository and build the default credential provider for it
+pub fn open_for_transfer(path: &Path) -> Result<(Repository, DefaultCredentials), String> {
+    let repo = open_repo(path)?;
+    let credentials = DefaultCredentials::new(&repo);
+    Ok((repo, credentials))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    // A bare repository reachable through a file:// URL
+    fn bare_remote() -> (TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q", "--bare", "-b", "main"]);
+        let url = format!("file://{}", dir.path().display());
+        (dir, url)
+    }
+
+    // A second working copy of `url`, on main
+    fn clone_of(url: &str) -> TempDir {
+        let dir = init_repo();
+        git(dir.path(), &["remote", "add", "origin", url]);
+        git(dir.path(), &["fetch", "-q", "origin"]);
+        git(dir.path(), &["reset", "-q", "--hard", "origin/main"]);
+        dir
+    }
+
+    fn push_main(path: &Path, settings: &PushSettings) -> PushResult {
+        let repo = Repository::open(path).unwrap();
+        push(&repo, "origin", "main", settings, &|_| {}, &DefaultCredentials::new(&repo)).unwrap()
+    }
+
+    fn pull_main(path: &Path, options: &PullOptions) -> Result<PullResult, String> {
+        let repo = Repository::open(path).unwrap();
+        let identity = GitConfig::default();
+        pull(&repo, "origin", "main", options, &identity, &|_| {}, &DefaultCredentials::new(&repo))
+    }
+
+    // Two working copies of one remote, both at "initial"
+    fn setup() -> (TempDir, TempDir, TempDir) {
+        let (remote, url) = bare_remote();
+        let first = init_repo();
+        commit_file(first.path(), "a.txt", "a\n", "initial");
+        git(first.path(), &["remote", "add", "origin", &url]);
+
+        let settings = PushSettings {
+            set_upstream: true,
+            ..Default::default()
+        };
+        let result = push_main(first.path(), &settings);
+        assert_eq!(result.pushed, vec!["refs/heads/main".to_string()]);
+        assert!(result.rejected.is_empty());
+        assert_eq!(git(first.path(), &["rev-parse", "--abbrev-ref", "main@{upstream}"]), "origin/main\n");
+
+        let second = clone_of(&url);
+        (remote, first, second)
+    }
+
+    #[test]
+    fn fetch_then_fast_forward_pull() {
+        let (_remote, first, second) = setup();
+        commit_file(first.path(), "a.txt", "a2\n", "second");
+        push_main(first.path(), &PushSettings::default());
+
+        let repo = Repository::open(second.path()).unwrap();
+        let updated = fetch(&repo, "origin", &|_| {}, &DefaultCredentials::new(&repo)).unwrap();
+        assert_eq!(updated, vec!["refs/remotes/origin/main".to_string()]);
+
+        let result = pull_main(second.path(), &PullOptions::default()).unwrap();
+        assert_eq!(result.outcome, PullOutcome::FastForward);
+        assert_eq!(result.commit.unwrap().message.trim(), "second");
+        assert_eq!(fs::read_to_string(second.path().join("a.txt")).unwrap(), "a2\n");
+
+        let result = pull_main(second.path(), &PullOptions::default()).unwrap();
+        assert_eq!(result.outcome, PullOutcome::UpToDate);
+    }
+
+    #[test]
+    fn diverged_pull_merges_unless_fast_forward_only() {
+        let (_remote, first, second) = setup();
+        commit_file(first.path(), "a.txt", "a2\n", "theirs");
+        push_main(first.path(), &PushSettings::default());
+        commit_file(second.path(), "b.txt", "b\n", "ours");
+
+        let options = PullOptions {
+            fast_forward_only: true,
+            ..Default::default()
+        };
+        assert!(pull_main(second.path(), &options).unwrap_err().contains("diverged"));
+
+        // The merge would mix the pulled changes into the local ones
+        fs::write(second.path().join("b.txt"), "local\n").unwrap();
+        assert!(pull_main(second.path(), &PullOptions::default())
+            .unwrap_err()
+            .contains("uncommitted changes"));
+        git(second.path(), &["checkout", "-q", "b.txt"]);
+
+        let result = pull_main(second.path(), &PullOptions::default()).unwrap();
+        assert_eq!(result.outcome, PullOutcome::Merged);
+        assert_eq!(git(second.path(), &["rev-list", "--parents", "-n", "1", "HEAD"]).split(' ').count(), 3);
+        assert_eq!(fs::read_to_string(second.path().join("a.txt")).unwrap(), "a2\n");
+        assert_eq!(git(second.path(), &["status", "--porcelain"]), "");
+    }
+
+    #[test]
+    fn non_fast_forward_push_is_rejected() {
+        let (_remote, first, second) = setup();
+        commit_file(first.path(), "a.txt", "a2\n", "theirs");
+        push_main(first.path(), &PushSettings::default());
+        commit_file(second.path(), "b.txt", "b\n", "ours");
+
+        let result = push_main(second.path(), &PushSettings::default());
+        assert!(result.pushed.is_empty());
+        assert_eq!(result.rejected.len(), 1);
+        assert!(result.rejected[0].reason.starts_with("non-fast-forward"));
+    }
+
+    #[test]
+    fn force_with_lease_refuses_a_stale_remote_branch() {
+        let (remote, first, second) = setup();
+        git(second.path(), &["fetch", "-q", "origin"]);
+        commit_file(first.path(), "a.txt", "a2\n", "theirs");
+        push_main(first.path(), &PushSettings::default());
+        commit_file(second.path(), "b.txt", "b\n", "ours");
+
+        let settings = PushSettings {
+            force_with_lease: true,
+            ..Default::default()
+        };
+        let result = push_main(second.path(), &settings);
+        assert!(result.pushed.is_empty());
+        assert!(result.rejected[0].reason.starts_with("stale info"));
+        assert_eq!(
+            git(remote.path(), &["rev-parse", "main"]),
+            git(first.path(), &["rev-parse", "HEAD"])
+        );
+
+        // Once the remote branch has been seen, it may be overwritten
+        git(second.path(), &["fetch", "-q", "origin"]);
+        let result = push_main(second.path(), &settings);
+        assert_eq!(result.pushed, vec!["refs/heads/main".to_string()]);
+        assert_eq!(
+            git(remote.path(), &["rev-parse", "main"]),
+            git(second.path(), &["rev-parse", "HEAD"])
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use git2::ObjectType;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::config::GitConfig;
+use crate::git::commit::resolve_signature;
+use crate::git::{format_time, open_repo};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub name: String,
+    // Commit (or other object) the tag points at
+    pub target: String,
+    pub annotated: bool,
+    // Tagger, date and message of annotated tags
+    pub tagger: Option<String>,
+    pub date: Option<String>,
+    pub message: Option<String>,
+}
+
+pub fn list_tags(path: &Path) -> Result<Vec<TagInfo>, String> {
+    let repo = open_repo(path)?;
+    let mut tags = Vec::new();
+
+    let mut references = Vec::new();
+    repo.tag_foreach(|oid, name| {
+        let name = String::from_utf8_lossy(name);
+        references.push((oid, name.trim_start_matches("refs/tags/").to_string()));
+        true
+    })
+    .map_err(|e| e.to_string())?;
+
+    for (oid, name) in references {
+        let tag = match repo.find_tag(oid) {
+            Ok(tag) => {
+                let target = tag
+                    .target()
+                    .and_then(|object| object.peel(ObjectType::Commit))
+                    .map(|object| object.id())
+                    .unwrap_or_else(|_| tag.target_id());
+                let tagger = tag.tagger();
+                TagInfo {
+                    name,
+                    target: target.to_string(),
+                    annotated: true,
+                    tagger: tagger.as_ref().map(|signature| {
+                        format!(
+                            "{} <{}>",
+                            signature.name().unwrap_or_default(),
+                            signature.email().unwrap_or_default()
+                        )
+                    }),
+                    date: tagger.as_ref().map(|signature| format_time(signature.when())),
+                    message: tag.message().map(str::to_string),
+                }
+            }
+            // Lightweight tags point straight at the commit
+            Err(_) => TagInfo {
+                name,
+                target: oid.to_string(),
+                annotated: false,
+                tagger: None,
+                date: None,
+                message: None,
+            },
+        };
+        tags.push(tag);
+    }
+
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tags)
+}
+
+// Create a tag at `target` (HEAD by default). A message makes it an
+// annotated tag, otherwise it is lightweight.
+pub fn create_tag(
+    path: &Path,
+    name: &str,
+    target: Option<&str>,
+    message: Option<&str>,
+    force: bool,
+    identity: &GitConfig,
+) -> Result<TagInfo, String> {
+    let repo = open_repo(path)?;
+
+    if !git2::Reference::is_valid_name(&format!("refs/tags/{}", name)) {
+        return Err(format!("Invalid tag name: {}", name));
+    }
+    if !force && repo.find_reference(&format!("refs/tags/{}", name)).is_ok() {
+        return Err(format!("Tag {} already exists", name));
+    }
+
+    let revision = target.unwrap_or("HEAD");
+    let object = repo
+        .revparse_single(revision)
+        .map_err(|e| format!("Invalid revision {}: {}", revision, e.message()))?;
+
+    let message = message.filter(|message| !message.trim().is_empty());
+    match message {
+        Some(message) => {
+            let tagger = resolve_signature(&repo, identity)?;
+            let message = git2::message_prettify(message, Some(b'#')).map_err(|e| e.to_string())?;
+            repo.tag(name, &object, &tagger, &message, force)
+        }
+        None => repo.tag_lightweight(name, &object, force),
+    }
+    .map_err(|e| e.to_string())?;
+
+    list_tags(path)?
+        .into_iter()
+        .find(|tag| tag.name == name)
+        .ok_or_else(|| format!("Tag {} was not created", name))
+}
+
+pub fn delete_tag(path: &Path, name: &str) -> Result<(), String> {
+    let repo = open_repo(path)?;
+    repo.tag_delete(name).map_err(|e| match e.code() {
+        git2::ErrorCode::NotFound => format!("Tag not found: {}", name),
+        _ => e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+
+    #[test]
+    fn lightweight_and_annotated_tags_are_listed_and_deleted() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "first");
+        let first = git(path, &["rev-parse", "HEAD"]).trim().to_string();
+        commit_file(path, "a.txt", "b\n", "second");
+        let head = git(path, &["rev-parse", "HEAD"]).trim().to_string();
+
+        let light = create_tag(path, "v1", Some(&first), None, false, &GitConfig::default()).unwrap();
+        assert!(!light.annotated);
+        assert_eq!(light.target, first);
+        assert_eq!(light.tagger, None);
+
+        // A blank message still makes a lightweight tag
+        let blank = create_tag(path, "v1.1", None, Some("  "), false, &GitConfig::default()).unwrap();
+        assert!(!blank.annotated);
+
+        let annotated = create_tag(path, "v2", None, Some("Release 2\n\n\n"), false, &GitConfig::default()).unwrap();
+        assert!(annotated.annotated);
+        // The target is the commit, not the tag object
+        assert_eq!(annotated.target, head);
+        assert_eq!(annotated.tagger.as_deref(), Some("Test <test@example.com>"));
+        assert_eq!(annotated.message.as_deref(), Some("Release 2\n"));
+        assert_eq!(git(path, &["cat-file", "-t", "v2"]).trim(), "tag");
+
+        let names: Vec<String> = list_tags(path).unwrap().into_iter().map(|tag| tag.name).collect();
+        assert_eq!(names, ["v1", "v1.1", "v2"]);
+
+        delete_tag(path, "v1").unwrap();
+        delete_tag(path, "v2").unwrap();
+        assert_eq!(delete_tag(path, "v2").unwrap_err(), "Tag not found: v2");
+        let names: Vec<String> = list_tags(path).unwrap().into_iter().map(|tag| tag.name).collect();
+        assert_eq!(names, ["v1.1"]);
+    }
+
+    #[test]
+    fn existing_tags_are_only_replaced_with_force() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "first");
+        let first = git(path, &["rev-parse", "HEAD"]).trim().to_string();
+        commit_file(path, "a.txt", "b\n", "second");
+
+        create_tag(path, "v1", Some(&first), None, false, &GitConfig::default()).unwrap();
+        assert_eq!(
+            create_tag(path, "v1", None, None, false, &GitConfig::default()).unwrap_err(),
+            "Tag v1 already exists"
+        );
+        assert!(create_tag(path, "bad..name", None, None, false, &GitConfig::default())
+            .unwrap_err()
+            .starts_with("Invalid tag name"));
+
+        let moved = create_tag(path, "v1", None, None, true, &GitConfig::default()).unwrap();
+        assert_eq!(moved.target, git(path, &["rev-parse", "HEAD"]).trim());
+    }
+}
@@ -0,0 +1,361 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{timeout, Duration, Instant};
+use crate::fs::watcher::{FileWatcher, WatcherEvent};
+use crate::git::commit::common_dir;
+use crate::git::{get_repo_status, open_repo, StatusEntry};
+
+// Event emitted to the frontend when the status of a watched repository changes
+pub const STATUS_CHANGED_EVENT: &str = "git-status-changed";
+
+// Quiet period after the last file change before the status is recomputed,
+// so a build or checkout touching many files yields a single event
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// Longest the status is held back while changes keep coming, e.g. during
+// a long build
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+// Files in the git directory whose changes can affect the status
+const GIT_DIR_FILES: [&str; 7] = [
+    "index",
+    "HEAD",
+    "refs",
+    "packed-refs",
+    "MERGE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REVERT_HEAD",
+];
+
+// Files in the common git directory that linked worktrees share
+const COMMON_DIR_FILES: [&str; 2] = ["refs", "packed-refs"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusChange {
+    // Working tree of the repository
+    pub repository: String,
+    // Current branch, or None when HEAD is detached or unborn
+    pub head: Option<String>,
+    // Entries that are new or differ from the previous event
+    pub changed: Vec<StatusEntry>,
+    // Entries that no longer apply, e.g. files that were committed
+    pub removed: Vec<StatusEntry>,
+}
+
+struct WatchedRepository {
+    git_dir: PathBuf,
+    // Same as `git_dir` except for linked worktrees
+    common_dir: PathBuf,
+    // File watches of the working tree and the git directory
+    watch_ids: Vec<u64>,
+    head: Option<String>,
+    entries: Vec<StatusEntry>,
+}
+
+// Recomputes the status of watched repositories when the file watcher
+// reports changes in their working tree or git directory
+pub struct GitStatusService {
+    repositories: Arc<Mutex<HashMap<PathBuf, WatchedRepository>>>,
+    watcher: FileWatcher,
+}
+
+impl GitStatusService {
+    pub fn new(app: AppHandle, watcher: FileWatcher) -> Self {
+        let repositories = Arc::new(Mutex::new(HashMap::new()));
+
+        let events = watcher.subscribe();
+        tauri::async_runtime::spawn(process_events(app, events, repositories.clone()));
+
+        GitStatusService { repositories, watcher }
+    }
+
+    // Start watching the repository containing `path`, returning its
+    // current status
+    pub fn watch(&self, path: &Path) -> Result<Vec<StatusEntry>, String> {
+        let repo = open_repo(path)?;
+        let workdir = repo
+            .workdir()
+            .ok_or("Cannot watch the status of a bare repository")?
+            .to_path_buf();
+        // Resolved, so they compare equal to the paths of reported changes
+        let git_dir = fs::canonicalize(repo.path()).map_err(|e| e.to_string())?;
+        let common_dir = fs::canonicalize(common_dir(&repo)).map_err(|e| e.to_string())?;
+
+        if let Some(watched) = self.repositories.lock().unwrap().get(&workdir) {
+            return Ok(watched.entries.clone());
+        }
+
+        let entries = get_repo_status(&workdir, false)?;
+        let head = current_head(&workdir);
+
+        // The git directory of a linked worktree lies inside the common one
+        let workdir_watch = self.watcher.watch_internal(&workdir)?;
+        let git_dir_watch = match self.watcher.watch_internal(&common_dir) {
+            Ok(watch) => watch,
+            Err(e) => {
+                let _ = self.watcher.unwatch_id(workdir_watch.id);
+                return Err(e);
+            }
+        };
+
+        self.repositories.lock().unwrap().insert(
+            workdir,
+            WatchedRepository {
+                git_dir,
+                common_dir,
+                watch_ids: vec![workdir_watch.id, git_dir_watch.id],
+                head,
+                entries: entries.clone(),
+            },
+        );
+
+        Ok(entries)
+    }
+
+    pub fn unwatch(&self, path: &Path) -> Result<(), String> {
+        let repo = open_repo(path)?;
+        let workdir = match repo.workdir() {
+            Some(workdir) => workdir.to_path_buf(),
+            None => return Ok(()),
+        };
+
+        if let Some(watched) = self.repositories.lock().unwrap().remove(&workdir) {
+            for id in watched.watch_ids {
+                self.watcher.unwatch_id(id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn process_events(
+    app: AppHandle,
+    mut events: UnboundedReceiver<WatcherEvent>,
+    repositories: Arc<Mutex<HashMap<PathBuf, WatchedRepository>>>,
+) {
+    while let Some(event) = events.recv().await {
+        let mut changed_paths = vec![event.path().to_path_buf()];
+
+        // Wait for the changes to settle
+        let deadline = Instant::now() + MAX_DELAY;
+        loop {
+            let wait = DEBOUNCE.min(deadline.saturating_duration_since(Instant::now()));
+            match timeout(wait, events.recv()).await {
+                Ok(Some(event)) => changed_paths.push(event.path().to_path_buf()),
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+
+        let affected: HashSet<PathBuf> = {
+            let repositories = repositories.lock().unwrap();
+            repositories
+                .iter()
+                .filter(|(workdir, watched)| {
+                    changed_paths
+                        .iter()
+                        .any(|path| affects_repository(workdir, watched, path))
+                })
+                .map(|(workdir, _)| workdir.clone())
+                .collect()
+        };
+
+        for workdir in affected {
+            let status_path = workdir.clone();
+            let refreshed = tauri::async_runtime::spawn_blocking(move || {
+                let entries = get_repo_status(&status_path, false)?;
+                Ok::<_, String>((entries, current_head(&status_path)))
+            })
+            .await;
+
+            let (entries, head) = match refreshed {
+                Ok(Ok(refreshed)) => refreshed,
+                Ok(Err(e)) => {
+                    log::warn!("Failed to refresh git status of {}: {}", workdir.display(), e);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+
+            let change = {
+                let mut repositories = repositories.lock().unwrap();
+                // Unwatched while the status was computed
+                let watched = match repositories.get_mut(&workdir) {
+                    Some(watched) => watched,
+                    None => continue,
+                };
+                let change = status_change(&workdir, watched, &entries, &head);
+                watched.entries = entries;
+                watched.head = head;
+                change
+            };
+
+            if let Some(change) = change {
+                let _ = app.emit(STATUS_CHANGED_EVENT, change);
+            }
+        }
+    }
+}
+
+// Whether a changed path can affect the status of a watched repository.
+// A ref change in the common git directory can affect several worktrees.
+fn affects_repository(workdir: &Path, watched: &WatchedRepository, path: &Path) -> bool {
+    let git_dirs = [(&watched.git_dir, &GIT_DIR_FILES[..]), (&watched.common_dir, &COMMON_DIR_FILES[..])];
+    match git_dirs
+        .iter()
+        .find_map(|(dir, files)| Some((path.strip_prefix(dir).ok()?, files)))
+    {
+        Some((relative, files)) => relative
+            .components()
+            .next()
+            .is_some_and(|first| files.contains(&first.as_os_str().to_string_lossy().as_ref())),
+        None => path.starts_with(workdir),
+    }
+}
+
+fn current_head(workdir: &Path) -> Option<String> {
+    let repo = open_repo(workdir).ok()?;
+    let head = repo.head().ok()?;
+    if head.is_branch() {
+        head.shorthand().map(str::to_string)
+    } else {
+        None
+    }
+}
+
+// Compare a fresh status with the last one sent. Entries are identified by
+// path and whether they are staged.
+fn status_change(
+    workdir: &Path,
+    previous: &WatchedRepository,
+    entries: &[StatusEntry],
+    head: &Option<String>,
+) -> Option<StatusChange> {
+    let key = |entry: &StatusEntry| (entry.path.clone(), entry.staged);
+    let old: HashMap<_, _> = previous.entries.iter().map(|entry| (key(entry), entry)).collect();
+    let new: HashSet<_> = entries.iter().map(key).collect();
+
+    let changed: Vec<StatusEntry> = entries
+        .iter()
+        .filter(|entry| old.get(&key(entry)) != Some(entry))
+        .cloned()
+        .collect();
+    let removed: Vec<StatusEntry> = previous
+        .entries
+        .iter()
+        .filter(|entry| !new.contains(&key(entry)))
+        .cloned()
+        .collect();
+
+    if changed.is_empty() && removed.is_empty() && previous.head == *head {
+        return None;
+    }
+
+    Some(StatusChange {
+        repository: workdir.to_string_lossy().to_string(),
+        head: head.clone(),
+        changed,
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::FileStatus;
+
+    fn entry(path: &str, status: FileStatus, staged: bool) -> StatusEntry {
+        StatusEntry {
+            path: path.to_string(),
+            old_path: None,
+            status,
+            staged,
+            submodule: None,
+        }
+    }
+
+    fn watched(git_dir: &str, common_dir: &str, entries: Vec<StatusEntry>) -> WatchedRepository {
+        WatchedRepository {
+            git_dir: PathBuf::from(git_dir),
+            common_dir: PathBuf::from(common_dir),
+            watch_ids: Vec::new(),
+            head: Some("main".to_string()),
+            entries,
+        }
+    }
+
+    #[test]
+    fn status_changes_are_keyed_on_path_and_stage() {
+        let previous = watched(
+            "/repo/.git",
+            "/repo/.git",
+            vec![
+                entry("a.txt", FileStatus::Modified, false),
+                entry("a.txt", FileStatus::Added, true),
+                entry("b.txt", FileStatus::Modified, false),
+            ],
+        );
+        let entries = vec![
+            entry("a.txt", FileStatus::Modified, false),
+            entry("a.txt", FileStatus::Modified, true),
+            entry("c.txt", FileStatus::Untracked, false),
+        ];
+        let head = Some("main".to_string());
+
+        let change = status_change(Path::new("/repo"), &previous, &entries, &head).unwrap();
+        assert_eq!(change.repository, "/repo");
+        assert_eq!(
+            change.changed,
+            [entry("a.txt", FileStatus::Modified, true), entry("c.txt", FileStatus::Untracked, false)]
+        );
+        assert_eq!(change.removed, [entry("b.txt", FileStatus::Modified, false)]);
+
+        assert!(status_change(Path::new("/repo"), &previous, &previous.entries, &head).is_none());
+    }
+
+    #[test]
+    fn head_changes_alone_are_reported() {
+        let previous = watched("/repo/.git", "/repo/.git", vec![entry("a.txt", FileStatus::Modified, false)]);
+
+        let change = status_change(Path::new("/repo"), &previous, &previous.entries, &None).unwrap();
+        assert_eq!(change.head, None);
+        assert!(change.changed.is_empty());
+        assert!(change.removed.is_empty());
+    }
+
+    #[test]
+    fn only_status_files_in_the_git_directory_count() {
+        let repo = watched("/repo/.git", "/repo/.git", Vec::new());
+        let affects = |path: &str| affects_repository(Path::new("/repo"), &repo, Path::new(path));
+
+        assert!(affects("/repo/.git/index"));
+        assert!(affects("/repo/.git/HEAD"));
+        assert!(affects("/repo/.git/refs/heads/main"));
+        assert!(affects("/repo/src/main.rs"));
+        assert!(!affects("/repo/.git/objects/ab/cdef"));
+        assert!(!affects("/repo/.git/logs/HEAD"));
+        assert!(!affects("/elsewhere/file"));
+    }
+
+    #[test]
+    fn linked_worktrees_share_only_refs() {
+        let worktree = watched("/repo/.git/worktrees/wt", "/repo/.git", Vec::new());
+        let affects = |path: &str| affects_repository(Path::new("/wt"), &worktree, Path::new(path));
+
+        assert!(affects("/wt/file.txt"));
+        assert!(affects("/repo/.git/worktrees/wt/index"));
+        assert!(affects("/repo/.git/worktrees/wt/HEAD"));
+        assert!(affects("/repo/.git/refs/heads/main"));
+        assert!(affects("/repo/.git/packed-refs"));
+        // The main worktree's own state and other worktrees
+        assert!(!affects("/repo/.git/index"));
+        assert!(!affects("/repo/.git/HEAD"));
+        assert!(!affects("/repo/.git/worktrees/other/index"));
+        assert!(!affects("/repo/src/main.rs"));
+    }
+}
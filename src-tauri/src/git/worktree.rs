@@ -0,0 +1,284 @@
+use git2::{BranchType, Repository, StatusOptions, Worktree, WorktreeAddOptions, WorktreeLockStatus, WorktreePruneOptions};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::git::open_repo;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorktreeInfo {
+    // None for the main working tree
+    pub name: Option<String>,
+    pub path: String,
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub is_main: bool,
+    // Lock reason, or an empty string for a lock without one
+    pub locked: Option<String>,
+    // The working tree is gone and its administrative files can be pruned
+    pub prunable: bool,
+}
+
+// The repository owning all worktrees, even when `path` is inside a linked one
+fn main_repo(path: &Path) -> Result<Repository, String> {
+    let repo = open_repo(path)?;
+    if !repo.is_worktree() {
+        return Ok(repo);
+    }
+
+    // `.git/worktrees/<name>/commondir` holds the path of the shared git directory
+    let commondir = fs::read_to_string(repo.path().join("commondir")).map_err(|e| e.to_string())?;
+    Repository::open(repo.path().join(commondir.trim())).map_err(|e| e.to_string())
+}
+
+fn head_info(repo: &Repository) -> (Option<String>, Option<String>) {
+    match repo.head() {
+        Ok(head) => {
+            let branch = if head.is_branch() {
+                head.shorthand().map(str::to_string)
+            } else {
+                None
+            };
+            (branch, head.target().map(|oid| oid.to_string()))
+        }
+        Err(_) => (None, None),
+    }
+}
+
+fn worktree_info(worktree: &Worktree) -> WorktreeInfo {
+    let (branch, head) = match Repository::open_from_worktree(worktree) {
+        Ok(repo) => head_info(&repo),
+        Err(_) => (None, None),
+    };
+    let locked = match worktree.is_locked() {
+        Ok(WorktreeLockStatus::Locked(reason)) => Some(lock_reason(reason)),
+        _ => None,
+    };
+
+    WorktreeInfo {
+        name: worktree.name().map(str::to_string),
+        path: worktree.path().to_string_lossy().to_string(),
+        branch,
+        head,
+        is_main: false,
+        locked,
+        prunable: worktree.validate().is_err(),
+    }
+}
+
+// `git worktree lock` stores the reason with a trailing newline
+fn lock_reason(reason: Option<String>) -> String {
+    reason.map(|reason| reason.trim_end().to_string()).unwrap_or_default()
+}
+
+// The main working tree followed by all linked ones
+pub fn list_worktrees(path: &Path) -> Result<Vec<WorktreeInfo>, String> {
+    let repo = main_repo(path)?;
+    let mut worktrees = Vec::new();
+
+    if let Some(workdir) = repo.workdir() {
+        let (branch, head) = head_info(&repo);
+        worktrees.push(WorktreeInfo {
+            name: None,
+            path: workdir.to_string_lossy().to_string(),
+            branch,
+            head,
+            is_main: true,
+            locked: None,
+            prunable: false,
+        });
+    }
+
+    let names = repo.worktrees().map_err(|e| e.to_string())?;
+    for name in names.iter().flatten() {
+        let worktree = repo.find_worktree(name).map_err(|e| e.to_string())?;
+        worktrees.push(worktree_info(&worktree));
+    }
+
+    Ok(worktrees)
+}
+
+// Check out `branch` in a new working tree at `worktree_path`. A branch that
+// does not exist yet is created at HEAD; without one, a branch named after
+// the worktree directory is created.
+pub fn add_worktree(path: &Path, worktree_path: &Path, branch: Option<&str>) -> Result<WorktreeInfo, String> {
+    let repo = main_repo(path)?;
+
+    if worktree_path.exists() {
+        return Err(format!("{} already exists", worktree_path.display()));
+    }
+    let name = worktree_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid worktree path: {}", worktree_path.display()))?;
+
+    let branch = branch.filter(|branch| !branch.trim().is_empty());
+    let reference = match branch {
+        Some(branch) => {
+            let local = match repo.find_branch(branch, BranchType::Local) {
+                Ok(local) => local,
+                Err(_) => {
+                    let head = repo
+                        .head()
+                        .and_then(|head| head.peel_to_commit())
+                        .map_err(|e| e.to_string())?;
+                    repo.branch(branch, &head, false)
+                        .map_err(|e| format!("Cannot create branch {}: {}", branch, e.message()))?
+                }
+            };
+            Some(local.into_reference())
+        }
+        None => None,
+    };
+
+    let mut options = WorktreeAddOptions::new();
+    options.reference(reference.as_ref());
+
+    let worktree = repo
+        .worktree(&name, worktree_path, Some(&options))
+        .map_err(|e| e.to_string())?;
+    Ok(worktree_info(&worktree))
+}
+
+// The working directory of the linked worktree `name`
+pub fn worktree_path(path: &Path, name: &str) -> Result<PathBuf, String> {
+    let repo = main_repo(path)?;
+    let worktree = repo
+        .find_worktree(name)
+        .map_err(|_| format!("Worktree not found: {}", name))?;
+    Ok(worktree.path().to_path_buf())
+}
+
+// Delete a linked worktree and its working directory. Worktrees with local
+// changes or a lock are only removed with `force`.
+pub fn remove_worktree(path: &Path, name: &str, force: bool) -> Result<(), String> {
+    let repo = main_repo(path)?;
+    let worktree = repo
+        .find_worktree(name)
+        .map_err(|_| format!("Worktree not found: {}", name))?;
+
+    if !force {
+        if let Ok(WorktreeLockStatus::Locked(reason)) = worktree.is_locked() {
+            let reason = lock_reason(reason);
+            return Err(if reason.is_empty() {
+                format!("Worktree {} is locked", name)
+            } else {
+                format!("Worktree {} is locked: {}", name, reason)
+            });
+        }
+
+        if worktree.validate().is_ok() {
+            let worktree_repo = Repository::open_from_worktree(&worktree).map_err(|e| e.to_string())?;
+            let mut options = StatusOptions::new();
+            options.include_untracked(true).include_ignored(false);
+            let statuses = worktree_repo.statuses(Some(&mut options)).map_err(|e| e.to_string())?;
+            if !statuses.is_empty() {
+                return Err(format!(
+                    "Worktree {} has uncommitted changes. Commit or stash them first.",
+                    name
+                ));
+            }
+        }
+    }
+
+    let mut options = WorktreePruneOptions::new();
+    options.valid(true).locked(force).working_tree(true);
+    worktree.prune(Some(&mut options)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{commit_file, git, init_repo};
+    use crate::utils::find_project_root;
+
+    #[test]
+    fn add_list_and_remove_worktrees() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        let parent = tempfile::tempdir().unwrap();
+
+        let feature = parent.path().join("feature");
+        let info = add_worktree(path, &feature, Some("feature")).unwrap();
+        assert_eq!(info.name.as_deref(), Some("feature"));
+        assert_eq!(info.branch.as_deref(), Some("feature"));
+        assert!(feature.join("a.txt").is_file());
+        assert!(add_worktree(path, &feature, None).unwrap_err().ends_with("already exists"));
+
+        // Without a branch, one named after the directory is created
+        let other = parent.path().join("other");
+        assert_eq!(add_worktree(path, &other, None).unwrap().branch.as_deref(), Some("other"));
+
+        // Listed the same from the main working tree and a linked one
+        let worktrees = list_worktrees(&feature).unwrap();
+        assert_eq!(worktrees.len(), 3);
+        assert!(worktrees[0].is_main);
+        assert_eq!(worktrees[0].branch.as_deref(), Some("main"));
+        assert_eq!(
+            Path::new(&worktrees[0].path).canonicalize().unwrap(),
+            path.canonicalize().unwrap()
+        );
+        assert!(worktrees[1..].iter().all(|worktree| !worktree.is_main && !worktree.prunable));
+        assert_eq!(list_worktrees(path).unwrap().len(), 3);
+        assert_eq!(
+            worktree_path(path, "feature").unwrap().canonicalize().unwrap(),
+            feature.canonicalize().unwrap()
+        );
+
+        remove_worktree(path, "other", false).unwrap();
+        assert!(!other.exists());
+        assert_eq!(list_worktrees(path).unwrap().len(), 2);
+        assert_eq!(remove_worktree(path, "other", false).unwrap_err(), "Worktree not found: other");
+    }
+
+    #[test]
+    fn dirty_or_locked_worktrees_are_only_removed_with_force() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        let parent = tempfile::tempdir().unwrap();
+        let feature = parent.path().join("feature");
+        add_worktree(path, &feature, None).unwrap();
+
+        fs::write(feature.join("a.txt"), "changed\n").unwrap();
+        assert!(remove_worktree(path, "feature", false).unwrap_err().contains("uncommitted changes"));
+        assert!(feature.join("a.txt").is_file());
+
+        git(path, &["worktree", "lock", "--reason", "in use", &feature.to_string_lossy()]);
+        assert_eq!(list_worktrees(path).unwrap()[1].locked.as_deref(), Some("in use"));
+        assert_eq!(
+            remove_worktree(path, "feature", false).unwrap_err(),
+            "Worktree feature is locked: in use"
+        );
+
+        git(path, &["worktree", "unlock", &feature.to_string_lossy()]);
+        git(path, &["worktree", "lock", &feature.to_string_lossy()]);
+        assert_eq!(list_worktrees(path).unwrap()[1].locked.as_deref(), Some(""));
+        assert_eq!(remove_worktree(path, "feature", false).unwrap_err(), "Worktree feature is locked");
+
+        remove_worktree(path, "feature", true).unwrap();
+        assert!(!feature.exists());
+        assert_eq!(list_worktrees(path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_linked_worktree_is_a_project_root() {
+        let dir = init_repo();
+        let path = dir.path();
+        commit_file(path, "a.txt", "a\n", "initial");
+        let parent = tempfile::tempdir().unwrap();
+        let feature = parent.path().join("feature");
+        add_worktree(path, &feature, None).unwrap();
+        fs::create_dir(feature.join("src")).unwrap();
+
+        assert!(feature.join(".git").is_file());
+        assert_eq!(find_project_root(&feature.join("src")), Some(feature.clone()));
+
+        // A `.git` file that points nowhere is not a marker, so the search
+        // falls back to where it started
+        let stray = parent.path().join("stray");
+        fs::create_dir_all(stray.join("src")).unwrap();
+        fs::write(stray.join(".git"), "gitdir: missing\n").unwrap();
+        assert_eq!(find_project_root(&stray.join("src")), Some(stray.join("src")));
+    }
+}
@@ -7,7 +7,9 @@ pub mod git;
 pub mod fs;
 pub mod ai;
 pub mod config;
+pub mod search;
 pub mod utils;
+pub mod workspace;
 
 // Initialize logging
 fn setup_logging() {
@@ -21,7 +23,39 @@ fn register_commands(app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
         // File system operations
         fs::commands::read_file,
         fs::commands::write_file,
+        fs::commands::read_text_file,
+        fs::commands::write_text_file,
+        fs::commands::save_file,
+        fs::commands::open_file,
+        fs::commands::read_file_lines,
+        fs::commands::read_file_hex,
         fs::commands::list_files,
+        fs::commands::list_files_page,
+        fs::commands::create_file,
+        fs::commands::create_directory,
+        fs::commands::rename_path,
+        fs::commands::move_path,
+        fs::commands::copy_path,
+        fs::commands::delete_path,
+        fs::commands::undo_file_operation,
+        fs::commands::get_file_operation_history,
+        fs::commands::watch_path,
+        fs::commands::unwatch_path,
+        
+        // Search operations
+        search::commands::index_workspace,
+        search::commands::remove_workspace_index,
+        search::commands::quick_open,
+        search::commands::search_in_files,
+        search::commands::cancel_search,
+        search::commands::replace_in_files,
+        
+        // Workspace trust operations
+        workspace::commands::pick_workspace,
+        workspace::commands::open_workspace,
+        workspace::commands::approve_path,
+        workspace::commands::revoke_trust,
+        workspace::commands::get_workspace_trust,
         
         // Editor operations
         editor::commands::get_syntax_highlighting,
@@ -35,13 +69,66 @@ fn register_commands(app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
         git::commands::get_status,
         git::commands::get_all_branches,
         git::commands::get_recent_commits,
+        git::commands::get_commit_log,
+        git::commands::get_workdir_diff,
+        git::commands::get_staged_diff,
+        git::commands::get_revision_diff,
+        git::commands::get_gutter_diff,
+        git::commands::get_blame,
         git::commands::checkout_branch,
         git::commands::create_branch,
+        git::commands::rename_branch,
+        git::commands::delete_branch,
+        git::commands::set_branch_upstream,
         git::commands::commit,
+        git::commands::fetch,
         git::commands::pull,
         git::commands::push,
         git::commands::stage_file,
         git::commands::unstage_file,
+        git::commands::stage_hunk,
+        git::commands::unstage_hunk,
+        git::commands::stage_lines,
+        git::commands::unstage_lines,
+        git::commands::discard_hunk,
+        git::commands::get_conflicts,
+        git::commands::parse_conflict_markers,
+        git::commands::resolve_conflict_regions,
+        git::commands::resolve_conflict_file,
+        git::commands::mark_resolved,
+        git::commands::get_repository_state,
+        git::commands::abort_operation,
+        git::commands::continue_operation,
+        git::commands::cherry_pick,
+        git::commands::revert_commits,
+        git::commands::get_rebase_commits,
+        git::commands::start_interactive_rebase,
+        git::commands::list_tags,
+        git::commands::create_tag,
+        git::commands::delete_tag,
+        git::commands::list_remotes,
+        git::commands::add_remote,
+        git::commands::rename_remote,
+        git::commands::remove_remote,
+        git::commands::set_remote_url,
+        git::commands::get_repo_config,
+        git::commands::get_repo_config_value,
+        git::commands::set_repo_config_value,
+        git::commands::unset_repo_config_value,
+        git::commands::list_stashes,
+        git::commands::push_stash,
+        git::commands::apply_stash,
+        git::commands::pop_stash,
+        git::commands::drop_stash,
+        git::commands::show_stash,
+        git::commands::init_repository,
+        git::commands::clone_repository,
+        git::commands::cancel_clone,
+        git::commands::list_worktrees,
+        git::commands::add_worktree,
+        git::commands::remove_worktree,
+        git::commands::watch_git_status,
+        git::commands::unwatch_git_status,
         
         // AI operations
         ai::commands::get_code_completion,
@@ -65,6 +152,41 @@ fn register_services(app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
     let doc_manager = editor::DocumentManager::new();
     app.manage(doc_manager);
     
+    // Initialize workspace trust from the recent projects and trusted paths
+    let workspace_trust = workspace::WorkspaceTrust::new(&app.state::<config::ConfigManager>());
+    app.manage(workspace_trust);
+    
+    // Initialize explorer file operations with their undo history
+    let file_operations = fs::operations::FileOperations::new();
+    app.manage(file_operations);
+    
+    // Initialize line indexes of large files, stored in the app data dir
+    let line_index_dir = app.path().app_data_dir()?.join("line-index");
+    let line_indexes = fs::large_file::LineIndexStore::new(line_index_dir);
+    app.manage(line_indexes);
+    
+    // Initialize git blame cache
+    let blame_cache = git::blame::BlameCache::new();
+    app.manage(blame_cache);
+    
+    // Initialize git clone registry
+    let clone_registry = git::clone::CloneRegistry::new();
+    app.manage(clone_registry);
+    
+    // Initialize file watcher, shared with the git status service
+    let file_watcher = fs::watcher::FileWatcher::new(app.handle().clone());
+    let git_status = git::watch::GitStatusService::new(app.handle().clone(), file_watcher.clone());
+    app.manage(git_status);
+    
+    // Initialize quick open file index
+    let file_index = search::file_index::FileIndex::new(app.handle().clone(), file_watcher.clone());
+    app.manage(file_index);
+    
+    // Initialize text search registry
+    let search_registry = search::text_search::SearchRegistry::new();
+    app.manage(search_registry);
+    app.manage(file_watcher);
+    
     // Initialize AI service
     let ai_service = ai::AIService::new();
     app.manage(ai_service);
@@ -113,13 +235,13 @@ pub fn run() {
             // Register commands
             register_commands(app)?;
             
-            // Register services
-            register_services(app)?;
-            
-            // Initialize config manager
+            // Initialize config manager, which services read their settings from
             let config_manager = init_config_manager(&app_handle)?;
             app.manage(config_manager);
             
+            // Register services
+            register_services(app)?;
+            
             // Spawn a new task to initialize database
             tauri::async_runtime::spawn(async move {
                 match init_database(&app_handle).await {
@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Emitter, State};
+use crate::config::ConfigManager;
+use crate::editor::DocumentManager;
+use crate::search::file_index::{FileIndex, QuickOpenResult};
+use crate::search::text_search::{
+    self, FileMatches, ReplaceResult, ReplaceSelection, SearchQuery, SearchRegistry, SearchSummary,
+    SEARCH_FINISHED_EVENT, SEARCH_RESULT_EVENT,
+};
+use crate::workspace::WorkspaceTrust;
+
+const DEFAULT_QUICK_OPEN_LIMIT: usize = 50;
+
+// Index the files of a workspace for quick open, returning how many there are
+#[command]
+pub async fn index_workspace(
+    file_index: State<'_, FileIndex>,
+    trust: State<'_, WorkspaceTrust>,
+    path: String,
+) -> Result<usize, String> {
+    trust.check("index_workspace", Path::new(&path))?;
+    let file_index = file_index.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || file_index.index(Path::new(&path)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn remove_workspace_index(file_index: State<'_, FileIndex>, path: String) -> Result<(), String> {
+    file_index.remove(Path::new(&path))
+}
+
+// Without a root, every indexed workspace is searched. A root that is not
+// indexed yet is indexed first.
+#[command]
+pub async fn quick_open(
+    file_index: State<'_, FileIndex>,
+    documents: State<'_, DocumentManager>,
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    root: Option<String>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<QuickOpenResult>, String> {
+    let root = root.map(PathBuf::from);
+    if let Some(root) = &root {
+        if !file_index.is_indexed(root) {
+            index_workspace(file_index.clone(), trust, root.to_string_lossy().to_string()).await?;
+        }
+    }
+
+    let open_paths = documents.open_paths();
+    let recent_projects = config_manager.get_config().recent_projects;
+    file_index.quick_open(
+        root.as_deref(),
+        &query,
+        limit.unwrap_or(DEFAULT_QUICK_OPEN_LIMIT),
+        &open_paths,
+        &recent_projects,
+    )
+}
+
+// Matches are streamed per file as `search-result` events, followed by a
+// `search-finished` event with the summary that is also returned
+#[command]
+pub async fn search_in_files(
+    app: AppHandle,
+    searches: State<'_, SearchRegistry>,
+    documents: State<'_, DocumentManager>,
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    search_id: String,
+    root: String,
+    query: SearchQuery,
+) -> Result<SearchSummary, String> {
+    trust.check("search_in_files", Path::new(&root))?;
+    let cancelled = searches.register(&search_id)?;
+    let settings = config_manager.get_config().files;
+    let documents = documents.inner().clone();
+    let id = search_id.clone();
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut on_file = |matches: FileMatches| {
+            let _ = app.emit(SEARCH_RESULT_EVENT, matches);
+        };
+        let summary = text_search::search(
+            &id,
+            Path::new(&root),
+            &query,
+            &settings,
+            &documents,
+            &cancelled,
+            &mut on_file,
+        )?;
+        let _ = app.emit(SEARCH_FINISHED_EVENT, summary.clone());
+        Ok(summary)
+    })
+    .await
+    .map_err(|e| e.to_string());
+
+    searches.finish(&search_id);
+    result?
+}
+
+#[command]
+pub async fn cancel_search(searches: State<'_, SearchRegistry>, search_id: String) -> Result<(), String> {
+    searches.cancel(&search_id)
+}
+
+// Without selections, every match below `root` is replaced
+#[command]
+pub async fn replace_in_files(
+    documents: State<'_, DocumentManager>,
+    config_manager: State<'_, ConfigManager>,
+    trust: State<'_, WorkspaceTrust>,
+    root: String,
+    query: SearchQuery,
+    replacement: String,
+    selections: Option<Vec<ReplaceSelection>>,
+) -> Result<ReplaceResult, String> {
+    trust.check("replace_in_files", Path::new(&root))?;
+    for selection in selections.iter().flatten() {
+        trust.check("replace_in_files", Path::new(&selection.path))?;
+    }
+    let settings = config_manager.get_config().files;
+    let documents = documents.inner().clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        text_search::replace(
+            Path::new(&root),
+            &query,
+            &replacement,
+            selections.as_deref(),
+            &settings,
+            &documents,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
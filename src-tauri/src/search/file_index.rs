@@ -0,0 +1,469 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc::UnboundedReceiver;
+use crate::config::ConfigManager;
+use crate::fs::ignores::IgnoreEngine;
+use crate::fs::watcher::{FileWatcher, WatcherEvent};
+use crate::search::fuzzy::{char_mask, fold, fuzzy_match};
+
+// Boost for files that are open in the editor
+const BONUS_OPEN: i64 = 60;
+// Boost for files of the most recent project, decreasing with its rank
+const BONUS_RECENT_PROJECT: i64 = 30;
+const PENALTY_DEPTH: i64 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuickOpenResult {
+    pub path: String,
+    // Path relative to the indexed workspace, with `/` separators
+    pub relative_path: String,
+    pub name: String,
+    pub score: i64,
+    // Character indices in `relative_path` that matched the query
+    pub matches: Vec<usize>,
+    pub is_open: bool,
+}
+
+struct IndexedFile {
+    chars: Box<[char]>,
+    mask: u64,
+}
+
+impl IndexedFile {
+    fn new(relative: &str) -> Self {
+        IndexedFile {
+            chars: relative.chars().collect(),
+            mask: char_mask(relative),
+        }
+    }
+}
+
+struct WorkspaceIndex {
+    ignores: IgnoreEngine,
+    // Keyed by relative path, so a directory's files form one range
+    files: BTreeMap<String, IndexedFile>,
+    watch_id: u64,
+}
+
+impl WorkspaceIndex {
+    fn build(root: &Path, exclude: &[String]) -> Self {
+        let mut index = WorkspaceIndex {
+            ignores: IgnoreEngine::new(root, exclude),
+            files: BTreeMap::new(),
+            watch_id: 0,
+        };
+        index.add_tree(root);
+        index
+    }
+
+    fn relative(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(self.ignores.root()).ok()?;
+        if relative.as_os_str().is_empty() {
+            return None;
+        }
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    fn add_file(&mut self, path: &Path) {
+        if let Some(relative) = self.relative(path) {
+            let file = IndexedFile::new(&relative);
+            self.files.insert(relative, file);
+        }
+    }
+
+    fn add_tree(&mut self, dir: &Path) {
+        let mut found = Vec::new();
+        self.ignores.walk(dir, &mut |path, is_dir| {
+            if !is_dir {
+                found.push(path.to_path_buf());
+            }
+            true
+        });
+        for path in found {
+            self.add_file(&path);
+        }
+    }
+
+    // Add a created path, which may be a whole directory moved in
+    fn add(&mut self, path: &Path) {
+        let is_dir = path.is_dir();
+        if self.ignores.is_ignored(path, is_dir) {
+            return;
+        }
+        if is_dir {
+            self.add_tree(path);
+        } else if path.is_file() {
+            self.add_file(path);
+        }
+    }
+
+    // Remove a file, or a directory with everything below it
+    fn remove(&mut self, path: &Path) {
+        let relative = match self.relative(path) {
+            Some(relative) => relative,
+            None => return,
+        };
+
+        self.files.remove(&relative);
+        let prefix = format!("{}/", relative);
+        let below: Vec<String> = self
+            .files
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in below {
+            self.files.remove(&key);
+        }
+    }
+
+    fn apply(&mut self, event: &WatcherEvent) {
+        match event {
+            WatcherEvent::Created(path) => self.add(path),
+            WatcherEvent::Modified(path) => {
+                let known = self
+                    .relative(path)
+                    .is_some_and(|relative| self.files.contains_key(&relative));
+                if !known {
+                    self.add(path);
+                }
+            }
+            WatcherEvent::Deleted(path) => self.remove(path),
+            WatcherEvent::Renamed { from, to } => {
+                self.remove(from);
+                if to.starts_with(self.ignores.root()) {
+                    self.add(to);
+                }
+            }
+        }
+
+        // Changed ignore rules can hide or reveal files anywhere below
+        let ignore_file = match event {
+            WatcherEvent::Renamed { from, to } if is_ignore_file(from) && !is_ignore_file(to) => Some(from.as_path()),
+            event if is_ignore_file(event.path()) => Some(event.path()),
+            _ => None,
+        };
+        if let Some(path) = ignore_file {
+            self.ignores.invalidate(path);
+            if let Some(dir) = path.parent().filter(|dir| dir.starts_with(self.ignores.root())) {
+                self.rescan(dir);
+            }
+        }
+    }
+
+    fn rescan(&mut self, dir: &Path) {
+        if dir == self.ignores.root() {
+            self.files.clear();
+            self.add_tree(dir);
+        } else {
+            self.remove(dir);
+            self.add(dir);
+        }
+    }
+}
+
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == ".gitignore" || name == ".ignore")
+}
+
+// In-memory list of the files of each indexed workspace for quick open,
+// kept up to date from file watcher events
+#[derive(Clone)]
+pub struct FileIndex {
+    workspaces: Arc<Mutex<HashMap<PathBuf, WorkspaceIndex>>>,
+    watcher: FileWatcher,
+    app: AppHandle,
+}
+
+impl FileIndex {
+    pub fn new(app: AppHandle, watcher: FileWatcher) -> Self {
+        let workspaces = Arc::new(Mutex::new(HashMap::new()));
+
+        let events = watcher.subscribe();
+        tauri::async_runtime::spawn(process_events(events, workspaces.clone()));
+
+        FileIndex { workspaces, watcher, app }
+    }
+
+    pub fn is_indexed(&self, root: &Path) -> bool {
+        self.workspaces.lock().unwrap().contains_key(root)
+    }
+
+    // Walk the workspace at `root` and start following its changes,
+    // returning the number of files indexed
+    pub fn index(&self, root: &Path) -> Result<usize, String> {
+        if !root.is_dir() {
+            return Err(format!("Path is not a directory: {}", root.display()));
+        }
+        if let Some(workspace) = self.workspaces.lock().unwrap().get(root) {
+            return Ok(workspace.files.len());
+        }
+
+        let settings = self
+            .app
+            .try_state::<ConfigManager>()
+            .map(|config_manager| config_manager.get_config().files)
+            .unwrap_or_default();
+
+        // Changes reported while walking are replayed afterwards
+        let mut during_walk = self.watcher.subscribe();
+        let watch = self.watcher.watch_internal(root)?;
+        let mut workspace = WorkspaceIndex::build(root, &settings.exclude);
+        workspace.watch_id = watch.id;
+
+        let mut workspaces = self.workspaces.lock().unwrap();
+        if let Some(existing) = workspaces.get(root) {
+            // Indexed concurrently
+            let count = existing.files.len();
+            drop(workspaces);
+            let _ = self.watcher.unwatch_id(watch.id);
+            return Ok(count);
+        }
+        while let Ok(event) = during_walk.try_recv() {
+            if event.path().starts_with(root) {
+                workspace.apply(&event);
+            }
+        }
+        let count = workspace.files.len();
+        workspaces.insert(root.to_path_buf(), workspace);
+        Ok(count)
+    }
+
+    pub fn remove(&self, root: &Path) -> Result<(), String> {
+        let removed = self.workspaces.lock().unwrap().remove(root);
+        match removed {
+            Some(workspace) => self.watcher.unwatch_id(workspace.watch_id),
+            None => Ok(()),
+        }
+    }
+
+    // Best matches for `query` in the workspace at `root`, or in every
+    // indexed workspace. Without a query, open files come first, then the
+    // files closest to the root.
+    pub fn quick_open(
+        &self,
+        root: Option<&Path>,
+        query: &str,
+        limit: usize,
+        open_paths: &[PathBuf],
+        recent_projects: &[String],
+    ) -> Result<Vec<QuickOpenResult>, String> {
+        let query: Vec<char> = query
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| if c == '\\' { '/' } else { fold(c) })
+            .collect();
+        let query_mask = query.iter().fold(0, |mask, c| mask | char_mask(&c.to_string()));
+
+        let workspaces = self.workspaces.lock().unwrap();
+        if let Some(root) = root {
+            if !workspaces.contains_key(root) {
+                return Err(format!("Workspace is not indexed: {}", root.display()));
+            }
+        }
+
+        let mut results = Vec::new();
+        for (workspace_root, workspace) in workspaces.iter() {
+            if root.is_some_and(|root| root != workspace_root) {
+                continue;
+            }
+
+            let open: HashSet<String> = open_paths
+                .iter()
+                .filter_map(|path| workspace.relative(path))
+                .collect();
+            let project_bonus = recent_projects
+                .iter()
+                .position(|project| Path::new(project) == workspace_root)
+                .map_or(0, |rank| BONUS_RECENT_PROJECT - (rank as i64) * 3)
+                .max(0);
+
+            for (relative, file) in &workspace.files {
+                if file.mask & query_mask != query_mask {
+                    continue;
+                }
+                let matched = match fuzzy_match(&query, &file.chars) {
+                    Some(matched) => matched,
+                    None => continue,
+                };
+
+                let is_open = open.contains(relative);
+                let depth = relative.matches('/').count() as i64;
+                let mut score = matched.score + project_bonus - depth * PENALTY_DEPTH;
+                if is_open {
+                    score += BONUS_OPEN;
+                }
+
+                results.push(Candidate {
+                    score,
+                    root: workspace_root,
+                    relative,
+                    positions: matched.positions,
+                    is_open,
+                });
+            }
+        }
+
+        // Higher scores first, then shorter paths
+        let order = |a: &Candidate, b: &Candidate| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.relative.len().cmp(&b.relative.len()))
+                .then_with(|| a.relative.cmp(b.relative))
+        };
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        if results.len() > limit {
+            results.select_nth_unstable_by(limit - 1, order);
+            results.truncate(limit);
+        }
+        results.sort_unstable_by(order);
+
+        Ok(results
+            .into_iter()
+            .map(|candidate| QuickOpenResult {
+                path: candidate.root.join(candidate.relative).to_string_lossy().to_string(),
+                relative_path: candidate.relative.clone(),
+                name: candidate
+                    .relative
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(candidate.relative)
+                    .to_string(),
+                score: candidate.score,
+                matches: candidate.positions,
+                is_open: candidate.is_open,
+            })
+            .collect())
+    }
+}
+
+struct Candidate<'a> {
+    score: i64,
+    root: &'a PathBuf,
+    relative: &'a String,
+    positions: Vec<usize>,
+    is_open: bool,
+}
+
+async fn process_events(
+    mut events: UnboundedReceiver<WatcherEvent>,
+    workspaces: Arc<Mutex<HashMap<PathBuf, WorkspaceIndex>>>,
+) {
+    while let Some(event) = events.recv().await {
+        // Applying an event can walk a whole tree, e.g. after a directory
+        // was moved in or an ignore file changed, so it runs off the async
+        // runtime. Events are still applied one at a time, in order.
+        let workspaces = workspaces.clone();
+        let applied = tauri::async_runtime::spawn_blocking(move || {
+            let mut workspaces = workspaces.lock().unwrap();
+            for (root, workspace) in workspaces.iter_mut() {
+                let affected = match &event {
+                    WatcherEvent::Renamed { from, to } => from.starts_with(root) || to.starts_with(root),
+                    event => event.path().starts_with(root),
+                };
+                if affected {
+                    workspace.apply(&event);
+                }
+            }
+        })
+        .await;
+        if let Err(e) = applied {
+            log::warn!("Failed to update the file index: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FilesConfig;
+    use crate::git::tests::init_repo;
+    use std::fs;
+
+    fn files(index: &WorkspaceIndex) -> Vec<&str> {
+        index.files.keys().map(String::as_str).collect()
+    }
+
+    // With the default `files.exclude`, which leaves out `.git`
+    fn build(root: &Path) -> WorkspaceIndex {
+        WorkspaceIndex::build(root, &FilesConfig::default().exclude)
+    }
+
+    fn write(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn created_files_and_directories_are_added() {
+        let dir = init_repo();
+        let root = dir.path();
+        write(&root.join("a.txt"));
+        let mut index = build(root);
+        assert_eq!(files(&index), ["a.txt"]);
+
+        write(&root.join("b.txt"));
+        index.apply(&WatcherEvent::Created(root.join("b.txt")));
+        write(&root.join("src/lib/mod.rs"));
+        write(&root.join("src/main.rs"));
+        index.apply(&WatcherEvent::Created(root.join("src")));
+        assert_eq!(files(&index), ["a.txt", "b.txt", "src/lib/mod.rs", "src/main.rs"]);
+    }
+
+    #[test]
+    fn deleted_directories_take_their_files_along() {
+        let dir = init_repo();
+        let root = dir.path();
+        for name in ["src/a.rs", "src/sub/b.rs", "src2/c.rs", "src.txt"] {
+            write(&root.join(name));
+        }
+        let mut index = build(root);
+
+        fs::remove_dir_all(root.join("src")).unwrap();
+        index.apply(&WatcherEvent::Deleted(root.join("src")));
+        assert_eq!(files(&index), ["src.txt", "src2/c.rs"]);
+    }
+
+    #[test]
+    fn renames_follow_files_and_drop_those_moved_out() {
+        let dir = init_repo();
+        let outside = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("a.txt"));
+        write(&root.join("b.txt"));
+        let mut index = build(root);
+
+        fs::rename(root.join("a.txt"), root.join("c.txt")).unwrap();
+        index.apply(&WatcherEvent::Renamed { from: root.join("a.txt"), to: root.join("c.txt") });
+        assert_eq!(files(&index), ["b.txt", "c.txt"]);
+
+        fs::rename(root.join("b.txt"), outside.path().join("b.txt")).unwrap();
+        index.apply(&WatcherEvent::Renamed { from: root.join("b.txt"), to: outside.path().join("b.txt") });
+        assert_eq!(files(&index), ["c.txt"]);
+    }
+
+    #[test]
+    fn ignore_file_changes_rescan() {
+        let dir = init_repo();
+        let root = dir.path();
+        for name in ["a.txt", "debug.log", "src/trace.log"] {
+            write(&root.join(name));
+        }
+        let mut index = build(root);
+        assert_eq!(files(&index), ["a.txt", "debug.log", "src/trace.log"]);
+
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        index.apply(&WatcherEvent::Created(root.join(".gitignore")));
+        assert_eq!(files(&index), [".gitignore", "a.txt"]);
+
+        fs::remove_file(root.join(".gitignore")).unwrap();
+        index.apply(&WatcherEvent::Deleted(root.join(".gitignore")));
+        assert_eq!(files(&index), ["a.txt", "debug.log", "src/trace.log"]);
+    }
+}
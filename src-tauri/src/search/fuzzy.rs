@@ -0,0 +1,201 @@
+// Fuzzy matching of quick open queries against relative paths. A query
+// matches when its characters appear in order, ignoring case; matches on
+// word boundaries, runs of consecutive characters and matches within the
+// file name score higher.
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 16;
+const BONUS_SEPARATOR: i64 = 30;
+const BONUS_BOUNDARY: i64 = 24;
+const BONUS_CAMEL_CASE: i64 = 20;
+const BONUS_FILE_NAME: i64 = 40;
+const BONUS_NAME_PREFIX: i64 = 50;
+const BONUS_EXACT_NAME: i64 = 80;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    // Character indices of the matched characters, for highlighting
+    pub positions: Vec<usize>,
+}
+
+// Lowercase one character to one character, so indices stay aligned
+pub fn fold(c: char) -> char {
+    if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+// Cheap prefilter: a bit per character class present in the text
+pub fn char_mask(text: &str) -> u64 {
+    text.chars().fold(0, |mask, c| mask | char_bit(fold(c)))
+}
+
+fn char_bit(c: char) -> u64 {
+    match c {
+        'a'..='z' => 1 << (c as u32 - 'a' as u32),
+        '0'..='9' => 1 << (26 + c as u32 - '0' as u32),
+        c if c.is_ascii() => 1 << 36,
+        c => 1 << (37 + (c as u32 % 27)),
+    }
+}
+
+// Score `query` (already folded, without spaces) against the characters
+// of a path using `/` as separator
+pub fn fuzzy_match(query: &[char], path: &[char]) -> Option<FuzzyMatch> {
+    if query.is_empty() {
+        return Some(FuzzyMatch { score: 0, positions: Vec::new() });
+    }
+
+    let name_start = path
+        .iter()
+        .rposition(|c| *c == '/')
+        .map_or(0, |index| index + 1);
+
+    // Prefer a match entirely within the file name
+    if let Some(positions) = find_positions(query, path, name_start) {
+        let mut score = score_positions(&positions, path) + BONUS_FILE_NAME;
+
+        let name = &path[name_start..];
+        let stem_len = name.iter().rposition(|c| *c == '.').unwrap_or(name.len());
+        let is_prefix = name.len() >= query.len() && name.iter().zip(query).all(|(c, q)| fold(*c) == *q);
+        if is_prefix {
+            score += BONUS_NAME_PREFIX;
+            if query.len() == stem_len || query.len() == name.len() {
+                score += BONUS_EXACT_NAME;
+            }
+        }
+        return Some(FuzzyMatch { score, positions });
+    }
+
+    let positions = find_positions(query, path, 0)?;
+    let score = score_positions(&positions, path);
+    Some(FuzzyMatch { score, positions })
+}
+
+// Positions of the shortest window, ending at the first complete match,
+// in which the query appears in order
+fn find_positions(query: &[char], text: &[char], from: usize) -> Option<Vec<usize>> {
+    // Forward pass finds where the first complete match ends
+    let mut query_index = 0;
+    let mut end = None;
+    for (index, c) in text.iter().enumerate().skip(from) {
+        if fold(*c) == query[query_index] {
+            query_index += 1;
+            if query_index == query.len() {
+                end = Some(index);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Backward pass tightens the window to start as late as possible
+    let mut positions = vec![0; query.len()];
+    let mut query_index = query.len();
+    let mut index = end + 1;
+    while query_index > 0 {
+        index -= 1;
+        if fold(text[index]) == query[query_index - 1] {
+            query_index -= 1;
+            positions[query_index] = index;
+        }
+    }
+
+    Some(positions)
+}
+
+fn score_positions(positions: &[usize], path: &[char]) -> i64 {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+
+    for &position in positions {
+        score += SCORE_MATCH;
+
+        match previous {
+            Some(previous) if position == previous + 1 => score += BONUS_CONSECUTIVE,
+            Some(previous) => {
+                let gap = (position - previous - 1) as i64;
+                score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (gap - 1).min(10);
+            }
+            None => {}
+        }
+
+        score += boundary_bonus(position, path);
+        previous = Some(position);
+    }
+
+    score
+}
+
+fn boundary_bonus(position: usize, path: &[char]) -> i64 {
+    if position == 0 {
+        return BONUS_SEPARATOR;
+    }
+
+    let before = path[position - 1];
+    if before == '/' {
+        BONUS_SEPARATOR
+    } else if matches!(before, '_' | '-' | '.' | ' ') {
+        BONUS_BOUNDARY
+    } else if before.is_lowercase() && path[position].is_uppercase() {
+        BONUS_CAMEL_CASE
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn matched(query: &str, path: &str) -> Option<FuzzyMatch> {
+        fuzzy_match(&chars(query), &chars(path))
+    }
+
+    fn score(query: &str, path: &str) -> i64 {
+        matched(query, path).unwrap().score
+    }
+
+    #[test]
+    fn names_rank_above_boundaries_above_scattered_matches() {
+        let exact = score("main", "src/main.rs");
+        let prefix = score("main", "src/maintenance.rs");
+        let boundary = score("main", "src/my_main.rs");
+        let scattered = score("main", "src/mxaxixn.rs");
+        assert!(exact > prefix, "{} > {}", exact, prefix);
+        assert!(prefix > boundary, "{} > {}", prefix, boundary);
+        assert!(boundary > scattered, "{} > {}", boundary, scattered);
+
+        // Within the file name beats spread over directories
+        assert!(score("main", "a/main") > score("main", "ma/in/x"));
+    }
+
+    #[test]
+    fn positions_point_at_the_matched_characters() {
+        assert_eq!(matched("main", "src/main.rs").unwrap().positions, [4, 5, 6, 7]);
+        assert_eq!(matched("mn", "src/mxaxixn.rs").unwrap().positions, [4, 10]);
+        // Matched in the directories when the name does not have it all
+        assert_eq!(matched("srm", "src/main.rs").unwrap().positions, [0, 1, 4]);
+        // The tightest window that ends at the first complete match
+        assert_eq!(matched("ab", "a_a_b").unwrap().positions, [2, 4]);
+        assert_eq!(matched("", "x").unwrap().positions, Vec::<usize>::new());
+        assert!(matched("xyz", "src/main.rs").is_none());
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        let query: Vec<char> = "readme".chars().map(fold).collect();
+        assert_eq!(fuzzy_match(&query, &chars("docs/README.md")).unwrap().positions, [5, 6, 7, 8, 9, 10]);
+        assert_eq!(char_mask("ABC"), char_mask("abc"));
+        assert!(score("fb", "FooBar") > score("fb", "foobar"));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod commands;
+pub mod file_index;
+pub mod fuzzy;
+pub mod text_search;
@@ -0,0 +1,664 @@
+use globset::GlobSet;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::config::FilesConfig;
+use crate::editor::DocumentManager;
+use crate::fs::encoding::{self, read_text_file, write_text_file, TextEncoding, WriteOptions};
+use crate::fs::ignores::{build_globs, IgnoreEngine};
+
+// Events streamed to the frontend while a search runs
+pub const SEARCH_RESULT_EVENT: &str = "search-result";
+pub const SEARCH_FINISHED_EVENT: &str = "search-finished";
+
+// Larger files are skipped
+const MAX_FILE_SIZE: u64 = 20 * 1024 * 1024;
+const DEFAULT_MAX_RESULTS: usize = 20_000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    // Let matches span lines, e.g. a regex containing `\n`
+    pub multiline: bool,
+    // Globs relative to the root; when given, only matching files are searched
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // Lines of context before and after each match
+    pub context_lines: usize,
+    pub max_results: Option<usize>,
+}
+
+// Lines and columns are 1-based, columns count characters and the end
+// column is exclusive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchRange {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub range: MatchRange,
+    // Full text of the lines the match covers
+    pub lines: Vec<String>,
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileMatches {
+    pub search_id: String,
+    pub path: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchSummary {
+    pub search_id: String,
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub match_count: usize,
+    // Stopped early because `max_results` was reached
+    pub limit_hit: bool,
+    pub cancelled: bool,
+}
+
+// Matches to replace in a file; without ranges every match is replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceSelection {
+    pub path: String,
+    pub ranges: Option<Vec<MatchRange>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceResult {
+    pub files_changed: usize,
+    pub replacements: usize,
+    // Open documents whose content was replaced
+    pub documents_updated: Vec<String>,
+    pub failures: Vec<ReplaceFailure>,
+}
+
+// Cancellation flags of running searches by the id the frontend gave them
+pub struct SearchRegistry {
+    searches: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl SearchRegistry {
+    pub fn new() -> Self {
+        SearchRegistry {
+            searches: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn register(&self, id: &str) -> Result<Arc<AtomicBool>, String> {
+        let mut searches = self.searches.lock().unwrap();
+        if searches.contains_key(id) {
+            return Err(format!("Search {} is already running", id));
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        searches.insert(id.to_string(), cancelled.clone());
+        Ok(cancelled)
+    }
+
+    pub fn finish(&self, id: &str) {
+        self.searches.lock().unwrap().remove(id);
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        match self.searches.lock().unwrap().get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            None => Err(format!("No search running with id {}", id)),
+        }
+    }
+}
+
+pub fn build_regex(query: &SearchQuery) -> Result<Regex, String> {
+    if query.pattern.is_empty() {
+        return Err("Search pattern is empty".to_string());
+    }
+
+    let pattern = if query.is_regex {
+        query.pattern.clone()
+    } else {
+        regex::escape(&query.pattern)
+    };
+    let pattern = if query.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!query.case_sensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+// A match and the text it was found in, needed to expand capture groups
+struct Found {
+    range: Range<usize>,
+    haystack: Range<usize>,
+}
+
+// Byte ranges of the non-empty matches in `content`. Unless `multiline` is
+// set, each line is searched on its own.
+fn find_matches(regex: &Regex, content: &str, multiline: bool) -> Vec<Found> {
+    let mut found = Vec::new();
+
+    if multiline {
+        for m in regex.find_iter(content).filter(|m| !m.is_empty()) {
+            found.push(Found { range: m.range(), haystack: 0..content.len() });
+        }
+        return found;
+    }
+
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        for m in regex.find_iter(text).filter(|m| !m.is_empty()) {
+            found.push(Found {
+                range: offset + m.start()..offset + m.end(),
+                haystack: offset..offset + text.len(),
+            });
+        }
+        offset += line.len();
+    }
+    found
+}
+
+struct LineIndex<'a> {
+    content: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(content: &'a str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(content.match_indices('\n').map(|(index, _)| index + 1));
+        LineIndex { content, starts }
+    }
+
+    // 0-based line of a byte offset
+    fn line_of(&self, offset: usize) -> usize {
+        match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    fn line_text(&self, line: usize) -> &'a str {
+        let start = self.starts[line];
+        let end = self.starts.get(line + 1).copied().unwrap_or(self.content.len());
+        self.content[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    // A trailing newline does not start another line
+    fn line_count(&self) -> usize {
+        if self.content.ends_with('\n') {
+            self.starts.len() - 1
+        } else {
+            self.starts.len()
+        }
+    }
+
+    fn range(&self, range: &Range<usize>) -> MatchRange {
+        let start_line = self.line_of(range.start);
+        let end_line = self.line_of(range.end);
+        MatchRange {
+            start_line: start_line + 1,
+            start_column: self.content[self.starts[start_line]..range.start].chars().count() + 1,
+            end_line: end_line + 1,
+            end_column: self.content[self.starts[end_line]..range.end].chars().count() + 1,
+        }
+    }
+}
+
+fn search_content(content: &str, regex: &Regex, query: &SearchQuery, limit: usize) -> Vec<SearchMatch> {
+    // A literal that is nowhere in the file is in none of its lines
+    if !query.is_regex && !regex.is_match(content) {
+        return Vec::new();
+    }
+    let lines = LineIndex::new(content);
+
+    find_matches(regex, content, query.multiline)
+        .into_iter()
+        .take(limit)
+        .map(|found| {
+            let range = lines.range(&found.range);
+            let first = range.start_line - 1;
+            let last = range.end_line - 1;
+            let before = first.saturating_sub(query.context_lines);
+            let after = (last + query.context_lines).min(lines.line_count().max(1) - 1);
+
+            SearchMatch {
+                range,
+                lines: (first..=last).map(|line| lines.line_text(line).to_string()).collect(),
+                context_before: (before..first).map(|line| lines.line_text(line).to_string()).collect(),
+                context_after: (last + 1..=after).map(|line| lines.line_text(line).to_string()).collect(),
+            }
+        })
+        .collect()
+}
+
+// Text of a file to search or replace in: the open document if there is
+// one, so unsaved edits are included, otherwise the file on disk decoded
+// like the editor opens it
+fn read_text(path: &Path, documents: &DocumentManager, fallback: TextEncoding) -> Option<String> {
+    if let Some((_, document)) = documents.find_by_path(path) {
+        return Some(document.content);
+    }
+
+    let metadata = fs::metadata(path).ok()?;
+    if metadata.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    // Binary files decode as the single-byte fallback, but keep their zero
+    // bytes; decoded UTF-16 text has none
+    let file = read_text_file(path, fallback).ok()?;
+    if file.content.contains('\0') {
+        return None;
+    }
+    Some(file.content)
+}
+
+struct PathFilter {
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    fn new(root: &Path, query: &SearchQuery) -> Self {
+        PathFilter {
+            root: root.to_path_buf(),
+            include: if query.include.is_empty() { None } else { Some(build_globs(&query.include)) },
+            exclude: build_globs(&query.exclude),
+        }
+    }
+
+    fn allows(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+        match &self.include {
+            Some(include) if !is_dir => include.is_match(relative),
+            _ => true,
+        }
+    }
+}
+
+// Search the files below `root` that are not ignored, calling `on_file`
+// with the matches of each file as it is done
+pub fn search(
+    search_id: &str,
+    root: &Path,
+    query: &SearchQuery,
+    settings: &FilesConfig,
+    documents: &DocumentManager,
+    cancelled: &AtomicBool,
+    on_file: &mut dyn FnMut(FileMatches),
+) -> Result<SearchSummary, String> {
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", root.display()));
+    }
+    let regex = build_regex(query)?;
+    let filter = PathFilter::new(root, query);
+    let ignores = IgnoreEngine::new(root, &settings.exclude);
+    let fallback = encoding::fallback_encoding(settings);
+    let max_results = query.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+    let mut summary = SearchSummary {
+        search_id: search_id.to_string(),
+        files_searched: 0,
+        files_matched: 0,
+        match_count: 0,
+        limit_hit: false,
+        cancelled: false,
+    };
+
+    ignores.walk(root, &mut |path, is_dir| {
+        if cancelled.load(Ordering::SeqCst) || summary.limit_hit || !filter.allows(path, is_dir) {
+            return false;
+        }
+        if is_dir {
+            return true;
+        }
+
+        let content = match read_text(path, documents, fallback) {
+            Some(content) => content,
+            None => return false,
+        };
+        summary.files_searched += 1;
+
+        let remaining = max_results - summary.match_count;
+        let matches = search_content(&content, &regex, query, remaining);
+        if matches.is_empty() {
+            return false;
+        }
+
+        summary.files_matched += 1;
+        summary.match_count += matches.len();
+        summary.limit_hit = summary.match_count >= max_results;
+        on_file(FileMatches {
+            search_id: search_id.to_string(),
+            path: path.to_string_lossy().to_string(),
+            matches,
+        });
+        false
+    });
+
+    summary.cancelled = cancelled.load(Ordering::SeqCst);
+    Ok(summary)
+}
+
+// Replace matches of `query` in the selected files, or in every file
+// below `root` when there is no selection. Open documents are updated in
+// the `DocumentManager`; those with unsaved changes are left unsaved
+// rather than overwriting the file on disk.
+pub fn replace(
+    root: &Path,
+    query: &SearchQuery,
+    replacement: &str,
+    selections: Option<&[ReplaceSelection]>,
+    settings: &FilesConfig,
+    documents: &DocumentManager,
+) -> Result<ReplaceResult, String> {
+    let regex = build_regex(query)?;
+    let fallback = encoding::fallback_encoding(settings);
+
+    let targets: Vec<(PathBuf, Option<&[MatchRange]>)> = match selections {
+        Some(selections) => selections
+            .iter()
+            .map(|selection| (PathBuf::from(&selection.path), selection.ranges.as_deref()))
+            .collect(),
+        None => {
+            let mut paths = Vec::new();
+            let cancelled = AtomicBool::new(false);
+            let query = SearchQuery { max_results: Some(usize::MAX), context_lines: 0, ..query.clone() };
+            search("", root, &query, settings, documents, &cancelled, &mut |file| {
+                paths.push((PathBuf::from(file.path), None));
+            })?;
+            paths
+        }
+    };
+
+    let mut result = ReplaceResult {
+        files_changed: 0,
+        replacements: 0,
+        documents_updated: Vec::new(),
+        failures: Vec::new(),
+    };
+
+    for (path, ranges) in targets {
+        match replace_in_file(&path, &regex, query, replacement, ranges, documents, fallback) {
+            Ok((0, _)) => {}
+            Ok((count, document)) => {
+                result.files_changed += 1;
+                result.replacements += count;
+                result.documents_updated.extend(document);
+            }
+            Err(error) => result.failures.push(ReplaceFailure {
+                path: path.to_string_lossy().to_string(),
+                error,
+            }),
+        }
+    }
+
+    Ok(result)
+}
+
+// Returns the number of replacements and the uri of the open document
+// that was updated, if any
+fn replace_in_file(
+    path: &Path,
+    regex: &Regex,
+    query: &SearchQuery,
+    replacement: &str,
+    ranges: Option<&[MatchRange]>,
+    documents: &DocumentManager,
+    fallback: TextEncoding,
+) -> Result<(usize, Option<String>), String> {
+    let document = documents.find_by_path(path);
+    let content = match &document {
+        Some((_, document)) => document.content.clone(),
+        None => read_text(path, documents, fallback).ok_or("Cannot read file as text")?,
+    };
+
+    let lines = LineIndex::new(&content);
+    let mut replaced = String::with_capacity(content.len());
+    let mut last = 0;
+    let mut count = 0;
+
+    for found in find_matches(regex, &content, query.multiline) {
+        // Ranges that no longer match, e.g. after an edit, are skipped
+        if let Some(ranges) = ranges {
+            if !ranges.contains(&lines.range(&found.range)) {
+                continue;
+            }
+        }
+
+        replaced.push_str(&content[last..found.range.start]);
+        if query.is_regex {
+            let haystack = &content[found.haystack.clone()];
+            if let Some(captures) = regex.captures_at(haystack, found.range.start - found.haystack.start) {
+                captures.expand(replacement, &mut replaced);
+            }
+        } else {
+            replaced.push_str(replacement);
+        }
+        last = found.range.end;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Ok((0, None));
+    }
+    replaced.push_str(&content[last..]);
+
+    // Written in the encoding, BOM and line endings the file already has
+    let options = WriteOptions::default();
+    match document {
+        Some((uri, document)) => {
+            documents.update_document(&uri, &replaced)?;
+            if !document.dirty {
+                write_text_file(path, &replaced, &options, fallback)?;
+                documents.mark_saved(&uri)?;
+            }
+            Ok((count, Some(uri)))
+        }
+        None => {
+            write_text_file(path, &replaced, &options, fallback)?;
+            Ok((count, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pattern: &str, is_regex: bool) -> SearchQuery {
+        SearchQuery {
+            pattern: pattern.to_string(),
+            is_regex,
+            case_sensitive: true,
+            ..Default::default()
+        }
+    }
+
+    fn ranges(found: &[Found]) -> Vec<Range<usize>> {
+        found.iter().map(|found| found.range.clone()).collect()
+    }
+
+    fn range(line: usize, start: usize, end: usize) -> MatchRange {
+        MatchRange {
+            start_line: line,
+            start_column: start,
+            end_line: line,
+            end_column: end,
+        }
+    }
+
+    fn replace_all(
+        path: &Path,
+        query: &SearchQuery,
+        replacement: &str,
+        ranges: Option<Vec<MatchRange>>,
+        documents: &DocumentManager,
+    ) -> ReplaceResult {
+        let selections = [ReplaceSelection {
+            path: path.to_string_lossy().to_string(),
+            ranges,
+        }];
+        let root = path.parent().unwrap();
+        replace(root, query, replacement, Some(&selections), &FilesConfig::default(), documents).unwrap()
+    }
+
+    #[test]
+    fn matches_stay_within_a_line_unless_multiline() {
+        let content = "foo bar\r\nbaz foo\n";
+        let regex = build_regex(&query("foo", false)).unwrap();
+        assert_eq!(ranges(&find_matches(&regex, content, false)), vec![0..3, 13..16]);
+
+        let regex = build_regex(&query(r"bar\s+baz", true)).unwrap();
+        assert!(find_matches(&regex, content, false).is_empty());
+        assert_eq!(ranges(&find_matches(&regex, content, true)), vec![4..12]);
+
+        // Empty matches are left out
+        let regex = build_regex(&query("x*", true)).unwrap();
+        assert!(find_matches(&regex, content, false).is_empty());
+    }
+
+    #[test]
+    fn matches_have_context_and_character_columns() {
+        let content = "ğüş foo\nline 2\nline 3\nçay foo\n";
+        let query = SearchQuery {
+            context_lines: 1,
+            ..query("foo", false)
+        };
+        let regex = build_regex(&query).unwrap();
+        let matches = search_content(content, &regex, &query, usize::MAX);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].range, range(1, 5, 8));
+        assert_eq!(matches[0].lines, vec!["ğüş foo"]);
+        assert!(matches[0].context_before.is_empty());
+        assert_eq!(matches[0].context_after, vec!["line 2"]);
+
+        assert_eq!(matches[1].range, range(4, 5, 8));
+        assert_eq!(matches[1].context_before, vec!["line 3"]);
+        assert!(matches[1].context_after.is_empty());
+
+        assert_eq!(search_content(content, &regex, &query, 1).len(), 1);
+    }
+
+    #[test]
+    fn files_are_searched_in_their_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("legacy.txt"), b"ba\xF0 needle\n").unwrap();
+        let utf16: Vec<u8> = "utf-16 needle\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        fs::write(dir.path().join("utf16.txt"), [&[0xFF, 0xFE][..], &utf16].concat()).unwrap();
+        fs::write(dir.path().join("data.bin"), b"needle\0\x01\x02").unwrap();
+
+        let mut found = Vec::new();
+        let summary = search(
+            "id",
+            dir.path(),
+            &query("needle", false),
+            &FilesConfig::default(),
+            &DocumentManager::new(),
+            &AtomicBool::new(false),
+            &mut |file| found.push((file.path, file.matches[0].lines[0].clone())),
+        )
+        .unwrap();
+        found.sort();
+
+        assert_eq!(summary.files_searched, 2);
+        assert_eq!(
+            found,
+            vec![
+                (dir.path().join("legacy.txt").to_string_lossy().to_string(), "bağ needle".to_string()),
+                (dir.path().join("utf16.txt").to_string_lossy().to_string(), "utf-16 needle".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_expands_captures_and_keeps_the_file_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.rs");
+        fs::write(&path, b"\xEF\xBB\xBFlet a = 1;\r\nlet b = 2;\r\n").unwrap();
+        let legacy = dir.path().join("legacy.txt");
+        fs::write(&legacy, b"x = \xF0\r\n").unwrap();
+        let documents = DocumentManager::new();
+
+        let result = replace_all(&path, &query(r"let (\w)", true), "const ${1}_", None, &documents);
+        assert_eq!((result.files_changed, result.replacements), (1, 2));
+        assert_eq!(fs::read(&path).unwrap(), b"\xEF\xBB\xBFconst a_ = 1;\r\nconst b_ = 2;\r\n");
+
+        // `$1` is literal text outside regex mode
+        replace_all(&legacy, &query("x", false), "$1", None, &documents);
+        assert_eq!(fs::read(&legacy).unwrap(), b"$1 = \xF0\r\n");
+    }
+
+    #[test]
+    fn replace_skips_ranges_that_no_longer_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "foo\nfoo\n").unwrap();
+
+        let selected = vec![range(2, 1, 4), range(1, 5, 8)];
+        let result = replace_all(&path, &query("foo", false), "bar", Some(selected), &DocumentManager::new());
+        assert_eq!(result.replacements, 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "foo\nbar\n");
+    }
+
+    #[test]
+    fn replace_leaves_dirty_documents_unsaved() {
+        let dir = tempfile::tempdir().unwrap();
+        let clean = dir.path().join("clean.txt");
+        let dirty = dir.path().join("dirty.txt");
+        fs::write(&clean, "foo\r\n").unwrap();
+        fs::write(&dirty, "foo\n").unwrap();
+
+        let documents = DocumentManager::new();
+        documents.open_document("clean", Some(clean.clone()), "plaintext", "foo\n").unwrap();
+        documents.open_document("dirty", Some(dirty.clone()), "plaintext", "foo\n").unwrap();
+        documents.update_document("dirty", "foo foo\n").unwrap();
+
+        let result = replace_all(&clean, &query("foo", false), "bar", None, &documents);
+        assert_eq!(result.documents_updated, vec!["clean".to_string()]);
+        assert_eq!(fs::read_to_string(&clean).unwrap(), "bar\r\n");
+        assert!(!documents.get_document("clean").unwrap().dirty);
+
+        let result = replace_all(&dirty, &query("foo", false), "bar", None, &documents);
+        assert_eq!(result.replacements, 2);
+        assert_eq!(fs::read_to_string(&dirty).unwrap(), "foo\n");
+        let document = documents.get_document("dirty").unwrap();
+        assert_eq!(document.content, "bar bar\n");
+        assert!(document.dirty);
+    }
+}
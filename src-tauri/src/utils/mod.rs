@@ -1,9 +1,9 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::io;
 use std::fs;
 
 // Find project root based on common project files
-pub fn find_project_root(start_path: &PathBuf) -> Option<PathBuf> {
+pub fn find_project_root(start_path: &Path) -> Option<PathBuf> {
     let markers = [
         ".git",
         "package.json",
@@ -14,13 +14,18 @@ pub fn find_project_root(start_path: &PathBuf) -> Option<PathBuf> {
         "requirements.txt",
     ];
     
-    let mut current = start_path.clone();
+    let mut current = start_path.to_path_buf();
     
     loop {
         // Check for project markers
         for marker in &markers {
             let marker_path = current.join(marker);
-            if marker_path.exists() {
+            let found = if *marker == ".git" {
+                is_git_marker(&marker_path)
+            } else {
+                marker_path.exists()
+            };
+            if found {
                 return Some(current.clone());
             }
         }
@@ -32,7 +37,28 @@ pub fn find_project_root(start_path: &PathBuf) -> Option<PathBuf> {
     }
     
     // If no project root found, just return the starting directory
-    Some(start_path.clone())
+    Some(start_path.to_path_buf())
+}
+
+// `.git` is a directory in a regular checkout. Linked worktrees and
+// submodules have a `.git` file instead, pointing at their git directory.
+fn is_git_marker(path: &Path) -> bool {
+    if path.is_dir() {
+        return true;
+    }
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return false,
+    };
+    match content.trim().strip_prefix("gitdir:") {
+        Some(gitdir) => {
+            let gitdir = Path::new(gitdir.trim());
+            let base = path.parent().unwrap_or(Path::new(""));
+            base.join(gitdir).is_dir()
+        }
+        None => false,
+    }
 }
 
 // Get file size in a human-readable format
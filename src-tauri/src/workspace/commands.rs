@@ -0,0 +1,101 @@
+use std::path::Path;
+use tauri::{command, AppHandle, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use crate::config::ConfigManager;
+use crate::workspace::{resolve_path, workspace_root, TrustInfo, WorkspaceTrust};
+
+// Let the user pick a folder and open it as a workspace. Picking it is what
+// trusts it. Returns the workspace root, or None when cancelled.
+#[command]
+pub async fn pick_workspace(
+    app: AppHandle,
+    trust: State<'_, WorkspaceTrust>,
+    config_manager: State<'_, ConfigManager>,
+) -> Result<Option<String>, String> {
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        app.dialog().file().set_title("Select a project folder").blocking_pick_folder()
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let path = match picked {
+        Some(folder) => folder.into_path().map_err(|e| e.to_string())?,
+        None => return Ok(None),
+    };
+    let root = workspace_root(&path)?;
+    trust.open_workspace(&root, &config_manager)?;
+    Ok(Some(root.to_string_lossy().to_string()))
+}
+
+// Open a workspace by path, e.g. a recent project. A folder that is not
+// trusted yet is only opened once the user confirms it.
+#[command]
+pub async fn open_workspace(
+    app: AppHandle,
+    trust: State<'_, WorkspaceTrust>,
+    config_manager: State<'_, ConfigManager>,
+    path: String,
+) -> Result<String, String> {
+    let root = workspace_root(Path::new(&path))?;
+    if !trust.is_trusted(&root) && !confirm_trust(app, &root).await? {
+        return Err(format!("Access denied: {} was not trusted", root.display()));
+    }
+    trust.open_workspace(&root, &config_manager)?;
+    Ok(root.to_string_lossy().to_string())
+}
+
+// Ask the user to allow access to a path outside the workspaces, such as
+// the destination of a clone. The user is shown the path with symlinks
+// resolved, which is what gets trusted.
+#[command]
+pub async fn approve_path(
+    app: AppHandle,
+    trust: State<'_, WorkspaceTrust>,
+    config_manager: State<'_, ConfigManager>,
+    path: String,
+) -> Result<String, String> {
+    let resolved = resolve_path(Path::new(&path))?;
+    if !confirm_trust(app, &resolved).await? {
+        return Err(format!("Access denied: {} was not trusted", resolved.display()));
+    }
+    // A symlink changed while the dialog was open would otherwise trust a
+    // path the user never saw
+    if resolve_path(&resolved)? != resolved {
+        return Err(format!("Access denied: {} changed while asking", resolved.display()));
+    }
+    trust.approve(&resolved, &config_manager)?;
+    Ok(resolved.to_string_lossy().to_string())
+}
+
+#[command]
+pub async fn revoke_trust(
+    trust: State<'_, WorkspaceTrust>,
+    config_manager: State<'_, ConfigManager>,
+    path: String,
+) -> Result<(), String> {
+    trust.revoke(Path::new(&path), &config_manager)
+}
+
+#[command]
+pub async fn get_workspace_trust(trust: State<'_, WorkspaceTrust>) -> Result<TrustInfo, String> {
+    Ok(trust.info())
+}
+
+// The question is asked by a native dialog, which a script in the webview
+// cannot answer
+async fn confirm_trust(app: AppHandle, path: &Path) -> Result<bool, String> {
+    let message = format!(
+        "Allow TurkCode to read and change the files in {}?\n\nOnly trust folders whose contents you know.",
+        path.display()
+    );
+    tauri::async_runtime::spawn_blocking(move || {
+        app.dialog()
+            .message(message)
+            .title("Trust this folder?")
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom("Trust".into(), "Cancel".into()))
+            .blocking_show()
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
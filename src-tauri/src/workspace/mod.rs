@@ -0,0 +1,266 @@
+pub mod commands;
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::config::ConfigManager;
+use crate::utils::find_project_root;
+
+// Denied operations kept for the UI, most recent last
+const DENIED_LIMIT: usize = 100;
+// Symlinks followed when resolving a path, as the OS does
+const MAX_SYMLINKS: usize = 40;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeniedAccess {
+    pub operation: String,
+    pub path: String,
+    pub time: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrustInfo {
+    // Workspaces opened in this session and recent projects
+    pub workspaces: Vec<String>,
+    // Other paths the user approved
+    pub approved: Vec<String>,
+    pub denied: Vec<DeniedAccess>,
+}
+
+// Paths the backend may touch on behalf of the webview: opened workspaces,
+// recent projects and paths the user approved. Paths are compared with
+// symlinks resolved, so a link inside a workspace cannot lead out of it.
+#[derive(Clone)]
+pub struct WorkspaceTrust {
+    workspaces: Arc<Mutex<Vec<PathBuf>>>,
+    approved: Arc<Mutex<Vec<PathBuf>>>,
+    denied: Arc<Mutex<VecDeque<DeniedAccess>>>,
+}
+
+impl WorkspaceTrust {
+    pub fn new(config_manager: &ConfigManager) -> Self {
+        let config = config_manager.get_config();
+        // Paths that no longer exist are left out until opened again
+        let resolve_all = |paths: &[String]| -> Vec<PathBuf> {
+            paths.iter().filter_map(|path| fs::canonicalize(path).ok()).collect()
+        };
+
+        WorkspaceTrust {
+            workspaces: Arc::new(Mutex::new(resolve_all(&config.recent_projects))),
+            approved: Arc::new(Mutex::new(resolve_all(&config.workspace.trusted_paths))),
+            denied: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    // Allow `operation` on `path` only inside a trusted path. Denied
+    // operations are logged.
+    pub fn check(&self, operation: &str, path: &Path) -> Result<(), String> {
+        if self.is_trusted(path) {
+            return Ok(());
+        }
+
+        log::warn!("Denied {} on {}: outside the trusted workspaces", operation, path.display());
+        let mut denied = self.denied.lock().unwrap();
+        denied.push_back(DeniedAccess {
+            operation: operation.to_string(),
+            path: path.to_string_lossy().to_string(),
+            time: chrono::Utc::now().to_rfc3339(),
+        });
+        while denied.len() > DENIED_LIMIT {
+            denied.pop_front();
+        }
+        Err(format!("Access denied: {} is outside the trusted workspaces", path.display()))
+    }
+
+    pub fn is_trusted(&self, path: &Path) -> bool {
+        let resolved = match resolve_path(path) {
+            Ok(resolved) => resolved,
+            Err(_) => return false,
+        };
+        let workspaces = self.workspaces.lock().unwrap();
+        let approved = self.approved.lock().unwrap();
+        workspaces.iter().chain(approved.iter()).any(|root| resolved.starts_with(root))
+    }
+
+    // Trust a workspace root and remember it as a recent project
+    pub fn open_workspace(&self, root: &Path, config_manager: &ConfigManager) -> Result<(), String> {
+        add_unique(&self.workspaces, root);
+        config_manager.add_recent_project(root)
+    }
+
+    // Trust a path the user approved, which need not exist yet, e.g. the
+    // destination of a clone
+    pub fn approve(&self, path: &Path, config_manager: &ConfigManager) -> Result<PathBuf, String> {
+        let resolved = resolve_path(path)?;
+        add_unique(&self.approved, &resolved);
+        config_manager.add_trusted_path(&resolved)?;
+        Ok(resolved)
+    }
+
+    // Stop trusting a workspace or approved path
+    pub fn revoke(&self, path: &Path, config_manager: &ConfigManager) -> Result<(), String> {
+        let resolved = resolve_path(path)?;
+        self.workspaces.lock().unwrap().retain(|root| *root != resolved);
+        self.approved.lock().unwrap().retain(|root| *root != resolved);
+        config_manager.remove_recent_project(&resolved)?;
+        config_manager.remove_trusted_path(&resolved)
+    }
+
+    pub fn info(&self) -> TrustInfo {
+        let display = |paths: &[PathBuf]| paths.iter().map(|path| path.to_string_lossy().to_string()).collect();
+        TrustInfo {
+            workspaces: display(&self.workspaces.lock().unwrap()),
+            approved: display(&self.approved.lock().unwrap()),
+            denied: self.denied.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
+
+// The root of the project `path` belongs to, with symlinks resolved
+pub fn workspace_root(path: &Path) -> Result<PathBuf, String> {
+    let path = fs::canonicalize(path).map_err(|e| e.to_string())?;
+    if !path.is_dir() {
+        return Err(format!("Path is not a directory: {}", path.display()));
+    }
+
+    // A marker in the home directory or at the file system root would
+    // otherwise trust everything below it
+    Ok(find_project_root(&path)
+        .filter(|root| !is_home_or_root(root))
+        .unwrap_or(path))
+}
+
+fn add_unique(paths: &Mutex<Vec<PathBuf>>, path: &Path) {
+    let mut paths = paths.lock().unwrap();
+    if !paths.iter().any(|known| known == path) {
+        paths.push(path.to_path_buf());
+    }
+}
+
+fn is_home_or_root(path: &Path) -> bool {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .and_then(|home| fs::canonicalize(home).ok());
+    path.parent().is_none() || home.is_some_and(|home| home.starts_with(path))
+}
+
+// The real path of `path`, with symlinks resolved. For a path that does not
+// exist yet, such as a file about to be created, its closest existing
+// ancestor is resolved and the rest appended.
+pub fn resolve_path(path: &Path) -> Result<PathBuf, String> {
+    resolve_with_limit(path, MAX_SYMLINKS)
+}
+
+fn resolve_with_limit(path: &Path, symlinks: usize) -> Result<PathBuf, String> {
+    let invalid = || format!("Cannot resolve path: {}", path.display());
+    let mut existing = path.to_path_buf();
+    let mut missing = Vec::new();
+
+    loop {
+        if let Ok(resolved) = fs::canonicalize(&existing) {
+            return Ok(missing.iter().rev().fold(resolved, |resolved, name| resolved.join(name)));
+        }
+
+        // A dangling symlink still points somewhere, which is what counts
+        let is_symlink = existing.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink());
+        if is_symlink {
+            if symlinks == 0 {
+                return Err(invalid());
+            }
+            let target = fs::read_link(&existing).map_err(|e| e.to_string())?;
+            let parent = existing.parent().unwrap_or(Path::new(""));
+            let resolved = resolve_with_limit(&parent.join(target), symlinks - 1)?;
+            return Ok(missing.iter().rev().fold(resolved, |resolved, name| resolved.join(name)));
+        }
+
+        // `..` after a missing directory cannot be resolved, so such paths
+        // are refused rather than guessed at
+        let name = existing.file_name().ok_or_else(invalid)?.to_os_string();
+        missing.push(name);
+        existing = existing
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .ok_or_else(invalid)?
+            .to_path_buf();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Trust that only covers `root`, without touching the settings. Also
+    // used by the tests of the file operations.
+    pub fn trusting(root: &Path) -> WorkspaceTrust {
+        WorkspaceTrust {
+            workspaces: Arc::new(Mutex::new(vec![fs::canonicalize(root).unwrap()])),
+            approved: Arc::new(Mutex::new(Vec::new())),
+            denied: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    #[test]
+    fn missing_paths_resolve_below_their_existing_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+
+        assert_eq!(resolve_path(&root.join("new/file.txt")).unwrap(), root.join("new/file.txt"));
+        assert!(resolve_path(&root.join("missing/../../etc")).is_err());
+    }
+
+    #[test]
+    fn paths_outside_the_workspace_are_denied_and_logged() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        fs::create_dir(&workspace).unwrap();
+        let trust = trusting(&workspace);
+
+        assert!(trust.check("read_file", &workspace.join("src/main.rs")).is_ok());
+        assert!(trust.check("read_file", &workspace.join("../outside.txt")).is_err());
+        assert!(trust.check("read_file", &dir.path().join("workspace-other/a.txt")).is_err());
+
+        let denied = trust.info().denied;
+        assert_eq!(denied.len(), 2);
+        assert_eq!(denied[0].operation, "read_file");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_lead_out_of_the_workspace() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        let outside = dir.path().join("outside");
+        fs::create_dir(&workspace).unwrap();
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("secret.txt"), "secret").unwrap();
+        symlink(&outside, workspace.join("escape")).unwrap();
+        symlink(outside.join("not-yet"), workspace.join("dangling")).unwrap();
+        symlink(workspace.join("inner"), workspace.join("link")).unwrap();
+        fs::create_dir(workspace.join("inner")).unwrap();
+        let trust = trusting(&workspace);
+
+        assert!(!trust.is_trusted(&workspace.join("escape/secret.txt")));
+        // A file created through the link would land outside as well
+        assert!(!trust.is_trusted(&workspace.join("escape/new.txt")));
+        assert!(!trust.is_trusted(&workspace.join("dangling")));
+        assert!(!trust.is_trusted(&workspace.join("dangling/below.txt")));
+        assert!(trust.is_trusted(&workspace.join("link/file.txt")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_do_not_resolve() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        symlink(dir.path().join("b"), dir.path().join("a")).unwrap();
+        symlink(dir.path().join("a"), dir.path().join("b")).unwrap();
+
+        assert!(resolve_path(&dir.path().join("a/file.txt")).is_err());
+        assert!(!trusting(dir.path()).is_trusted(&dir.path().join("a")));
+    }
+}
@@ -1,6 +1,5 @@
 import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { open } from '@tauri-apps/plugin-dialog';
 import { FolderIcon, FileIcon, ChevronRightIcon, ChevronDownIcon } from './icons';
 
 interface FileInfo {
@@ -21,18 +20,15 @@ const FileExplorer = ({ onFileSelect }: FileExplorerProps) => {
     const [expandedFolders, setExpandedFolders] = useState<Record<string, boolean>>({});
     const [isLoading, setIsLoading] = useState(false);
 
-    // Open a folder
+    // Open a folder. The backend shows the picker, since only folders the
+    // user picked there are trusted as workspaces.
     const handleOpenFolder = async () => {
         try {
-            const selected = await open({
-                directory: true,
-                multiple: false,
-                title: 'Select a project folder',
-            });
+            const root = await invoke<string | null>('pick_workspace');
 
-            if (selected && typeof selected === 'string') {
-                setCurrentPath(selected);
-                loadFiles(selected);
+            if (root) {
+                setCurrentPath(root);
+                loadFiles(root);
             }
         } catch (err) {
             console.error('Error opening folder:', err);